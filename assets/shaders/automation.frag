@@ -0,0 +1,18 @@
+#version 410
+
+layout(location = 0) in float v_Slope;
+layout(location = 1) in float v_WorldY;
+layout(location = 2) in float v_Edge;
+layout(location = 0) out vec4 f_Color;
+
+uniform vec4 u_Color;
+// In pixels, see timeline_tone.geom.
+uniform float u_LineWidth;
+uniform float u_Feather;
+
+void main() {
+    f_Color = u_Color;
+
+    float half_width = u_LineWidth * 0.5;
+    f_Color.a *= 1.0 - smoothstep(half_width - u_Feather * 0.5, half_width + u_Feather * 0.5, abs(v_Edge));
+}
\ No newline at end of file
@@ -0,0 +1,43 @@
+#version 410
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 f_Color;
+
+uniform vec2 u_ViewOffset;
+uniform vec2 u_ViewScale;
+// Quarter note beats per bar.
+uniform float u_BarBeats;
+uniform float u_Subdivisions;
+
+uniform vec4 u_BarColor;
+uniform vec4 u_BeatColor;
+uniform vec4 u_SubdivisionColor;
+uniform float u_BarWidth;
+uniform float u_BeatWidth;
+uniform float u_SubdivisionWidth;
+
+// Lines closer together than this many pixels fade out, so zooming out doesn't turn the grid into a solid fill.
+const float MIN_SPACING = 4.0;
+const float FULL_SPACING = 12.0;
+
+// How much of a `width` pixels wide line every `period` beats covers this fragment.
+float line_coverage(float beat, float period, float width) {
+    float pixel_beats = fwidth(beat);
+    float distance_pixels = abs(beat - round(beat / period) * period) / pixel_beats;
+    float spacing = smoothstep(MIN_SPACING, FULL_SPACING, period / pixel_beats);
+
+    return clamp(width * 0.5 - distance_pixels + 0.5, 0.0, 1.0) * spacing;
+}
+
+void main() {
+    float beat = v_TexCoord.x * u_ViewScale.x + u_ViewOffset.x;
+
+    vec4 color = vec4(u_SubdivisionColor.rgb, u_SubdivisionColor.a * line_coverage(beat, 1.0 / u_Subdivisions, u_SubdivisionWidth));
+    float beat_coverage = line_coverage(beat, 1.0, u_BeatWidth);
+    color = mix(color, u_BeatColor, beat_coverage);
+    float bar_coverage = line_coverage(beat, u_BarBeats, u_BarWidth);
+    color = mix(color, u_BarColor, bar_coverage);
+
+    if (color.a <= 0.0) discard;
+    f_Color = color;
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+#version 410
+layout(location = 0) out vec4 f_Color;
+
+uniform vec4 u_Color;
+
+void main() {
+    f_Color = u_Color;
+}
\ No newline at end of file
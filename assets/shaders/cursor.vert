@@ -0,0 +1,17 @@
+#version 410
+
+layout(location = 0) in float a_PositionY;
+
+uniform vec2 u_ViewOffset, u_ViewScale;
+uniform vec2 u_Position;
+uniform vec2 u_HalfSize;
+uniform vec2 u_Axis;
+
+void main() {
+    gl_Position = vec4(u_Position, 0.0, 1.0);
+    gl_Position.xy -= u_ViewOffset;
+    gl_Position.xy /= u_ViewScale;
+    gl_Position.xy = gl_Position.xy * 2.0 - 1.0;
+
+    gl_Position.xy += u_Axis * a_PositionY * u_HalfSize;
+}
\ No newline at end of file
@@ -0,0 +1,17 @@
+#version 410
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 f_Color;
+
+uniform vec2 u_ViewOffset;
+uniform vec2 u_ViewScale;
+// First and last beat of the region.
+uniform vec2 u_Region;
+uniform vec4 u_Color;
+
+void main() {
+    float beat = v_TexCoord.x * u_ViewScale.x + u_ViewOffset.x;
+    if (beat < u_Region.x || beat > u_Region.y) discard;
+
+    f_Color = u_Color;
+}
\ No newline at end of file
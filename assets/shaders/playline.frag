@@ -5,9 +5,11 @@ layout(location = 0) in float v_TexCoordY;
 layout(location = 0) out vec4 f_Color;
 
 uniform float u_Time;
+uniform vec4 u_BaseColor;
+uniform vec4 u_Color;
 
 void main() {
     float multiplier = sin(u_Time * PI * 0.5) * 0.5 + 0.5;
     multiplier = multiplier * 0.7 + 0.3;
-    f_Color = mix(vec4(0.16, 1.0, 0.1, 0.2), vec4(0.4, 1.0, 0.16, 1.0), v_TexCoordY * multiplier);
+    f_Color = mix(u_BaseColor, u_Color, v_TexCoordY * multiplier);
 }
\ No newline at end of file
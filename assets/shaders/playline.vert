@@ -1,16 +1,19 @@
 #version 410
 
-layout(location = 0) in float a_PositionY;
+layout(location = 0) in vec2 a_Position;
 layout(location = 0) out float v_TexCoordY;
 
 uniform float u_ViewOffset, u_ViewScale;
 uniform float u_Time;
+// Half the line width in clip space.
+uniform float u_HalfWidth;
 
 void main() {
-    gl_Position = vec4(u_Time, a_PositionY, 0.0, 1.0);
+    gl_Position = vec4(u_Time, a_Position.y, 0.0, 1.0);
     gl_Position.x -= u_ViewOffset;
     gl_Position.x /= u_ViewScale;
     gl_Position.x = gl_Position.x * 2.0 - 1.0;
+    gl_Position.x += a_Position.x * u_HalfWidth;
 
-    v_TexCoordY = a_PositionY * 0.5 + 0.5;
+    v_TexCoordY = a_Position.y * 0.5 + 0.5;
 }
\ No newline at end of file
@@ -0,0 +1,40 @@
+#version 410
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 f_Color;
+
+uniform vec2 u_ViewOffset;
+uniform vec2 u_ViewScale;
+// Height of the strip along the top edge, as a fraction of the screen.
+uniform float u_StripHeight;
+// Quarter note beats between labeled ticks and between the short ticks in between.
+uniform float u_LabelBeats;
+uniform float u_TickBeats;
+
+uniform vec4 u_BackgroundColor;
+uniform vec4 u_TickColor;
+
+// Same fading as the beat grid, ticks closer together than this many pixels disappear.
+const float MIN_SPACING = 4.0;
+const float FULL_SPACING = 12.0;
+
+float tick_coverage(float beat, float period) {
+    float pixel_beats = fwidth(beat);
+    float distance_pixels = abs(beat - round(beat / period) * period) / pixel_beats;
+    float spacing = smoothstep(MIN_SPACING, FULL_SPACING, period / pixel_beats);
+
+    return clamp(1.0 - distance_pixels, 0.0, 1.0) * spacing;
+}
+
+void main() {
+    float beat = v_TexCoord.x * u_ViewScale.x + u_ViewOffset.x;
+    // 0 at the top edge, 1 at the bottom of the strip.
+    float depth = (1.0 - v_TexCoord.y) / u_StripHeight;
+
+    float label_coverage = tick_coverage(beat, u_LabelBeats);
+    // Short ticks only reach up a third of the strip from its bottom.
+    float tick_coverage = tick_coverage(beat, u_TickBeats) * step(2.0 / 3.0, depth);
+    if (depth > 1.0) discard;
+
+    f_Color = mix(u_BackgroundColor, u_TickColor, max(label_coverage, tick_coverage));
+}
@@ -0,0 +1,28 @@
+#version 410
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 f_Color;
+
+uniform vec2 u_ViewOffset;
+uniform vec2 u_ViewScale;
+
+uniform vec4 u_SemitoneColor;
+uniform vec4 u_OctaveColor;
+uniform float u_SemitoneWidth;
+uniform float u_OctaveWidth;
+
+void main() {
+    // Whole pitches sit half a semitone above whole world heights, matching the pitch mapping of the rendered audio.
+    float pitch = v_TexCoord.y * u_ViewScale.y + u_ViewOffset.y + 0.5;
+    float nearest_pitch = floor(pitch + 0.5);
+    float distance_pixels = abs(pitch - nearest_pitch) / fwidth(pitch);
+
+    bool is_octave = mod(nearest_pitch, 12.0) == 0.0;
+    vec4 color = is_octave ? u_OctaveColor : u_SemitoneColor;
+    float width = is_octave ? u_OctaveWidth : u_SemitoneWidth;
+
+    float coverage = clamp(width * 0.5 - distance_pixels + 0.5, 0.0, 1.0);
+    if (coverage <= 0.0) discard;
+
+    f_Color = vec4(color.rgb, color.a * coverage);
+}
\ No newline at end of file
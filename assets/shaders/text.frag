@@ -0,0 +1,14 @@
+#version 410
+layout(location = 0) out vec4 f_Color;
+
+in vec2 v_TexCoord;
+
+uniform sampler2D u_FontSampler;
+uniform vec4 u_Color;
+
+void main() {
+    float coverage = texture(u_FontSampler, v_TexCoord).a;
+    if (coverage <= 0.0) discard;
+
+    f_Color = vec4(u_Color.rgb, u_Color.a * coverage);
+}
\ No newline at end of file
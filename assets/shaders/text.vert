@@ -0,0 +1,18 @@
+#version 410
+
+layout(location = 0) in vec2 a_Position;
+layout(location = 1) in vec2 a_Offset;
+layout(location = 2) in float a_Glyph;
+
+uniform vec2 u_ScreenSize;
+uniform vec2 u_GlyphSize;
+uniform float u_GlyphCount;
+
+out vec2 v_TexCoord;
+
+void main() {
+    vec2 corner = a_Position * 0.5 + 0.5;
+
+    gl_Position = vec4((a_Offset + corner * u_GlyphSize) / u_ScreenSize * 2.0 - 1.0, 0.0, 1.0);
+    v_TexCoord = vec2((a_Glyph + corner.x) / u_GlyphCount, corner.y);
+}
\ No newline at end of file
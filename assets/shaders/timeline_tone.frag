@@ -1,6 +1,61 @@
 #version 410
+
+layout(location = 0) in float v_Slope;
+layout(location = 1) in float v_WorldY;
+layout(location = 2) in float v_Edge;
 layout(location = 0) out vec4 f_Color;
 
+// Semitones per beat where a line starts and stops counting as a glide.
+const float GLIDE_START = 0.5;
+const float GLIDE_FULL = 8.0;
+
+// Pitches the spectrum ramp spreads its hues over, 0 is C5.
+const float SPECTRUM_LOW = -36.0;
+const float SPECTRUM_HIGH = 24.0;
+
+// Bit n is set when pitch class n (0 is C) is in the scale.
+uniform int u_ScaleMask;
+uniform bool u_HighlightOutOfScale;
+// 0 plain, 1 spectrum, 2 chroma, 3 ice.
+uniform int u_ColorRamp;
+// In pixels, see timeline_tone.geom.
+uniform float u_LineWidth;
+uniform float u_Feather;
+
+vec3 hue_to_rgb(float hue) {
+    vec3 rgb = clamp(abs(mod(hue * 6.0 + vec3(0.0, 4.0, 2.0), 6.0) - 3.0) - 1.0, 0.0, 1.0);
+    return mix(vec3(1.0), rgb, 0.65);
+}
+
+vec3 ramp_color(float pitch) {
+    if (u_ColorRamp == 1) {
+        // Blue for low notes through green to yellow for high ones, red stays free for warnings.
+        float t = clamp((pitch - SPECTRUM_LOW) / (SPECTRUM_HIGH - SPECTRUM_LOW), 0.0, 1.0);
+        return hue_to_rgb(mix(0.66, 0.15, t));
+    }
+    if (u_ColorRamp == 2) {
+        // Every pitch class its own hue, the same in each octave.
+        return hue_to_rgb(mod(floor(pitch + 0.5), 12.0) / 12.0);
+    }
+    if (u_ColorRamp == 3) {
+        float t = clamp((pitch - SPECTRUM_LOW) / (SPECTRUM_HIGH - SPECTRUM_LOW), 0.0, 1.0);
+        return mix(vec3(0.2, 0.45, 0.6), vec3(1.0), t);
+    }
+    return vec3(1.0);
+}
+
 void main() {
-    f_Color = vec4(1.0, 1.0, 1.0, 0.7);
+    float pitch = v_WorldY + 0.5;
+
+    float glide = smoothstep(GLIDE_START, GLIDE_FULL, v_Slope);
+    f_Color = mix(vec4(ramp_color(pitch), 0.7), vec4(1.0, 0.55, 0.2, 0.9), glide);
+
+    // Checked per fragment, so only the parts of a slope that pass through wrong notes light up.
+    int pitch_class = int(mod(floor(pitch + 0.5), 12.0));
+    if (u_HighlightOutOfScale && (u_ScaleMask & (1 << pitch_class)) == 0) {
+        f_Color = vec4(1.0, 0.2, 0.25, 0.9);
+    }
+
+    float half_width = u_LineWidth * 0.5;
+    f_Color.a *= 1.0 - smoothstep(half_width - u_Feather * 0.5, half_width + u_Feather * 0.5, abs(v_Edge));
 }
\ No newline at end of file
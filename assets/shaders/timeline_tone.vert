@@ -2,12 +2,19 @@
 
 layout(location = 0) in float a_Mix;
 layout(location = 1) in vec4 a_Transform;
+layout(location = 3) in float a_Slope;
+
+layout(location = 0) out float v_Slope;
+layout(location = 1) out float v_WorldY;
 
 uniform vec2 u_ViewOffset, u_ViewScale;
 
 void main() {
     gl_Position = vec4(mix(a_Transform.xy, a_Transform.zw, a_Mix), 0.0, 1.0);
+    v_WorldY = gl_Position.y;
     gl_Position.xy -= u_ViewOffset;
     gl_Position.xy /= u_ViewScale;
     gl_Position.xy = gl_Position.xy * 2.0 - 1.0;
+
+    v_Slope = a_Slope;
 }
\ No newline at end of file
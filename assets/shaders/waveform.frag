@@ -0,0 +1,6 @@
+#version 410
+layout(location = 0) out vec4 f_Color;
+
+void main() {
+    f_Color = vec4(0.4, 0.7, 1.0, 0.5);
+}
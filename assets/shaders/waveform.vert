@@ -0,0 +1,15 @@
+#version 410
+
+layout(location = 0) in vec2 a_Position;
+
+uniform float u_ViewOffset, u_ViewScale;
+uniform float u_StripHeight;
+
+void main() {
+    gl_Position = vec4(a_Position, 0.0, 1.0);
+    gl_Position.x -= u_ViewOffset;
+    gl_Position.x /= u_ViewScale;
+    gl_Position.x = gl_Position.x * 2.0 - 1.0;
+
+    gl_Position.y = -1.0 + u_StripHeight * (1.0 + a_Position.y);
+}
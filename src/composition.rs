@@ -0,0 +1,2580 @@
+use core::f32;
+use std::{borrow::Cow, cmp::Ordering, collections::{hash_map::DefaultHasher, HashMap}, hash::{Hash, Hasher}, ops::Range, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::Duration};
+
+use nalgebra::{Point2, Vector2};
+use hound::{SampleFormat, WavReader};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rodio::Source;
+
+use crate::{effects::{Bitcrusher, BitcrusherState, Compressor, CompressorState, Delay, DelayState, Reverb, ReverbState, Tremolo, TremoloState}, timeline::TimelineConfig, tuning::{self, Scale, Temperament}};
+
+#[derive(Default, PartialEq)]
+enum RecordDirection {
+    #[default] Undefined,
+    Right,
+    Left,
+}
+
+#[derive(Default)]
+struct Record {
+    direction: RecordDirection,
+    clamp_x: f32,
+}
+
+/// Uniform world space grid over line indices, so finding the lines near a point doesn't scan every line.
+#[derive(Default)]
+struct LineIndex {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+impl LineIndex {
+    /// Width in beats and height in semitones of a cell.
+    const CELL_SIZE: Vector2<f32> = Vector2::new(0.5, 1.0);
+    /// Lines covering more cells than this are only put in the cells of their ends.
+    const MAX_CELLS_PER_LINE: i64 = 1024;
+
+    fn cell(point: Point2<f32>) -> (i32, i32) {
+        ((point.x / Self::CELL_SIZE.x).floor() as i32, (point.y / Self::CELL_SIZE.y).floor() as i32)
+    }
+    fn cells_of(line: &Line) -> Vec<(i32, i32)> {
+        let (start, end) = (Self::cell(line.start), Self::cell(line.end));
+        let (min_x, max_x) = (start.0.min(end.0), start.0.max(end.0));
+        let (min_y, max_y) = (start.1.min(end.1), start.1.max(end.1));
+
+        if (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1) > Self::MAX_CELLS_PER_LINE {
+            return if start == end { vec![start] } else { vec![start, end] };
+        }
+        (min_x..=max_x).flat_map(|x| (min_y..=max_y).map(move |y| (x, y))).collect()
+    }
+
+    fn insert(&mut self, index: usize, line: &Line) {
+        for cell in Self::cells_of(line) {
+            self.cells.entry(cell).or_default().push(index);
+        }
+    }
+    fn remove(&mut self, index: usize, line: &Line) {
+        for cell in Self::cells_of(line) {
+            if let Some(indices) = self.cells.get_mut(&cell) {
+                indices.retain(|other| *other != index);
+                if indices.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    fn query_near(&self, lines: &[Line], point: Point2<f32>, radius: f32) -> Vec<usize> {
+        let offset = Vector2::new(radius, radius);
+        let (min, max) = (Self::cell(point - offset), Self::cell(point + offset));
+
+        let mut found: Vec<usize> = (min.0..=max.0)
+            .flat_map(|x| (min.1..=max.1).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|index| lines[*index].distance_to(point) <= radius)
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+
+        found
+    }
+}
+
+/// Synth settings a record was drawn with, what's `None` falls back to its track.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StrokeParams {
+    pub instrument: Option<Instrument>,
+    /// Loudness before the velocity falloff, from 0 to 1, see [Composition::BASE_AMPLITUDE].
+    pub amplitude: Option<f32>,
+}
+
+/// An undoable change to a [RecordSystem].
+enum Edit {
+    /// A record was added after the others. Records are only ever added at the end, so undoing removes the last one.
+    Append,
+    /// A record was removed from `index`.
+    Remove { index: usize, lines: Vec<Line>, params: StrokeParams },
+    /// Every record was replaced at once, these are the lines and records from before.
+    Replace { lines: Vec<Line>, history: Vec<Range<usize>>, params: Vec<StrokeParams> },
+    /// The lines of the record at `index` changed, these are the ones from before.
+    Change { index: usize, lines: Vec<Line> },
+}
+/// An undone [Edit] with what's needed to make it again.
+enum Redo {
+    Append(Vec<Line>, StrokeParams),
+    Remove(usize),
+    Replace { lines: Vec<Line>, history: Vec<Range<usize>>, params: Vec<StrokeParams> },
+    Change { index: usize, lines: Vec<Line> },
+}
+
+#[derive(Default)]
+pub struct RecordSystem {
+    /// Lines of every record, in the order they were drawn.
+    lines: Vec<Line>,
+    /// Range of `lines` covered by each record.
+    history: Vec<Range<usize>>,
+    /// Synth settings of each record, parallel to `history`.
+    params: Vec<StrokeParams>,
+    /// Newest last, at most `undo_limit` of them.
+    edits: Vec<Edit>,
+    /// 0 keeps every edit.
+    undo_limit: usize,
+    redo_history: Vec<Redo>,
+    line_index: LineIndex,
+    /// Index of the first line that changed since the last [Self::take_dirty_from].
+    dirty_from: Option<usize>,
+
+    current: Record,
+}
+impl RecordSystem {
+    /// Starts a record drawn with `params`.
+    pub fn new_record(&mut self, params: StrokeParams) {
+        self.current = Record::default();
+        self.redo_history.clear();
+
+        self.pop_empty_record();
+        self.history.push(self.lines.len()..self.lines.len());
+        self.params.push(params);
+    }
+    /// Drops the last record if it has no lines, like the one a click without dragging leaves.
+    fn pop_empty_record(&mut self) -> bool {
+        let is_empty = self.history.last().is_some_and(Range::is_empty);
+        if is_empty {
+            self.history.pop();
+            self.params.pop();
+        }
+        is_empty
+    }
+    /// Once there are more than `undo_limit` edits the oldest ones are dropped, what they did stays.
+    fn push_edit(&mut self, edit: Edit) {
+        self.edits.push(edit);
+        self.drop_old_edits();
+    }
+    fn drop_old_edits(&mut self) {
+        if self.undo_limit > 0 && self.edits.len() > self.undo_limit {
+            self.edits.drain(..self.edits.len() - self.undo_limit);
+        }
+    }
+    /// Forgets every edit, what they did stays. Nothing can be undone or redone afterwards.
+    pub fn clear_undo_history(&mut self) {
+        self.edits.clear();
+        self.redo_history.clear();
+    }
+    pub const fn get_undo_limit(&self) -> usize {
+        self.undo_limit
+    }
+    /// How many edits can be undone, 0 for no limit. Going below the current count drops the oldest ones right away.
+    pub fn set_undo_limit(&mut self, undo_limit: usize) {
+        self.undo_limit = undo_limit;
+        self.drop_old_edits();
+    }
+    fn mark_dirty(&mut self, from: usize) {
+        self.dirty_from = Some(self.dirty_from.map_or(from, |dirty_from| dirty_from.min(from)));
+    }
+    /// Index of the first line that changed since the last call, lines after it may have moved or been removed.
+    pub fn take_dirty_from(&mut self) -> Option<usize> {
+        self.dirty_from.take()
+    }
+
+    pub fn get_lines(&self) -> &[Line] {
+        &self.lines
+    }
+    pub fn records(&self) -> impl Iterator<Item = &[Line]> {
+        self.history.iter().map(|range| &self.lines[range.clone()])
+    }
+    /// Synth settings of each of [Self::records].
+    pub fn get_record_params(&self) -> &[StrokeParams] {
+        &self.params
+    }
+    /// Records with at least one line.
+    pub fn get_record_count(&self) -> usize {
+        self.history.iter().filter(|range| !range.is_empty()).count()
+    }
+    pub fn get_line_count(&self) -> usize {
+        self.lines.len()
+    }
+    /// Smallest and largest corner around every line, x in beats and y in semitones, `None` when there are no lines.
+    pub fn get_bounds(&self) -> Option<(Point2<f32>, Point2<f32>)> {
+        bounds(self.lines.iter())
+    }
+
+    /// Indices into [Self::get_lines] of the lines passing within `radius` world units of `point`, in ascending order.
+    pub fn query_near(&self, point: Point2<f32>, radius: f32) -> Vec<usize> {
+        self.line_index.query_near(&self.lines, point, radius)
+    }
+    pub fn add_line(&mut self, start: Point2<f32>, end: Point2<f32>, delta_secs: f32) -> Option<&Line> {
+        let velocity = if delta_secs > 0.0 { (end - start).magnitude() / delta_secs } else { 0.0 };
+        let line = Line { start, end, velocity };
+        if self.current.direction == RecordDirection::Undefined {
+            if start.x < end.x {
+                self.current.direction = RecordDirection::Right;
+                self.current.clamp_x = 0.0;
+            } else {
+                self.current.direction = RecordDirection::Left;
+                self.current.clamp_x = f32::INFINITY;
+            }
+        }
+
+        if end.x - start.x > 0.005 {
+            if self.current.direction == RecordDirection::Left {
+                self.new_record(self.params.last().cloned().unwrap_or_default());
+                self.current.direction = RecordDirection::Right;
+                self.current.clamp_x = f32::INFINITY;
+            }
+        } else if end.x - start.x < -0.005 && self.current.direction == RecordDirection::Right {
+            self.new_record(self.params.last().cloned().unwrap_or_default());
+            self.current.direction = RecordDirection::Left;
+            self.current.clamp_x = 0.0;
+        }
+        
+        if !self.history.is_empty() {
+            if self.history.last().is_some_and(Range::is_empty) {
+                self.push_edit(Edit::Append);
+            }
+
+            self.mark_dirty(self.lines.len());
+            self.line_index.insert(self.lines.len(), &line);
+            self.lines.push(line);
+            if let Some(last) = self.history.last_mut() {
+                last.end = self.lines.len();
+            }
+
+            return self.lines.last();
+        }
+
+        None
+    }
+
+    /// Appends a finished record as a new edit, discarding the redo history.
+    pub fn add_record(&mut self, lines: Vec<Line>, params: StrokeParams) {
+        self.redo_history.clear();
+        self.pop_empty_record();
+        self.push_record(lines, params);
+    }
+    /// Appends a finished record, like drawing it line by line would.
+    pub fn push_record(&mut self, lines: Vec<Line>, params: StrokeParams) {
+        let start = self.lines.len();
+        for (i, line) in lines.iter().enumerate() {
+            self.line_index.insert(start + i, line);
+        }
+        if !lines.is_empty() {
+            self.push_edit(Edit::Append);
+        }
+        self.lines.extend(lines);
+        self.history.push(start..self.lines.len());
+        self.params.push(params);
+        self.mark_dirty(start);
+    }
+
+    /// Index of the record with a line passing within `radius` of `point`, the closest one if there are several.
+    /// `radius` is separate per axis, so it can be a fixed size on screen.
+    pub fn find_record_near(&self, point: Point2<f32>, radius: Vector2<f32>) -> Option<usize> {
+        let normalize = |point: Point2<f32>| Point2::new(point.x / radius.x, point.y / radius.y);
+
+        let (line, _) = self.query_near(point, radius.x.max(radius.y))
+            .into_iter()
+            .map(|index| {
+                let line = &self.lines[index];
+                let normalized = Line { start: normalize(line.start), end: normalize(line.end), velocity: line.velocity };
+                (index, normalized.distance_to(normalize(point)))
+            })
+            .filter(|(_, distance)| *distance <= 1.0)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+        Some(self.history.partition_point(|range| range.end <= line))
+    }
+    /// Removes a record as a new edit, discarding the redo history.
+    pub fn remove_record(&mut self, index: usize) {
+        if index < self.history.len() {
+            self.redo_history.clear();
+            self.remove_record_at(index);
+        }
+    }
+    fn remove_record_at(&mut self, index: usize) {
+        let range = self.history.remove(index);
+        let params = self.params.remove(index);
+
+        // Every line after the record moves down, so their indices are updated in the line index too.
+        self.unindex_from(range.start);
+        let lines: Vec<Line> = self.lines.drain(range.clone()).collect();
+        for later in &mut self.history[index..] {
+            *later = later.start - lines.len()..later.end - lines.len();
+        }
+        self.index_from(range.start);
+
+        self.mark_dirty(range.start);
+        self.push_edit(Edit::Remove { index, lines, params });
+    }
+    /// Moves every line of a record by `offset`, as a new edit, discarding the redo history.
+    pub fn move_record(&mut self, index: usize, offset: Vector2<f32>) {
+        let Some(range) = self.history.get(index).cloned() else {
+            return;
+        };
+        self.redo_history.clear();
+
+        let lines = self.lines[range]
+            .iter()
+            .map(|line| Line { start: line.start + offset, end: line.end + offset, velocity: line.velocity })
+            .collect();
+        let lines = self.swap_record_lines(index, lines);
+        self.push_edit(Edit::Change { index, lines });
+    }
+    /// Replaces every record as a single edit, discarding the redo history. Empty records are dropped.
+    pub fn replace_records(&mut self, records: Vec<(Vec<Line>, StrokeParams)>) {
+        self.redo_history.clear();
+
+        let mut lines = Vec::new();
+        let mut history = Vec::new();
+        let mut params = Vec::new();
+        for (record, record_params) in records.into_iter().filter(|(record, _)| !record.is_empty()) {
+            history.push(lines.len()..lines.len() + record.len());
+            lines.extend(record);
+            params.push(record_params);
+        }
+
+        let (lines, history, params) = self.swap_records(lines, history, params);
+        self.push_edit(Edit::Replace { lines, history, params });
+    }
+    /// Removes every record as a single edit, so one undo brings them all back. Discards the redo history.
+    /// Without any records nothing happens, rather than leaving an edit that undoes nothing.
+    pub fn clear(&mut self) {
+        if !self.history.is_empty() {
+            self.replace_records(Vec::new());
+        }
+    }
+    /// Snaps every record to `grid` beats for good like [quantize_lines], as a single edit.
+    pub fn quantize(&mut self, grid: f32) {
+        if grid > 0.0 {
+            let records = self.records()
+                .zip(&self.params)
+                .map(|(lines, params)| (quantize_lines(lines, grid).into_owned(), params.clone()))
+                .collect();
+            self.replace_records(records);
+        }
+    }
+    /// Merges neighbouring records into one, as a single edit, and returns how many records fewer there are.
+    /// Only records at least `min_gap` beats after the end of the previous one are merged, and with `max_gap` only if
+    /// they're at most that many beats apart. Records with different [StrokeParams] stay apart.
+    /// No line moves, a merged record just plays as one voice.
+    pub fn consolidate(&mut self, min_gap: f32, max_gap: Option<f32>) -> usize {
+        let x_range = |lines: &[Line]| lines.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), line| {
+            (min.min(line.start.x).min(line.end.x), max.max(line.start.x).max(line.end.x))
+        });
+
+        let mut records: Vec<(Vec<Line>, StrokeParams)> = Vec::new();
+        let mut group_end = f32::NEG_INFINITY;
+        for (lines, params) in self.records().zip(&self.params).filter(|(lines, _)| !lines.is_empty()) {
+            let (start, end) = x_range(lines);
+            let gap = start - group_end;
+            match records.last_mut() {
+                Some((group, group_params)) if group_params == params && gap >= min_gap && max_gap.is_none_or(|max_gap| gap <= max_gap) => {
+                    group.extend_from_slice(lines);
+                }
+                _ => records.push((lines.to_vec(), params.clone())),
+            }
+            group_end = end;
+        }
+
+        let merged = self.get_record_count() - records.len();
+        if merged > 0 {
+            self.replace_records(records);
+        }
+        merged
+    }
+    /// Returns the lines, records and their settings that were there before.
+    fn swap_records(
+        &mut self,
+        lines: Vec<Line>,
+        history: Vec<Range<usize>>,
+        params: Vec<StrokeParams>,
+    ) -> (Vec<Line>, Vec<Range<usize>>, Vec<StrokeParams>) {
+        self.unindex_from(0);
+        let previous_lines = std::mem::replace(&mut self.lines, lines);
+        let previous_history = std::mem::replace(&mut self.history, history);
+        let previous_params = std::mem::replace(&mut self.params, params);
+        self.index_from(0);
+
+        self.mark_dirty(0);
+        (previous_lines, previous_history, previous_params)
+    }
+    /// Returns the lines the record had before, `lines` can be a different count.
+    fn swap_record_lines(&mut self, index: usize, lines: Vec<Line>) -> Vec<Line> {
+        let range = self.history[index].clone();
+        let count = lines.len();
+
+        self.unindex_from(range.start);
+        let previous: Vec<Line> = self.lines.splice(range.clone(), lines).collect();
+        self.history[index] = range.start..range.start + count;
+        for later in &mut self.history[index + 1..] {
+            *later = later.start + count - previous.len()..later.end + count - previous.len();
+        }
+        self.index_from(range.start);
+
+        self.mark_dirty(range.start);
+        previous
+    }
+    fn insert_record_at(&mut self, index: usize, lines: Vec<Line>, params: StrokeParams) {
+        let index = index.min(self.history.len());
+        let start = self.history.get(index).map_or(self.lines.len(), |range| range.start);
+        let count = lines.len();
+
+        self.unindex_from(start);
+        self.lines.splice(start..start, lines);
+        for later in &mut self.history[index..] {
+            *later = later.start + count..later.end + count;
+        }
+        self.history.insert(index, start..start + count);
+        self.params.insert(index, params);
+        self.index_from(start);
+
+        self.mark_dirty(start);
+    }
+    fn unindex_from(&mut self, first: usize) {
+        for (index, line) in self.lines.iter().enumerate().skip(first) {
+            self.line_index.remove(index, line);
+        }
+    }
+    fn index_from(&mut self, first: usize) {
+        for (index, line) in self.lines.iter().enumerate().skip(first) {
+            self.line_index.insert(index, line);
+        }
+    }
+
+    /// Empty records left by clicking without dragging are dropped on the way, so undoing a drawing always removes lines.
+    pub fn undo(&mut self) {
+        match self.edits.pop() {
+            Some(Edit::Append) => {
+                while self.pop_empty_record() {}
+
+                if let (Some(range), Some(params)) = (self.history.pop(), self.params.pop()) {
+                    self.unindex_from(range.start);
+                    self.redo_history.push(Redo::Append(self.lines.drain(range.clone()).collect(), params));
+                    self.mark_dirty(range.start);
+                }
+            }
+            Some(Edit::Remove { index, lines, params }) => {
+                self.insert_record_at(index, lines, params);
+                self.redo_history.push(Redo::Remove(index));
+            }
+            Some(Edit::Replace { lines, history, params }) => {
+                let (lines, history, params) = self.swap_records(lines, history, params);
+                self.redo_history.push(Redo::Replace { lines, history, params });
+            }
+            Some(Edit::Change { index, lines }) if index < self.history.len() => {
+                let lines = self.swap_record_lines(index, lines);
+                self.redo_history.push(Redo::Change { index, lines });
+            }
+            _ => {}
+        }
+    }
+    pub fn redo(&mut self) {
+        match self.redo_history.pop() {
+            Some(Redo::Append(lines, params)) => self.push_record(lines, params),
+            Some(Redo::Remove(index)) if index < self.history.len() => self.remove_record_at(index),
+            Some(Redo::Replace { lines, history, params }) => {
+                let (lines, history, params) = self.swap_records(lines, history, params);
+                self.push_edit(Edit::Replace { lines, history, params });
+            }
+            Some(Redo::Change { index, lines }) if index < self.history.len() => {
+                let lines = self.swap_record_lines(index, lines);
+                self.push_edit(Edit::Change { index, lines });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A recorded one-shot played back at the drawn pitch.
+pub struct Sample {
+    path: PathBuf,
+    data: Box<[f32]>,
+    sample_rate: u32,
+    base_frequency: f32,
+}
+impl Sample {
+    /// A4, used when the pitch the sample was recorded at is unknown.
+    pub const DEFAULT_BASE_FREQUENCY: f32 = 440.0;
+    /// Lowest base frequency the setter allows, lower ones would step through the sample absurdly fast.
+    pub const MIN_BASE_FREQUENCY: f32 = 1.0;
+
+    /// Multichannel files are mixed down to mono.
+    pub fn load(path: &Path, base_frequency: f32) -> Result<Self, String> {
+        if !Self::is_valid_base_frequency(base_frequency) {
+            return Err(format!("Sample at: {} has an invalid base frequency: {}.", path.display(), base_frequency));
+        }
+        let reader = WavReader::open(path)
+            .map_err(|error| format!("Failed to open sample at: {}. Error: {}", path.display(), error))?;
+        let spec = reader.spec();
+
+        let samples: Result<Vec<f32>, hound::Error> = match spec.sample_format {
+            SampleFormat::Float => reader.into_samples::<f32>().collect(),
+            SampleFormat::Int => {
+                let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader.into_samples::<i32>().map(|sample| sample.map(|sample| sample as f32 * scale)).collect()
+            }
+        };
+        let samples = samples
+            .map_err(|error| format!("Failed to read sample at: {}. Error: {}", path.display(), error))?;
+
+        let channels = spec.channels.max(1) as usize;
+        let data: Box<[f32]> = samples
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        if data.is_empty() {
+            return Err(format!("Sample at: {} is empty.", path.display()));
+        }
+
+        Ok(Self { path: path.to_path_buf(), data, sample_rate: spec.sample_rate, base_frequency })
+    }
+
+    /// File the sample was loaded from.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub const fn get_base_frequency(&self) -> f32 {
+        self.base_frequency
+    }
+    /// The pitch the sample was recorded at, it plays back unchanged when drawn at this frequency.
+    pub fn set_base_frequency(&mut self, base_frequency: f32) {
+        self.base_frequency = if base_frequency.is_finite() { base_frequency.max(Self::MIN_BASE_FREQUENCY) } else { Self::DEFAULT_BASE_FREQUENCY };
+    }
+    /// The playback rate divides by the base frequency, anything else makes it infinite or NaN.
+    pub fn is_valid_base_frequency(base_frequency: f32) -> bool {
+        base_frequency.is_finite() && base_frequency > 0.0
+    }
+
+    fn get(&self, position: f64) -> f32 {
+        let index = position as usize % self.data.len();
+        let next = (index + 1) % self.data.len();
+        let fraction = position.fract() as f32;
+
+        self.data[index] + (self.data[next] - self.data[index]) * fraction
+    }
+}
+impl std::fmt::Debug for Sample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} frames at {} Hz, base {} Hz", self.data.len(), self.sample_rate, self.base_frequency)
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub enum Instrument {
+    #[default] Sine,
+    Square,
+    Saw,
+    /// White noise through a band-pass filter centered at the drawn frequency.
+    Noise,
+    Sample(Arc<Sample>),
+}
+impl Instrument {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Sine => Self::Square,
+            Self::Square => Self::Saw,
+            Self::Saw => Self::Noise,
+            Self::Noise | Self::Sample(_) => Self::Sine,
+        }
+    }
+}
+/// Samples are only equal to the same loaded sample.
+impl PartialEq for Instrument {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Sample(sample), Self::Sample(other)) => Arc::ptr_eq(sample, other),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Track {
+    record_system: RecordSystem,
+    /// Volume curve strokes, x in beats and y the gain from 0 to 1. They make no sound themselves.
+    automation: RecordSystem,
+    instrument: Instrument,
+    detune_cents: f32,
+}
+impl Track {
+    pub const fn get_record_system(&self) -> &RecordSystem {
+        &self.record_system
+    }
+    pub fn get_record_system_mut(&mut self) -> &mut RecordSystem {
+        &mut self.record_system
+    }
+    pub fn push_record(&mut self, lines: Vec<Line>, params: StrokeParams) {
+        self.record_system.add_record(lines, params);
+    }
+
+    pub const fn get_automation(&self) -> &RecordSystem {
+        &self.automation
+    }
+    pub fn get_automation_mut(&mut self) -> &mut RecordSystem {
+        &mut self.automation
+    }
+    pub fn push_automation(&mut self, lines: Vec<Line>) {
+        self.automation.add_record(lines, StrokeParams::default());
+    }
+    /// Applies [RecordSystem::clear_undo_history] to both the records and the automation.
+    pub fn clear_undo_history(&mut self) {
+        self.record_system.clear_undo_history();
+        self.automation.clear_undo_history();
+    }
+    /// Applies [RecordSystem::set_undo_limit] to both the records and the automation.
+    pub fn set_undo_limit(&mut self, undo_limit: usize) {
+        self.record_system.set_undo_limit(undo_limit);
+        self.automation.set_undo_limit(undo_limit);
+    }
+
+    pub const fn get_instrument(&self) -> &Instrument {
+        &self.instrument
+    }
+    pub fn set_instrument(&mut self, instrument: Instrument) {
+        self.instrument = instrument;
+    }
+
+    pub const fn get_detune_cents(&self) -> f32 {
+        self.detune_cents
+    }
+    /// Shifts every frequency of the track, 1200 cents is an octave.
+    pub fn set_detune_cents(&mut self, detune_cents: f32) {
+        self.detune_cents = detune_cents.clamp(-2400.0, 2400.0);
+    }
+}
+
+#[derive(Default)]
+struct RenderCache {
+    /// Tones of every record rendered by the last [Composition::render_audio], keyed by [Composition::record_key].
+    tones: HashMap<u64, Arc<[Tone]>>,
+    /// Buffers of records that changed, reused by the next renders instead of allocating.
+    spare: Vec<Arc<[Tone]>>,
+    /// Hash of everything the last render's output depends on.
+    song_key: u64,
+}
+
+enum RenderJob<'a> {
+    Cached(Arc<[Tone]>),
+    /// Lines to render, their track's detune in cents, their base amplitude and a spare buffer to render them into.
+    Render(&'a [Line], f32, f32, Option<Arc<[Tone]>>),
+}
+
+/// Everything a song is made of and how it's synthesized, without any window or GPU state.  
+/// [Composition::render_audio] works without an OpenGL context, so it can run headless.
+pub struct Composition {
+    tracks: Vec<Track>,
+
+    bpm: f32,
+    sample_rate: u32,
+    master_gain: f32,
+    default_instrument: Instrument,
+    envelope: Envelope,
+    glide_secs: f32,
+
+    velocity_sensitivity: f32,
+    band_limited: bool,
+    tuning_a4: f32,
+    temperament: Temperament,
+    tonic: u8,
+    scale: Option<Scale>,
+    time_quantize: Option<f32>,
+    pitch_interpolation: PitchInterpolation,
+    swing: f32,
+    swing_pair_beats: f32,
+    loop_region: Option<(f32, f32)>,
+
+    mix_mode: MixMode,
+    compressor: Compressor,
+    compressor_bypass: bool,
+    bitcrusher: Bitcrusher,
+    delay: Delay,
+    reverb: Reverb,
+    tremolo: Tremolo,
+
+    render_cache: Mutex<RenderCache>,
+    retain_render_buffers: bool,
+    /// Of every track, see [RecordSystem::set_undo_limit].
+    undo_limit: usize,
+}
+impl Composition {
+    pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+    pub const BASE_AMPLITUDE: f32 = 0.33;
+    pub const DEFAULT_BPM: f32 = 168.0;
+    pub const DEFAULT_VELOCITY_SENSITIVITY: f32 = 0.05;
+    /// Longest song [Self::render_audio] renders, anything after it is cut off.
+    pub const MAX_RENDER_SECS: f32 = 600.0;
+    pub const MAX_SWING: f32 = 0.6;
+
+    pub fn new(config: TimelineConfig) -> Self {
+        let mut composition = Self {
+            tracks: Vec::new(),
+
+            bpm: config.bpm.clamp(1.0, 1000.0),
+            sample_rate: config.sample_rate.max(1),
+            master_gain: config.master_gain,
+            default_instrument: config.default_instrument,
+            envelope: config.envelope,
+            glide_secs: config.glide_secs.max(0.0),
+
+            velocity_sensitivity: Self::DEFAULT_VELOCITY_SENSITIVITY,
+            band_limited: true,
+            tuning_a4: tuning::DEFAULT_TUNING_A4,
+            temperament: Temperament::default(),
+            tonic: 0,
+            scale: None,
+            time_quantize: None,
+            pitch_interpolation: PitchInterpolation::default(),
+            swing: 0.0,
+            swing_pair_beats: 1.0,
+            loop_region: None,
+
+            mix_mode: MixMode::default(),
+            compressor: Compressor::default(),
+            compressor_bypass: true,
+            bitcrusher: config.bitcrusher,
+            delay: Delay { feedback: config.delay.feedback.clamp(0.0, Delay::MAX_FEEDBACK), wet: config.delay.wet.clamp(0.0, 1.0), ..config.delay },
+            reverb: Reverb { wet: config.reverb.wet.clamp(0.0, 1.0), room_size: config.reverb.room_size.clamp(0.0, 1.0) },
+            tremolo: Tremolo { depth: config.tremolo.depth.clamp(0.0, 1.0), ..config.tremolo },
+
+            render_cache: Mutex::default(),
+            retain_render_buffers: true,
+            undo_limit: config.undo_limit,
+        };
+        composition.add_track();
+
+        composition
+    }
+
+    /// Never empty, a composition starts with one track.
+    pub fn get_tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+    pub fn get_track_mut(&mut self, index: usize) -> &mut Track {
+        &mut self.tracks[index]
+    }
+    /// Adds an empty track with the default instrument.
+    pub fn add_track(&mut self) -> &mut Track {
+        let track = self.new_track();
+        self.tracks.push(track);
+        self.tracks.last_mut().unwrap()
+    }
+    fn new_track(&self) -> Track {
+        let mut track = Track { instrument: self.default_instrument.clone(), ..Track::default() };
+        track.set_undo_limit(self.undo_limit);
+        track
+    }
+    /// Takes a track out. The only track is swapped for an empty one instead, so there's always one left.
+    pub fn remove_track(&mut self, index: usize) -> Track {
+        if self.tracks.len() == 1 {
+            let track = self.new_track();
+            std::mem::replace(&mut self.tracks[0], track)
+        } else {
+            self.tracks.remove(index)
+        }
+    }
+    /// Puts a track back at `index`, or after the others if there aren't that many.
+    pub fn insert_track(&mut self, index: usize, track: Track) {
+        self.tracks.insert(index.min(self.tracks.len()), track);
+    }
+
+    pub const fn get_undo_limit(&self) -> usize {
+        self.undo_limit
+    }
+    /// Applies to every track, see [RecordSystem::set_undo_limit].
+    pub fn set_undo_limit(&mut self, undo_limit: usize) {
+        self.undo_limit = undo_limit;
+        for track in &mut self.tracks {
+            track.set_undo_limit(undo_limit);
+        }
+    }
+
+    pub const fn get_bpm(&self) -> f32 {
+        self.bpm
+    }
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.bpm = bpm.clamp(1.0, 1000.0);
+    }
+
+    pub const fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub const fn get_master_gain(&self) -> f32 {
+        self.master_gain
+    }
+    pub fn set_master_gain(&mut self, master_gain: f32) {
+        self.master_gain = master_gain.max(0.0);
+    }
+
+    pub const fn get_envelope(&self) -> Envelope {
+        self.envelope
+    }
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.envelope = Envelope { attack: envelope.attack.max(0.0), release: envelope.release.max(0.0) };
+    }
+    pub const fn get_glide_secs(&self) -> f32 {
+        self.glide_secs
+    }
+    /// Portamento between the lines of a record, 0 jumps straight to the next pitch.
+    pub fn set_glide_secs(&mut self, glide_secs: f32) {
+        self.glide_secs = glide_secs.max(0.0);
+    }
+
+    pub const fn get_velocity_sensitivity(&self) -> f32 {
+        self.velocity_sensitivity
+    }
+    /// How strongly the drawing speed lowers the amplitude, 0 disables the dynamics.
+    pub fn set_velocity_sensitivity(&mut self, sensitivity: f32) {
+        self.velocity_sensitivity = sensitivity.max(0.0);
+    }
+
+    pub const fn is_band_limited(&self) -> bool {
+        self.band_limited
+    }
+    /// Applies PolyBLEP correction to the square and saw oscillators to reduce aliasing.
+    pub fn set_band_limited(&mut self, band_limited: bool) {
+        self.band_limited = band_limited;
+    }
+
+    pub const fn get_tuning_a4(&self) -> f32 {
+        self.tuning_a4
+    }
+    /// Concert pitch in Hz, only affects the rendered audio.
+    pub fn set_tuning_a4(&mut self, tuning_a4: f32) {
+        self.tuning_a4 = tuning_a4.clamp(1.0, 20000.0);
+    }
+
+    pub const fn get_temperament(&self) -> &Temperament {
+        &self.temperament
+    }
+    pub fn set_temperament(&mut self, temperament: Temperament) {
+        self.temperament = temperament;
+    }
+    pub const fn get_tonic(&self) -> u8 {
+        self.tonic
+    }
+    /// Pitch class (0 is C) the temperament's and the scale's degrees are counted from.
+    pub fn set_tonic(&mut self, tonic: u8) {
+        self.tonic = tonic % 12;
+    }
+    pub const fn get_scale(&self) -> Option<Scale> {
+        self.scale
+    }
+    /// Scale the drawn pitches snap to when rendering, `None` keeps them free.
+    pub fn set_scale(&mut self, scale: Option<Scale>) {
+        self.scale = scale;
+    }
+
+    /// Pitch rendered at world height `y`, see [tuning::pitch_from_y], snapped to the scale if one is set.
+    pub fn get_pitch_at(&self, y: f32) -> f32 {
+        let pitch = tuning::pitch_from_y(y);
+        match self.scale {
+            Some(scale) => scale.snap(pitch, self.tonic),
+            None => pitch,
+        }
+    }
+    /// Where a point drawn at `position` ends up when rendered: on the [Self::get_time_quantize] grid and at the height
+    /// of the [Self::get_pitch_at] pitch.
+    pub fn get_snapped_point(&self, position: Point2<f32>) -> Point2<f32> {
+        let x = match self.time_quantize {
+            Some(grid) => (position.x / grid).round() * grid,
+            None => position.x,
+        };
+        Point2::new(x, tuning::y_from_pitch(self.get_pitch_at(position.y)))
+    }
+    /// Frequency of `pitch` with the composition's tuning, temperament and tonic, before any track detune.
+    pub fn get_frequency(&self, pitch: f32) -> f32 {
+        self.temperament.frequency(pitch, self.tuning_a4, self.tonic)
+    }
+
+    pub const fn get_time_quantize(&self) -> Option<f32> {
+        self.time_quantize
+    }
+    /// Grid in beats the start and end of every record snap to when rendering, the drawing itself is untouched.
+    pub fn set_time_quantize(&mut self, grid: Option<f32>) {
+        self.time_quantize = grid.filter(|grid| *grid > 0.0);
+    }
+
+    pub const fn get_swing(&self) -> f32 {
+        self.swing
+    }
+    /// Length in beats of the note pairs swing applies to, the off-beat is halfway through.
+    pub const fn get_swing_pair_beats(&self) -> f32 {
+        self.swing_pair_beats
+    }
+    /// Delays the second half of every `pair_beats` long pair by `swing` times half a pair, from 0 for straight timing
+    /// to [Self::MAX_SWING]. A third is triplet swing.
+    pub fn set_swing(&mut self, swing: f32, pair_beats: f32) {
+        self.swing = if swing.is_finite() { swing.clamp(0.0, Self::MAX_SWING) } else { 0.0 };
+        self.swing_pair_beats = if pair_beats.is_finite() && pair_beats > 0.0 { pair_beats } else { 1.0 };
+    }
+    /// Beat drawn at `beat` is played at, see [Self::set_swing].
+    pub fn get_played_beat(&self, beat: f32) -> f32 {
+        if self.swing == 0.0 {
+            return beat;
+        }
+
+        let half = self.swing_pair_beats * 0.5;
+        let pair_start = (beat / self.swing_pair_beats).floor() * self.swing_pair_beats;
+        let offset = beat - pair_start;
+        let swung_half = half * (1.0 + self.swing);
+        pair_start + if offset <= half { offset * (1.0 + self.swing) } else { swung_half + (offset - half) * (1.0 - self.swing) }
+    }
+    /// Inverse of [Self::get_played_beat], the drawn beat playing at `played_beat`.
+    pub fn get_drawn_beat(&self, played_beat: f32) -> f32 {
+        if self.swing == 0.0 {
+            return played_beat;
+        }
+
+        let half = self.swing_pair_beats * 0.5;
+        let pair_start = (played_beat / self.swing_pair_beats).floor() * self.swing_pair_beats;
+        let offset = played_beat - pair_start;
+        let swung_half = half * (1.0 + self.swing);
+        pair_start + if offset <= swung_half { offset / (1.0 + self.swing) } else { half + (offset - swung_half) / (1.0 - self.swing) }
+    }
+    /// Moves the lines of a record to where they play with swing, split where the timing bends.
+    fn swing_record<'a>(&self, lines: Cow<'a, [Line]>) -> Cow<'a, [Line]> {
+        if self.swing == 0.0 {
+            return lines;
+        }
+
+        let half = self.swing_pair_beats * 0.5;
+        let mut swung = Vec::with_capacity(lines.len());
+        for line in lines.iter() {
+            let (min, max) = (line.start.x.min(line.end.x), line.start.x.max(line.end.x));
+            let mut points = vec![line.start];
+            // Every off-beat and pair start strictly inside the line, in the line's direction.
+            let mut bends: Vec<f32> = ((min / half).floor() as i64 + 1..=(max / half).ceil() as i64 - 1)
+                .map(|i| i as f32 * half)
+                .filter(|x| *x > min && *x < max)
+                .collect();
+            if line.end.x < line.start.x {
+                bends.reverse();
+            }
+            for x in bends {
+                let t = (x - line.start.x) / (line.end.x - line.start.x);
+                points.push(Point2::new(x, line.start.y + (line.end.y - line.start.y) * t));
+            }
+            points.push(line.end);
+
+            swung.extend(points.windows(2).map(|points| Line {
+                start: Point2::new(self.get_played_beat(points[0].x), points[0].y),
+                end: Point2::new(self.get_played_beat(points[1].x), points[1].y),
+                velocity: line.velocity,
+            }));
+        }
+
+        Cow::Owned(swung)
+    }
+
+    pub const fn get_pitch_interpolation(&self) -> PitchInterpolation {
+        self.pitch_interpolation
+    }
+    /// How the pitch moves between the drawn points when rendering, the drawing itself is untouched.
+    pub fn set_pitch_interpolation(&mut self, pitch_interpolation: PitchInterpolation) {
+        self.pitch_interpolation = pitch_interpolation;
+    }
+
+    /// First and last beat of the loop region.
+    pub const fn get_loop_region(&self) -> Option<(f32, f32)> {
+        self.loop_region
+    }
+    /// The ends are put in order, a region that doesn't start at or after beat 0 and end after its start is cleared.
+    pub fn set_loop_region(&mut self, loop_region: Option<(f32, f32)>) {
+        self.loop_region = loop_region
+            .map(|(start, end)| (start.min(end), start.max(end)))
+            .filter(|(start, end)| start.is_finite() && end.is_finite() && *start >= 0.0 && end > start);
+    }
+    /// The loop region in samples of [Self::render_audio], never empty.
+    pub fn get_loop_region_samples(&self) -> Option<Range<usize>> {
+        let (start, end) = self.loop_region?;
+        let to_sample = |beat: f32| (self.get_played_beat(beat) / self.bpm * 60.0 * self.sample_rate as f32).round() as usize;
+
+        let range = to_sample(start)..to_sample(end);
+        (!range.is_empty()).then_some(range)
+    }
+
+    pub const fn get_mix_mode(&self) -> MixMode {
+        self.mix_mode
+    }
+    pub fn set_mix_mode(&mut self, mix_mode: MixMode) {
+        self.mix_mode = mix_mode;
+    }
+
+    pub const fn get_compressor(&self) -> &Compressor {
+        &self.compressor
+    }
+    /// Applied to the mix of every render, unless bypassed.
+    pub fn set_compressor(&mut self, compressor: Compressor) {
+        self.compressor = compressor;
+    }
+    pub const fn is_compressor_bypassed(&self) -> bool {
+        self.compressor_bypass
+    }
+    pub fn set_compressor_bypass(&mut self, bypass: bool) {
+        self.compressor_bypass = bypass;
+    }
+
+    pub const fn get_bitcrusher(&self) -> &Bitcrusher {
+        &self.bitcrusher
+    }
+    /// Applied to the mix of every render after the compressor, before the delay and the reverb.
+    pub fn set_bitcrusher(&mut self, bitcrusher: Bitcrusher) {
+        self.bitcrusher = bitcrusher;
+    }
+
+    pub const fn get_delay(&self) -> &Delay {
+        &self.delay
+    }
+    /// Applied to the mix of every render after the compressor and before the reverb, its repeats make renders longer.
+    pub fn set_delay(&mut self, delay: Delay) {
+        self.delay = Delay { feedback: delay.feedback.clamp(0.0, Delay::MAX_FEEDBACK), wet: delay.wet.clamp(0.0, 1.0), ..delay };
+    }
+
+    pub const fn get_reverb(&self) -> &Reverb {
+        &self.reverb
+    }
+    /// Applied to the mix of every render after the compressor, its tail makes renders longer.
+    pub fn set_reverb(&mut self, reverb: Reverb) {
+        self.reverb = Reverb { wet: reverb.wet.clamp(0.0, 1.0), room_size: reverb.room_size.clamp(0.0, 1.0) };
+    }
+
+    pub const fn get_tremolo(&self) -> &Tremolo {
+        &self.tremolo
+    }
+    /// Applied to every render last, after the master gain.
+    pub fn set_tremolo(&mut self, tremolo: Tremolo) {
+        self.tremolo = Tremolo { depth: tremolo.depth.clamp(0.0, 1.0), ..tremolo };
+    }
+
+    pub const fn get_retain_render_buffers(&self) -> bool {
+        self.retain_render_buffers
+    }
+    /// Keeps the buffers of edited records around so the next render can fill them instead of allocating.
+    pub fn set_retain_render_buffers(&mut self, retain_render_buffers: bool) {
+        self.retain_render_buffers = retain_render_buffers;
+        if !retain_render_buffers {
+            self.render_cache.lock().unwrap().spare.clear();
+        }
+    }
+    /// Hash of everything the output of the last [Self::render_audio] depends on, 0 if it was silent.
+    pub fn get_render_key(&self) -> u64 {
+        self.render_cache.lock().unwrap().song_key
+    }
+    /// The [Self::get_render_key] the next [Self::render_audio] will have, without synthesizing anything.
+    pub fn song_key(&self) -> u64 {
+        let records = self.prepared_records(None);
+        let Some(length_secs) = self.length_secs(records.iter().flat_map(|(_, _, lines)| lines.iter())) else {
+            return 0;
+        };
+        let length = (length_secs.min(Self::MAX_RENDER_SECS) * self.sample_rate as f32) as usize + 1;
+
+        let base_amplitude = |params: &StrokeParams| params.amplitude.map_or(Self::BASE_AMPLITUDE, |amplitude| amplitude.clamp(0.0, 1.0));
+        let keys: Vec<u64> = records
+            .par_iter()
+            .map(|(track, params, lines)| self.record_key(lines, track.detune_cents, base_amplitude(params), length))
+            .collect();
+        self.hash_song(None, &records, &keys)
+    }
+
+    /// Snaps the earliest and latest x of a record to the [Self::set_time_quantize] grid, stretching the lines in between.
+    /// Drops lines [Line::is_renderable] rejects, then applies [Self::quantize_record], the [PitchInterpolation] and the
+    /// swing. Beats of the result are when the lines play.
+    pub fn prepare_record<'a>(&self, lines: &'a [Line]) -> Cow<'a, [Line]> {
+        let prepared = if lines.iter().all(Line::is_renderable) {
+            self.quantize_record(lines)
+        } else {
+            let lines: Vec<Line> = lines.iter().filter(|line| line.is_renderable()).cloned().collect();
+            Cow::Owned(self.quantize_record(&lines).into_owned())
+        };
+
+        let prepared = match self.pitch_interpolation {
+            PitchInterpolation::Linear => prepared,
+            PitchInterpolation::Spline => Cow::Owned(spline_lines(&prepared)),
+        };
+        self.swing_record(prepared)
+    }
+    fn quantize_record<'a>(&self, lines: &'a [Line]) -> Cow<'a, [Line]> {
+        match self.time_quantize {
+            Some(grid) => quantize_lines(lines, grid),
+            None => Cow::Borrowed(lines),
+        }
+    }
+
+    /// Hash of everything the tones of a record depend on, so unchanged records can reuse their cached tones.
+    fn record_key(&self, lines: &[Line], detune_cents: f32, amplitude: f32, length: usize) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        length.hash(&mut hasher);
+        self.sample_rate.hash(&mut hasher);
+        for value in [self.bpm, self.tuning_a4, self.velocity_sensitivity, self.glide_secs, detune_cents, amplitude] {
+            value.to_bits().hash(&mut hasher);
+        }
+        self.tonic.hash(&mut hasher);
+        self.scale.hash(&mut hasher);
+        std::mem::discriminant(&self.temperament).hash(&mut hasher);
+        if let Temperament::Custom(cents) = &self.temperament {
+            cents.iter().for_each(|cents| cents.to_bits().hash(&mut hasher));
+        }
+        for line in lines {
+            [line.start.x, line.start.y, line.end.x, line.end.y, line.velocity].iter().for_each(|value| value.to_bits().hash(&mut hasher));
+        }
+
+        hasher.finish()
+    }
+    /// Hash of the whole mix of `records`, given the [Self::record_key] of each.
+    fn hash_song(&self, solo: Option<usize>, records: &[(&Track, &StrokeParams, Cow<'_, [Line]>)], keys: &[u64]) -> u64 {
+        let mut song_hasher = DefaultHasher::new();
+        solo.hash(&mut song_hasher);
+        self.band_limited.hash(&mut song_hasher);
+        self.mix_mode.hash(&mut song_hasher);
+        [self.master_gain, self.envelope.attack, self.envelope.release, self.swing, self.swing_pair_beats].iter().for_each(|value| value.to_bits().hash(&mut song_hasher));
+        if !self.compressor_bypass {
+            let Compressor { threshold, ratio, attack, release, makeup } = self.compressor;
+            [threshold, ratio, attack, release, makeup].iter().for_each(|value| value.to_bits().hash(&mut song_hasher));
+        }
+        if self.bitcrusher.is_enabled() {
+            [self.bitcrusher.bits, self.bitcrusher.hold].hash(&mut song_hasher);
+        }
+        if self.delay.is_enabled() {
+            let Delay { time, feedback, wet } = self.delay;
+            std::mem::discriminant(&time).hash(&mut song_hasher);
+            [time.secs(self.bpm), feedback, wet].iter().for_each(|value| value.to_bits().hash(&mut song_hasher));
+        }
+        if self.tremolo.is_enabled() {
+            [self.tremolo.rate.hz(self.bpm), self.tremolo.depth].iter().for_each(|value| value.to_bits().hash(&mut song_hasher));
+        }
+        if self.reverb.is_enabled() {
+            [self.reverb.wet, self.reverb.room_size].iter().for_each(|value| value.to_bits().hash(&mut song_hasher));
+        }
+
+        for track in &self.tracks {
+            for line in track.automation.get_lines() {
+                [line.start.x, line.start.y, line.end.x, line.end.y].iter().for_each(|value| value.to_bits().hash(&mut song_hasher));
+            }
+        }
+        for ((track, params, _), key) in records.iter().zip(keys) {
+            let instrument = params.instrument.as_ref().unwrap_or(&track.instrument);
+            key.hash(&mut song_hasher);
+            std::mem::discriminant(instrument).hash(&mut song_hasher);
+            if let Instrument::Sample(sample) = instrument {
+                Arc::as_ptr(sample).hash(&mut song_hasher);
+            }
+        }
+
+        song_hasher.finish()
+    }
+    /// Renders into `reuse` when it has the right length and nothing else holds it, otherwise allocates.
+    fn render_record_tones(&self, lines: &[Line], detune_cents: f32, base_amplitude: f32, length: usize, reuse: Option<Arc<[Tone]>>) -> Arc<[Tone]> {
+        let detune = f32::powf(2.0, detune_cents / 1200.0);
+        let silence = Tone { frequency: 0.0, amplitude: 0.0 };
+
+        let mut buffer = reuse.filter(|buffer| buffer.len() == length).unwrap_or_else(|| vec![silence.clone(); length].into());
+        if Arc::get_mut(&mut buffer).is_none() {
+            buffer = vec![silence.clone(); length].into();
+        }
+
+        let samples = Arc::get_mut(&mut buffer).unwrap();
+        samples.fill(silence);
+        for line in lines.iter() {
+            let start = Point2::new(line.start.x / (self.bpm / 60.0), line.start.y);
+            let end = Point2::new(line.end.x / (self.bpm / 60.0), line.end.y);
+
+            let min = if start.x < end.x { start } else { end };
+            let max = if start.x > end.x { start } else { end };
+
+            let first = ((min.x * self.sample_rate as f32) as usize).min(samples.len());
+            let count = (((max.x - min.x) * self.sample_rate as f32) as usize).saturating_add(1).min(samples.len() - first);
+
+            for (i, sample) in samples[first..first + count].iter_mut().enumerate().map(|(i, sample)| (first + i, sample)) {
+                let value = self.get_pitch_at(min.y + (max.y - min.y) * (i as f32 / self.sample_rate as f32 - min.x));
+                let frequency = self.get_frequency(value) * detune;
+                let amplitude = base_amplitude / (1.0 + self.velocity_sensitivity * line.velocity);
+
+                *sample = Tone { frequency, amplitude };
+            }
+        }
+        if self.glide_secs > 0.0 {
+            self.apply_glide(samples);
+        }
+
+        buffer
+    }
+    /// Slides over pitch jumps between the lines of a record instead of stepping, each slide taking the glide time.
+    /// A jump across a silent gap slides too if the gap is shorter than the glide time, the gap itself stays silent.
+    fn apply_glide(&self, samples: &mut [Tone]) {
+        // Steeper changes between two samples are jumps, drawn slopes change far less per sample.
+        const JUMP_OCTAVES: f32 = 0.5 / 12.0;
+
+        let glide_samples = (self.glide_secs * self.sample_rate as f32).max(1.0);
+        // Index and drawn and played pitch in octaves of the last sounding sample.
+        let mut previous: Option<(usize, f32, f32)> = None;
+        // Played pitch minus drawn pitch where the current slide started, and the sample it started at.
+        let mut slide: Option<(f32, usize)> = None;
+
+        for (i, tone) in samples.iter_mut().enumerate() {
+            if tone.amplitude <= 0.0 || tone.frequency <= 0.0 {
+                continue;
+            }
+
+            let target = tone.frequency.log2();
+            if let Some((previous_i, previous_target, previous_played)) = previous {
+                let is_close = ((i - previous_i) as f32) < glide_samples;
+                if is_close && (target - previous_target).abs() > JUMP_OCTAVES {
+                    slide = Some((previous_played - target, i));
+                }
+            }
+
+            let mut played = target;
+            if let Some((offset, start)) = slide {
+                let progress = (i - start) as f32 / glide_samples;
+                if progress < 1.0 {
+                    played += offset * (1.0 - progress);
+                    tone.frequency = played.exp2();
+                } else {
+                    slide = None;
+                }
+            }
+
+            previous = Some((i, target, played));
+        }
+    }
+    /// Gain of `track` at every sample, 1 wherever no automation line is drawn. `None` without any automation.
+    ///
+    /// Lines drawn later win where strokes overlap.
+    fn render_automation(&self, track: &Track, length: usize) -> Option<Arc<[f32]>> {
+        let lines = track.automation.get_lines();
+        if lines.is_empty() {
+            return None;
+        }
+        let lines: Vec<Line> = lines.iter().filter(|line| line.is_renderable()).cloned().collect();
+
+        let mut gain = vec![1.0; length];
+        for line in self.swing_record(Cow::Owned(lines)).iter() {
+            let (min, max) = if line.start.x <= line.end.x { (&line.start, &line.end) } else { (&line.end, &line.start) };
+            let samples_per_beat = self.sample_rate as f32 * 60.0 / self.bpm;
+
+            let first = ((min.x * samples_per_beat) as usize).min(length);
+            let last = ((max.x * samples_per_beat) as usize).saturating_add(1).min(length);
+            for (i, sample) in gain[first..last].iter_mut().enumerate().map(|(i, sample)| (first + i, sample)) {
+                let t = if max.x > min.x { (i as f32 / samples_per_beat - min.x) / (max.x - min.x) } else { 1.0 };
+                *sample = (min.y + (max.y - min.y) * t.clamp(0.0, 1.0)).clamp(0.0, 1.0);
+            }
+        }
+
+        Some(gain.into())
+    }
+
+    /// Every record of every track as it will be rendered, with the track it belongs to and its own settings.
+    /// With `solo` only the records of that track.
+    fn prepared_records(&self, solo: Option<usize>) -> Vec<(&Track, &StrokeParams, Cow<'_, [Line]>)> {
+        self.tracks
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| solo.is_none_or(|solo| solo == *i))
+            .map(|(_, track)| track)
+            .flat_map(|track| track.record_system.records().zip(&track.record_system.params).map(move |(lines, params)| (track, params, lines)))
+            .map(|(track, params, lines)| (track, params, self.prepare_record(lines)))
+            .collect()
+    }
+    /// Seconds until the last line ends, `None` without any lines.
+    fn length_secs<'a>(&self, lines: impl Iterator<Item = &'a Line>) -> Option<f32> {
+        let length = lines.max_by(|a, b| {
+            if f32::max(a.start.x, a.end.x) > f32::max(b.start.x, b.end.x) {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            }
+        })?;
+
+        Some(f32::max(length.start.x, length.end.x) / (self.bpm / 60.0))
+    }
+
+    pub fn get_record_count(&self) -> usize {
+        self.tracks.iter().map(|track| track.record_system.get_record_count()).sum()
+    }
+    pub fn get_line_count(&self) -> usize {
+        self.tracks.iter().map(|track| track.record_system.get_line_count()).sum()
+    }
+    /// Smallest and largest corner around the lines of every track, see [RecordSystem::get_bounds].
+    pub fn get_bounds(&self) -> Option<(Point2<f32>, Point2<f32>)> {
+        bounds(self.tracks.iter().flat_map(|track| track.record_system.get_lines()))
+    }
+    /// Lowest and highest semitone drawn.
+    pub fn get_pitch_range(&self) -> Option<(f32, f32)> {
+        self.get_bounds().map(|(min, max)| (min.y, max.y))
+    }
+    /// First and last beat drawn.
+    pub fn get_time_range(&self) -> Option<(f32, f32)> {
+        self.get_bounds().map(|(min, max)| (min.x, max.x))
+    }
+    /// Length of [Self::render_audio] in seconds at the current bpm, quantizing and the delay and reverb tails included.
+    pub fn get_duration_secs(&self) -> f32 {
+        let records = self.prepared_records(None);
+        self.length_secs(records.iter().flat_map(|(_, _, lines)| lines.iter()))
+            .map_or(0.0, |length_secs| length_secs.min(Self::MAX_RENDER_SECS) + self.delay.tail_secs(self.bpm) + self.reverb.tail_secs())
+    }
+
+    /// Every sample of [Self::render_audio], unclamped.
+    pub fn render_samples_f32(&self) -> Vec<f32> {
+        self.render_audio().collect()
+    }
+    pub fn render_audio(&self) -> PlayerSource {
+        self.render_tracks(None)
+    }
+    /// Every sample of the track at `index` rendered alone, through the same master effects as [Self::render_audio].  
+    /// It ends with the last line of that track, not of the whole song.
+    pub fn render_track_samples_f32(&self, index: usize) -> Vec<f32> {
+        self.render_tracks(Some(index)).collect()
+    }
+    /// With `solo` every other track is muted.
+    fn render_tracks(&self, solo: Option<usize>) -> PlayerSource {
+        let mut tones_samples = Vec::new();
+        let records = self.prepared_records(solo);
+
+        if let Some(mut length_secs) = self.length_secs(records.iter().flat_map(|(_, _, lines)| lines.iter())) {
+            if length_secs > Self::MAX_RENDER_SECS {
+                eprintln!("Song is {} seconds long, rendering only the first {} seconds.", length_secs, Self::MAX_RENDER_SECS);
+                length_secs = Self::MAX_RENDER_SECS;
+            }
+            let length = (length_secs * self.sample_rate as f32) as usize + 1;
+
+            let mut cache = self.render_cache.lock().unwrap();
+            let cache = &mut *cache;
+
+            let base_amplitude = |params: &StrokeParams| params.amplitude.map_or(Self::BASE_AMPLITUDE, |amplitude| amplitude.clamp(0.0, 1.0));
+            let keys: Vec<u64> = records
+                .par_iter()
+                .map(|(track, params, lines)| self.record_key(lines, track.detune_cents, base_amplitude(params), length))
+                .collect();
+            let mut spare = cache.spare.drain(..).filter(|buffer| buffer.len() == length);
+            let jobs: Vec<RenderJob> = records
+                .iter()
+                .zip(&keys)
+                .map(|((track, params, lines), key)| match cache.tones.get(key) {
+                    Some(samples) => RenderJob::Cached(samples.clone()),
+                    None => RenderJob::Render(lines, track.detune_cents, base_amplitude(params), spare.next()),
+                })
+                .collect();
+            drop(spare);
+
+            // Records are independent until mixing, collecting keeps them in the serial order.
+            let rendered: Vec<Arc<[Tone]>> = jobs
+                .into_par_iter()
+                .map(|job| match job {
+                    RenderJob::Cached(samples) => samples,
+                    RenderJob::Render(lines, detune_cents, amplitude, reuse) => self.render_record_tones(lines, detune_cents, amplitude, length, reuse),
+                })
+                .collect();
+
+            let gains: Vec<Option<Arc<[f32]>>> = self.tracks.iter().map(|track| self.render_automation(track, length)).collect();
+
+            let mut used_tones = HashMap::with_capacity(records.len());
+            for (seed, ((track, params, _), (key, samples))) in records.iter().zip(keys.iter().copied().zip(rendered)).enumerate() {
+                let instrument = params.instrument.as_ref().unwrap_or(&track.instrument);
+                let gain = self.tracks.iter().position(|other| std::ptr::eq(other, *track)).and_then(|i| gains[i].clone());
+                tones_samples.push(
+                    ToneSamples::new(samples.clone(), instrument.clone(), seed as u64, self.band_limited, self.envelope, self.sample_rate)
+                        .with_gain(gain),
+                );
+                used_tones.insert(key, samples);
+            }
+            cache.song_key = self.hash_song(solo, &records, &keys);
+
+            if solo.is_some() {
+                // The tones of the muted tracks stay cached for the next full render.
+                cache.tones.extend(used_tones);
+            } else {
+                let stale_tones = std::mem::replace(&mut cache.tones, used_tones);
+                if self.retain_render_buffers {
+                    cache.spare.extend(stale_tones.into_iter().filter(|(key, _)| !cache.tones.contains_key(key)).map(|(_, samples)| samples));
+                }
+            }
+        } else {
+            self.render_cache.lock().unwrap().song_key = 0;
+        }
+
+        let compressor = (!self.compressor_bypass).then(|| CompressorState::new(&self.compressor, self.sample_rate));
+        let reverb = self.reverb.is_enabled().then(|| (ReverbState::new(&self.reverb, self.sample_rate), self.reverb.tail_secs()));
+        let delay = self.delay.is_enabled().then(|| (DelayState::new(&self.delay, self.bpm, self.sample_rate), self.delay.tail_secs(self.bpm)));
+        let bitcrusher = self.bitcrusher.is_enabled().then(|| BitcrusherState::new(&self.bitcrusher));
+        PlayerSource::new(tones_samples.into_boxed_slice(), self.mix_mode, compressor, delay, reverb, self.master_gain, self.sample_rate)
+            .with_bitcrusher(bitcrusher)
+            .with_tremolo(self.tremolo.is_enabled().then(|| TremoloState::new(&self.tremolo, self.bpm, self.sample_rate)))
+    }
+}
+/// Smallest and largest corner around `lines`, skipping values that aren't finite.
+fn bounds<'a>(lines: impl Iterator<Item = &'a Line>) -> Option<(Point2<f32>, Point2<f32>)> {
+    lines
+        .flat_map(|line| [line.start, line.end])
+        .filter(|point| point.x.is_finite() && point.y.is_finite())
+        .fold(None, |bounds, point| match bounds {
+            None => Some((point, point)),
+            Some((min, max)) => Some((min.inf(&point), max.sup(&point))),
+        })
+}
+
+impl Default for Composition {
+    fn default() -> Self {
+        Self::new(TimelineConfig::default())
+    }
+}
+
+/// Snaps the earliest and latest x of a record to a `grid` in beats, stretching the lines in between. Pitches are kept.
+pub fn quantize_lines(lines: &[Line], grid: f32) -> Cow<'_, [Line]> {
+    let (min, max) = lines.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), line| {
+        (min.min(line.start.x).min(line.end.x), max.max(line.start.x).max(line.end.x))
+    });
+    if lines.is_empty() || max <= min {
+        return Cow::Borrowed(lines);
+    }
+
+    let start = (min / grid).round() * grid;
+    let mut end = (max / grid).round() * grid;
+    if end <= start {
+        end = start + grid;
+    }
+
+    let scale = (end - start) / (max - min);
+    let map = |x: f32| start + (x - min) * scale;
+
+    Cow::Owned(lines.iter().map(|line| {
+        let mut line = line.clone();
+        line.start.x = map(line.start.x);
+        line.end.x = map(line.end.x);
+        line
+    }).collect())
+}
+
+/// Follows a Catmull-Rom spline through the ends of each chain of connected lines, split into lines at most
+/// 1/64 beat wide. The spline passes through every drawn point. At the ends of a chain it keeps the slope of the
+/// line there, so a lone line stays straight.
+pub fn spline_lines(lines: &[Line]) -> Vec<Line> {
+    const STEP_BEATS: f32 = 1.0 / 64.0;
+    const MAX_STEPS: usize = 256;
+    // Ends closer than this belong to the same stroke.
+    const CONNECT_DISTANCE: f32 = 1e-4;
+
+    let slope = |line: &Line| {
+        let width = line.end.x - line.start.x;
+        if width == 0.0 { 0.0 } else { (line.end.y - line.start.y) / width }
+    };
+    // A stroke turning back in time has no slope at the turn.
+    let slope_through = |before: Point2<f32>, point: Point2<f32>, after: Point2<f32>| {
+        if (point.x - before.x) * (after.x - point.x) <= 0.0 { 0.0 } else { (after.y - before.y) / (after.x - before.x) }
+    };
+
+    let mut smoothed = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|i| &lines[i]).filter(|previous| (line.start - previous.end).magnitude() <= CONNECT_DISTANCE);
+        let next = lines.get(i + 1).filter(|next| (next.start - line.end).magnitude() <= CONNECT_DISTANCE);
+
+        let width = line.end.x - line.start.x;
+        if width == 0.0 {
+            smoothed.push(line.clone());
+            continue;
+        }
+        let start_slope = previous.map_or_else(|| slope(line), |previous| slope_through(previous.start, line.start, line.end));
+        let end_slope = next.map_or_else(|| slope(line), |next| slope_through(line.start, line.end, next.end));
+
+        let steps = ((width.abs() / STEP_BEATS).ceil() as usize).clamp(1, MAX_STEPS);
+        let mut start = line.start;
+        for step in 1..=steps {
+            let end = if step == steps {
+                line.end
+            } else {
+                // Cubic Hermite basis.
+                let t = step as f32 / steps as f32;
+                let (t2, t3) = (t * t, t * t * t);
+                let y = (2.0 * t3 - 3.0 * t2 + 1.0) * line.start.y
+                    + (t3 - 2.0 * t2 + t) * width * start_slope
+                    + (3.0 * t2 - 2.0 * t3) * line.end.y
+                    + (t3 - t2) * width * end_slope;
+                Point2::new(line.start.x + width * t, y)
+            };
+
+            smoothed.push(Line { start, end, velocity: line.velocity });
+            start = end;
+        }
+    }
+
+    smoothed
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct Line {
+    pub start: Point2<f32>,
+    pub end: Point2<f32>,
+    /// Drawing speed in world units per second, 0 when unknown.
+    pub velocity: f32,
+}
+impl Line {
+    /// Whether the line can be rendered: every value is finite and it doesn't start before the timeline.
+    pub fn is_renderable(&self) -> bool {
+        [self.start.x, self.start.y, self.end.x, self.end.y, self.velocity].iter().all(|value| value.is_finite()) &&
+            self.start.x >= 0.0 && self.end.x >= 0.0
+    }
+    pub fn distance_to(&self, point: Point2<f32>) -> f32 {
+        let direction = self.end - self.start;
+        let length_squared = direction.magnitude_squared();
+        if length_squared == 0.0 {
+            return (point - self.start).magnitude();
+        }
+
+        let t = ((point - self.start).dot(&direction) / length_squared).clamp(0.0, 1.0);
+        (point - (self.start + direction * t)).magnitude()
+    }
+}
+
+/// Linear gain ramps applied to every note, 0 seconds switches a note on or off instantly.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Envelope {
+    pub attack: f32,
+    /// The last tone of a note keeps sounding while it fades out.
+    pub release: f32,
+}
+
+/// How [PlayerSource] adds up the voices playing at once, before the compressor and the master gain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MixMode {
+    /// Voices add up as they are. Every voice makes the mix louder, so chords rely on the compressor or clip.
+    Sum,
+    /// The sum divided by how many voices sound, counting fading ones partly. A chord is as loud as one of its
+    /// notes, which keeps headroom but makes thick chords sound thin.
+    Average,
+    /// The sum divided by the square root of the summed amplitudes. Loudness grows with the square root of the
+    /// voice count, like unrelated sounds adding up, and a lone voice plays louder than its own amplitude.
+    #[default] RmsNormalized,
+}
+impl MixMode {
+    pub const fn next(&self) -> Self {
+        match self {
+            Self::Sum => Self::Average,
+            Self::Average => Self::RmsNormalized,
+            Self::RmsNormalized => Self::Sum,
+        }
+    }
+}
+
+/// How the rendered pitch moves between the points of a stroke.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PitchInterpolation {
+    /// Straight ramps along the drawn lines, turning sharply at every point.
+    #[default] Linear,
+    /// A smooth curve through the drawn points, see [spline_lines].
+    Spline,
+}
+
+/// What a record sounds like at one sample, silent when the amplitude is 0.
+#[derive(Clone)]
+pub struct Tone {
+    pub frequency: f32,
+    pub amplitude: f32,
+}
+struct ToneSamples {
+    samples: Arc<[Tone]>,
+    i: usize,
+    time: f32,
+    sample_rate: f32,
+
+    envelope: Envelope,
+    envelope_gain: f32,
+    /// Last sounding tone, kept playing during the release.
+    held: Tone,
+    last_amplitude: f32,
+
+    instrument: Instrument,
+    band_limited: bool,
+    noise: StdRng,
+    filter_low: f32,
+    filter_band: f32,
+    sample_position: f64,
+    /// Track automation per sample, see [Composition::render_automation].
+    gain: Option<Arc<[f32]>>,
+}
+impl ToneSamples {
+    const NOISE_SEED: u64 = 0x5EED_F00D;
+    const NOISE_FILTER_DAMPING: f32 = 0.5;
+
+    /// `seed` is mixed into the noise seed so that simultaneous noise records don't sound identical.
+    pub fn new(samples: Arc<[Tone]>, instrument: Instrument, seed: u64, band_limited: bool, envelope: Envelope, sample_rate: u32) -> Self {
+        Self {
+            samples,
+            i: 0,
+            time: 0.0,
+            sample_rate: sample_rate as f32,
+
+            envelope,
+            envelope_gain: 0.0,
+            held: Tone { frequency: 0.0, amplitude: 0.0 },
+            last_amplitude: 0.0,
+
+            instrument,
+            band_limited,
+            noise: StdRng::seed_from_u64(Self::NOISE_SEED ^ seed),
+            filter_low: 0.0,
+            filter_band: 0.0,
+            sample_position: 0.0,
+            gain: None,
+        }
+    }
+    /// Scales the output by `gain` per sample. It isn't part of [Self::last_amplitude], so it stays linear in the mix.
+    pub fn with_gain(mut self, gain: Option<Arc<[f32]>>) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Amplitude of the sample last returned by [Iterator::next] including the envelope, 0 before the first one.
+    pub fn last_amplitude(&self) -> f32 {
+        self.last_amplitude
+    }
+
+    /// Tone to play at the current sample after applying the envelope.
+    fn next_tone(&mut self) -> Tone {
+        let tone = &self.samples[self.i];
+        if tone.amplitude > 0.0 {
+            // A note after full silence starts its waveform over, the same as a new voice would.
+            if self.envelope_gain <= 0.0 {
+                self.time = 0.0;
+                self.filter_low = 0.0;
+                self.filter_band = 0.0;
+            }
+            self.envelope_gain = match self.envelope.attack {
+                attack if attack > 0.0 => (self.envelope_gain + 1.0 / (attack * self.sample_rate)).min(1.0),
+                _ => 1.0,
+            };
+            self.held = tone.clone();
+        } else {
+            self.envelope_gain = match self.envelope.release {
+                release if release > 0.0 => (self.envelope_gain - 1.0 / (release * self.sample_rate)).max(0.0),
+                _ => 0.0,
+            };
+        }
+
+        let tone = if tone.amplitude > 0.0 { tone } else { &self.held };
+        Tone { frequency: tone.frequency, amplitude: tone.amplitude * self.envelope_gain }
+    }
+
+    /// Polynomial band-limited step residual for a discontinuity at phase 0, `dt` is the phase increment per sample.
+    fn poly_blep(phase: f32, dt: f32) -> f32 {
+        if phase < dt {
+            let t = phase / dt;
+            2.0 * t - t * t - 1.0
+        } else if phase > 1.0 - dt {
+            let t = (phase - 1.0) / dt;
+            t * t + 2.0 * t + 1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn get_sample(&mut self, tone: &Tone) -> f32 {
+        let phase = (self.time / f32::consts::TAU).fract();
+        let dt = tone.frequency / self.sample_rate;
+
+        match self.instrument {
+            Instrument::Sine => f32::sin(self.time),
+            Instrument::Square => {
+                let mut value = if phase < 0.5 { 1.0 } else { -1.0 };
+                if self.band_limited && dt > 0.0 {
+                    value += Self::poly_blep(phase, dt) - Self::poly_blep((phase + 0.5).fract(), dt);
+                }
+                value
+            }
+            Instrument::Saw => {
+                let mut value = 2.0 * phase - 1.0;
+                if self.band_limited && dt > 0.0 {
+                    value -= Self::poly_blep(phase, dt);
+                }
+                value
+            }
+            Instrument::Noise => {
+                // Chamberlin state variable filter, stable while the cutoff stays below a sixth of the sample rate.
+                let cutoff = tone.frequency.min(self.sample_rate / 6.0);
+                let f = 2.0 * f32::sin(f32::consts::PI * cutoff / self.sample_rate);
+                let input = self.noise.random_range(-1.0..1.0);
+
+                let high = input - self.filter_low - Self::NOISE_FILTER_DAMPING * self.filter_band;
+                self.filter_band += f * high;
+                self.filter_low += f * self.filter_band;
+
+                self.filter_band * Self::NOISE_FILTER_DAMPING
+            }
+            Instrument::Sample(ref sample) => {
+                // Every note starts the sample from its beginning.
+                if self.i > 0 && self.samples[self.i].amplitude > 0.0 && self.samples[self.i - 1].amplitude <= 0.0 {
+                    self.sample_position = 0.0;
+                }
+                let value = sample.get(self.sample_position);
+
+                let rate = tone.frequency / sample.base_frequency * sample.sample_rate as f32 / self.sample_rate;
+                self.sample_position = (self.sample_position + rate as f64) % sample.data.len() as f64;
+
+                value
+            }
+        }
+    }
+}
+impl ToneSamples {
+    /// Advances `n` samples without synthesizing them. The phase is the sum of the skipped frequencies like during
+    /// playback, so oscillators continue exactly where they would have been. The noise filter isn't run, which the
+    /// fade-in of [PlayerSource::skip_samples] covers.
+    fn skip(&mut self, n: usize) {
+        let end = self.i.saturating_add(n).min(self.samples.len());
+        while self.i < end {
+            let tone = self.next_tone();
+
+            if let Instrument::Sample(sample) = &self.instrument {
+                if self.i > 0 && self.samples[self.i].amplitude > 0.0 && self.samples[self.i - 1].amplitude <= 0.0 {
+                    self.sample_position = 0.0;
+                }
+                let rate = tone.frequency / sample.base_frequency * sample.sample_rate as f32 / self.sample_rate;
+                self.sample_position = (self.sample_position + rate as f64) % sample.data.len() as f64;
+            }
+
+            self.i += 1;
+            self.time += f32::consts::PI * 2.0 * tone.frequency / self.sample_rate;
+            self.last_amplitude = tone.amplitude;
+        }
+    }
+}
+impl Iterator for ToneSamples {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.i >= self.samples.len() {
+            return None;
+        }
+        let tone = self.next_tone();
+        let gain = self.gain.as_ref().map_or(1.0, |gain| gain[self.i]);
+        let sample = self.get_sample(&tone) * tone.amplitude * gain;
+
+        self.i += 1;
+        self.time += f32::consts::PI * 2.0 * tone.frequency / self.sample_rate;
+        self.last_amplitude = tone.amplitude;
+        Some(sample)
+    }
+}
+
+/// The voices of a render mixed into one stream. The mix goes through the compressor, the bitcrusher, the delay, the
+/// reverb and the master gain, then the tremolo. The tremolo comes after the note envelopes and the [MixMode], so it
+/// dips the whole mix at once.
+pub struct PlayerSource {
+    sample_rate: u32,
+    tones_samples: Box<[ToneSamples]>,
+    mix_mode: MixMode,
+    compressor: Option<CompressorState>,
+    bitcrusher: Option<BitcrusherState>,
+    delay: Option<DelayState>,
+    reverb: Option<ReverbState>,
+    /// Samples the delay and the reverb keep ringing for once every voice has ended, counted down from then on.
+    tail_samples: usize,
+    master_gain: f32,
+    tremolo: Option<TremoloState>,
+
+    /// Samples returned since the fade-in started.
+    fade_in_position: usize,
+    /// 0 without a fade-in.
+    fade_in_samples: usize,
+}
+impl PlayerSource {
+    /// Ramp from silence at the start of live playback, so starting on a sounding note doesn't click.
+    pub const FADE_IN_SECS: f32 = 0.005;
+
+    /// `delay` and `reverb` come with the seconds of their tails.
+    fn new(
+        tones_samples: Box<[ToneSamples]>,
+        mix_mode: MixMode,
+        compressor: Option<CompressorState>,
+        delay: Option<(DelayState, f32)>,
+        reverb: Option<(ReverbState, f32)>,
+        master_gain: f32,
+        sample_rate: u32,
+    ) -> Self {
+        let tail_secs = delay.as_ref().map_or(0.0, |(_, tail_secs)| *tail_secs) + reverb.as_ref().map_or(0.0, |(_, tail_secs)| *tail_secs);
+        Self {
+            sample_rate,
+            tones_samples,
+            mix_mode,
+            compressor,
+            bitcrusher: None,
+            delay: delay.map(|(state, _)| state),
+            reverb: reverb.map(|(state, _)| state),
+            tail_samples: (tail_secs * sample_rate as f32).ceil() as usize,
+            master_gain,
+            tremolo: None,
+
+            fade_in_position: 0,
+            fade_in_samples: 0,
+        }
+    }
+
+    pub fn with_bitcrusher(mut self, bitcrusher: Option<BitcrusherState>) -> Self {
+        self.bitcrusher = bitcrusher;
+        self
+    }
+    pub fn with_tremolo(mut self, tremolo: Option<TremoloState>) -> Self {
+        self.tremolo = tremolo;
+        self
+    }
+    /// Ramps in over [Self::FADE_IN_SECS] from the start and after every [Self::skip_samples], for playback that
+    /// starts mid-note. Off by default, so offline renders and exports keep every sample as synthesized.
+    pub fn with_fade_in(mut self, fade_in: bool) -> Self {
+        self.fade_in_samples = if fade_in { ((Self::FADE_IN_SECS * self.sample_rate as f32) as usize).max(1) } else { 0 };
+        self
+    }
+
+    /// Starts playback `n` samples in, without synthesizing the skipped audio, and fades in from there with
+    /// [Self::with_fade_in].
+    ///
+    /// Oscillator phases and envelopes end up exactly where playing the skipped samples would have left them.
+    /// The compressor and the noise filter start from rest instead, which the fade-in hides. The delay and the reverb
+    /// start from rest too, without the tail of the skipped audio.
+    pub fn skip_samples(&mut self, n: usize) {
+        for tone_samples in &mut self.tones_samples {
+            tone_samples.skip(n);
+        }
+        if let Some(tremolo) = &mut self.tremolo {
+            tremolo.skip(n);
+        }
+        self.fade_in_position = 0;
+    }
+
+    /// Tones of every voice, one voice per record, sample by sample.
+    pub fn voices(&self) -> impl Iterator<Item = &[Tone]> {
+        self.tones_samples.iter().map(|tone_samples| &*tone_samples.samples)
+    }
+}
+impl Iterator for PlayerSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut sample = 0.0;
+        let mut accumulated_amplitude = 0.0;
+        // Voices weighted by their envelopes, so the average doesn't jump when one fades in or out.
+        let mut sounding_voices = 0.0;
+        
+        let mut no_more_samples = true;
+        for tone_samples in &mut self.tones_samples {
+            if let Some(next_sample) = tone_samples.next() {
+                no_more_samples = false;
+
+                sample += next_sample;
+                accumulated_amplitude += tone_samples.last_amplitude();
+                sounding_voices += tone_samples.envelope_gain;
+            }
+        }
+        if no_more_samples {
+            if self.tail_samples == 0 {
+                return None;
+            }
+            self.tail_samples -= 1;
+        }
+        match self.mix_mode {
+            MixMode::Sum => {}
+            MixMode::Average => sample /= f32::max(sounding_voices, 1.0),
+            MixMode::RmsNormalized if accumulated_amplitude > 0.0 => sample /= f32::sqrt(accumulated_amplitude),
+            MixMode::RmsNormalized => {}
+        }
+        if let Some(compressor) = &mut self.compressor {
+            sample = compressor.process(sample);
+        }
+        if let Some(bitcrusher) = &mut self.bitcrusher {
+            sample = bitcrusher.process(sample);
+        }
+        if let Some(delay) = &mut self.delay {
+            sample = delay.process(sample);
+        }
+        if let Some(reverb) = &mut self.reverb {
+            sample = reverb.process(sample);
+        }
+
+        sample *= self.master_gain;
+        if let Some(tremolo) = &mut self.tremolo {
+            sample = tremolo.process(sample);
+        }
+
+        if self.fade_in_position < self.fade_in_samples {
+            sample *= self.fade_in_position as f32 / self.fade_in_samples as f32;
+            self.fade_in_position += 1;
+        }
+        Some(sample)
+    }
+}
+impl Source for PlayerSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let max_samples = self.tones_samples.iter()
+            .map(|tone_samples| tone_samples.samples.len())
+            .max()
+            .unwrap_or(0);
+
+        if max_samples == 0 {
+            return None;
+        }
+
+        Some(Duration::from_secs_f32((max_samples + self.tail_samples) as f32 / self.sample_rate as f32))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::TremoloRate;
+
+    fn line(start: f32, end: f32, y: f32) -> Line {
+        Line { start: Point2::new(start, y), end: Point2::new(end, y), velocity: 0.0 }
+    }
+    /// Every record as its line coordinates, since [Line] doesn't compare.
+    fn records_of(system: &RecordSystem) -> Vec<Vec<[f32; 5]>> {
+        system.records().map(|lines| lines.iter().map(|line| [line.start.x, line.start.y, line.end.x, line.end.y, line.velocity]).collect()).collect()
+    }
+    /// Looks up every line through the line index, it must be found in its own record.
+    fn assert_indexed(system: &RecordSystem) {
+        for (index, lines) in system.records().enumerate() {
+            for line in lines {
+                let middle = Point2::from((line.start.coords + line.end.coords) * 0.5);
+                assert_eq!(system.find_record_near(middle, Vector2::new(0.01, 0.01)), Some(index));
+            }
+        }
+    }
+    fn composition_with(records: &[Vec<Line>]) -> Composition {
+        let mut composition = Composition::default();
+        for lines in records {
+            composition.get_track_mut(0).push_record(lines.clone(), StrokeParams::default());
+        }
+        composition
+    }
+
+    #[test]
+    fn offline_renders_dont_fade_in() {
+        let composition = composition_with(&[vec![line(0.0, 2.0, 0.0)]]);
+        let rendered = composition.render_samples_f32();
+        let faded: Vec<f32> = composition.render_audio().with_fade_in(true).collect();
+
+        let fade_in_samples = (PlayerSource::FADE_IN_SECS * composition.get_sample_rate() as f32) as usize;
+        assert_eq!(rendered.len(), faded.len());
+        assert_eq!(rendered[fade_in_samples..], faded[fade_in_samples..]);
+        assert!(rendered[1..fade_in_samples].iter().zip(&faded[1..]).any(|(rendered, faded)| rendered != faded));
+    }
+
+    #[test]
+    fn song_key_matches_the_next_render() {
+        let mut composition = composition_with(&[vec![line(0.0, 1.0, 0.0)], vec![line(1.0, 2.0, 3.0)]]);
+        let song_key = composition.song_key();
+        composition.render_audio();
+        assert_eq!(song_key, composition.get_render_key());
+
+        composition.set_master_gain(0.5);
+        assert_ne!(song_key, composition.song_key());
+        composition.render_audio();
+        assert_eq!(composition.song_key(), composition.get_render_key());
+
+        assert_eq!(Composition::default().song_key(), 0);
+    }
+
+    #[test]
+    fn skipping_matches_the_unskipped_render() {
+        let composition = composition_with(&[
+            vec![Line { start: Point2::new(0.0, 0.0), end: Point2::new(2.0, 7.0), velocity: 0.0 }],
+            vec![line(0.5, 1.5, 3.0), line(1.5, 3.0, 5.0)],
+        ]);
+        let rendered = composition.render_samples_f32();
+
+        for n in [0, 1, 30000, rendered.len() - 1, rendered.len()] {
+            let mut source = composition.render_audio();
+            source.skip_samples(n);
+            let skipped: Vec<f32> = source.collect();
+            assert_eq!(skipped, rendered[n..], "skipped {n} samples");
+        }
+    }
+
+    #[test]
+    fn cached_renders_match_cold_renders() {
+        for retain_render_buffers in [false, true] {
+            let mut composition = composition_with(&[vec![line(0.0, 1.0, 0.0)], vec![line(1.0, 2.0, 4.0)], vec![line(2.0, 3.0, 7.0)]]);
+            composition.set_retain_render_buffers(retain_render_buffers);
+            let before = composition.render_samples_f32();
+
+            composition.get_track_mut(0).get_record_system_mut().move_record(1, Vector2::new(0.0, 2.0));
+            let cached = composition.render_samples_f32();
+            assert_ne!(cached, before);
+
+            let records: Vec<Vec<Line>> = composition.get_tracks()[0].get_record_system().records().map(<[Line]>::to_vec).collect();
+            let cold = composition_with(&records).render_samples_f32();
+            assert_eq!(cached, cold);
+        }
+    }
+
+    #[test]
+    fn quantizing_is_one_undoable_edit() {
+        let mut system = RecordSystem::default();
+        system.add_record(vec![line(0.1, 0.9, 0.0), line(0.9, 1.3, 2.0)], StrokeParams::default());
+        system.add_record(vec![line(2.2, 2.7, 5.0)], StrokeParams::default());
+        let drawn = records_of(&system);
+
+        system.quantize(0.5);
+        let quantized = records_of(&system);
+        assert_eq!(quantized[0][0][0], 0.0);
+        assert_eq!(quantized[0][1][2], 1.5);
+        assert_eq!((quantized[1][0][0], quantized[1][0][2]), (2.0, 2.5));
+        assert_indexed(&system);
+
+        system.undo();
+        assert_eq!(records_of(&system), drawn);
+        assert_indexed(&system);
+        system.redo();
+        assert_eq!(records_of(&system), quantized);
+        assert_indexed(&system);
+
+        system.add_record(vec![line(4.0, 5.0, 9.0)], StrokeParams::default());
+        assert_indexed(&system);
+        system.undo();
+        assert_eq!(records_of(&system), quantized);
+        system.undo();
+        assert_eq!(records_of(&system), drawn);
+        assert_indexed(&system);
+
+        system.redo();
+        system.redo();
+        assert_eq!(records_of(&system).len(), 3);
+        assert_eq!(records_of(&system)[..2], quantized);
+        assert_indexed(&system);
+    }
+
+    #[test]
+    fn consolidating_keeps_the_sound() {
+        for instrument in [Instrument::Sine, Instrument::Saw] {
+            let mut composition = composition_with(&[
+                vec![line(0.0, 0.5, 0.0)],
+                vec![line(1.0, 1.5, 4.0), line(1.5, 2.0, 7.0)],
+                vec![line(2.5, 3.0, 2.0)],
+                vec![line(8.0, 9.0, 0.0)],
+            ]);
+            composition.get_track_mut(0).set_instrument(instrument);
+            let rendered = composition.render_samples_f32();
+
+            let release_beats = (composition.get_envelope().release + 0.001) * composition.get_bpm() / 60.0;
+            let merged = composition.get_track_mut(0).get_record_system_mut().consolidate(release_beats, Some(4.0));
+            assert_eq!(merged, 2);
+            assert_eq!(composition.render_samples_f32(), rendered);
+
+            let mut source = composition.render_audio();
+            source.skip_samples(30000);
+            assert_eq!(source.collect::<Vec<f32>>(), rendered[30000..]);
+        }
+    }
+
+    #[test]
+    fn automation_scales_the_covered_samples() {
+        let mut composition = composition_with(&[vec![line(0.0, 4.0, 0.0)]]);
+        let rendered = composition.render_samples_f32();
+
+        composition.get_track_mut(0).push_automation(vec![line(1.0, 2.0, 0.5)]);
+        let automated = composition.render_samples_f32();
+        assert_eq!(automated.len(), rendered.len());
+
+        let samples_per_beat = composition.get_sample_rate() as f32 * 60.0 / composition.get_bpm();
+        let covered = (samples_per_beat as usize)..(2.0 * samples_per_beat) as usize + 1;
+        for (i, (automated, rendered)) in automated.iter().zip(&rendered).enumerate() {
+            let expected = if covered.contains(&i) { rendered * 0.5 } else { *rendered };
+            assert_eq!(*automated, expected, "sample {i}");
+        }
+    }
+
+    #[test]
+    fn stroke_params_override_the_track() {
+        let saw = StrokeParams { instrument: Some(Instrument::Saw), amplitude: None };
+        let mut stroke = Composition::default();
+        stroke.get_track_mut(0).push_record(vec![line(0.0, 1.0, 0.0)], saw);
+        let mut track = composition_with(&[vec![line(0.0, 1.0, 0.0)]]);
+        track.get_track_mut(0).set_instrument(Instrument::Saw);
+        assert_eq!(stroke.render_samples_f32(), track.render_samples_f32());
+
+        let loud = StrokeParams { instrument: None, amplitude: Some(Composition::BASE_AMPLITUDE * 2.0) };
+        let mut louder = Composition::default();
+        louder.get_track_mut(0).push_record(vec![line(0.0, 1.0, 0.0)], loud);
+        let quiet = composition_with(&[vec![line(0.0, 1.0, 0.0)]]).render_samples_f32();
+        for (loud, quiet) in louder.render_samples_f32().iter().zip(&quiet) {
+            assert!((loud - quiet * f32::sqrt(2.0)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn stroke_params_follow_their_records() {
+        let saw = StrokeParams { instrument: Some(Instrument::Saw), amplitude: None };
+        let quiet = StrokeParams { instrument: None, amplitude: Some(0.1) };
+        let mut system = RecordSystem::default();
+        system.add_record(vec![line(0.0, 1.0, 0.0)], saw.clone());
+        system.add_record(vec![line(1.5, 2.0, 0.0)], saw.clone());
+        system.add_record(vec![line(2.5, 3.0, 0.0)], quiet.clone());
+        let params = vec![saw.clone(), saw.clone(), quiet.clone()];
+
+        system.remove_record(1);
+        assert_eq!(system.get_record_params(), [saw.clone(), quiet.clone()]);
+        system.undo();
+        assert_eq!(system.get_record_params(), params);
+
+        system.quantize(0.25);
+        assert_eq!(system.get_record_params(), params);
+
+        assert_eq!(system.consolidate(0.0, None), 1);
+        assert_eq!(system.get_record_params(), [saw.clone(), quiet.clone()]);
+        system.undo();
+        assert_eq!(system.get_record_params(), params);
+        system.redo();
+        assert_eq!(system.get_record_params(), [saw, quiet]);
+    }
+
+    #[test]
+    fn mix_modes_on_a_two_voice_chord() {
+        let render = |records: &[Vec<Line>], mix_mode: MixMode| {
+            let mut composition = composition_with(records);
+            composition.set_mix_mode(mix_mode);
+            composition.render_samples_f32()
+        };
+        let (root, fifth) = (vec![line(0.0, 2.0, 0.0)], vec![line(0.0, 2.0, 7.0)]);
+        let a = render(std::slice::from_ref(&root), MixMode::Sum);
+        let b = render(std::slice::from_ref(&fifth), MixMode::Sum);
+        let chord = [root, fifth];
+
+        let composition = Composition::default();
+        let samples_per_sec = composition.get_sample_rate() as f32;
+        // Both voices sound at full envelope in between, the modes only differ by their divisor there.
+        let sustain = ((composition.get_envelope().attack + 0.01) * samples_per_sec) as usize..(2.0 * 60.0 / composition.get_bpm() * samples_per_sec) as usize;
+        let amplitude = 2.0 * Composition::BASE_AMPLITUDE;
+
+        let sum = render(&chord, MixMode::Sum);
+        let average = render(&chord, MixMode::Average);
+        let rms = render(&chord, MixMode::RmsNormalized);
+        for i in 0..sum.len() {
+            assert!((sum[i] - (a[i] + b[i])).abs() < 1e-6);
+        }
+        for i in sustain {
+            assert!((average[i] - (a[i] + b[i]) / 2.0).abs() < 1e-6);
+            assert!((rms[i] - (a[i] + b[i]) / amplitude.sqrt()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn glide_slides_between_close_lines() {
+        let tones = |lines: Vec<Line>, glide_secs: f32| {
+            let mut composition = composition_with(&[lines]);
+            composition.set_bpm(120.0);
+            composition.set_glide_secs(glide_secs);
+            composition.render_audio().voices().next().unwrap().to_vec()
+        };
+        let sample_rate = Composition::default().get_sample_rate() as f32;
+        let at = |secs: f32| (secs * sample_rate) as usize;
+
+        // An octave jump at 0.5 s, after a 0.2 s gap and after a 1 s gap, at 120 bpm.
+        for (second_line, jump_secs, slides) in [(line(1.0, 3.0, 12.0), 0.5, true), (line(1.4, 3.0, 12.0), 0.7, true), (line(3.0, 5.0, 12.0), 1.5, false)] {
+            let lines = vec![line(0.0, 1.0, 0.0), second_line];
+            let straight = tones(lines.clone(), 0.0);
+            let glided = tones(lines, 0.5);
+
+            assert!(straight.iter().zip(&glided).all(|(straight, glided)| straight.amplitude == glided.amplitude));
+            let ratio = |secs: f32| glided[at(secs)].frequency / straight[at(secs)].frequency;
+            assert_eq!(ratio(0.25), 1.0);
+            if slides {
+                assert!(ratio(jump_secs + 0.01) < 0.55);
+                assert!(ratio(jump_secs + 0.25) < 0.99);
+                assert_eq!(ratio(jump_secs + 0.55), 1.0);
+            } else {
+                assert_eq!(ratio(jump_secs), 1.0);
+            }
+        }
+        let jump = tones(vec![line(0.0, 1.0, 0.0), line(1.0, 3.0, 12.0)], 0.5);
+        assert!((jump[at(0.75)].frequency / jump[at(0.25)].frequency - f32::sqrt(2.0)).abs() < 1e-2);
+    }
+
+    #[test]
+    fn removed_tracks_leave_the_mix() {
+        let mut composition = composition_with(&[vec![line(0.0, 1.0, 0.0)]]);
+        composition.add_track().push_record(vec![line(0.5, 1.5, 7.0)], StrokeParams::default());
+        let rendered = composition.render_samples_f32();
+
+        let track = composition.remove_track(0);
+        assert_eq!(composition.get_tracks().len(), 1);
+        let mut second = Composition::default();
+        second.get_track_mut(0).push_record(vec![line(0.5, 1.5, 7.0)], StrokeParams::default());
+        assert_eq!(composition.render_samples_f32(), second.render_samples_f32());
+
+        composition.insert_track(0, track);
+        assert_eq!(composition.render_samples_f32(), rendered);
+
+        let only = second.remove_track(0);
+        assert_eq!(only.get_record_system().get_record_count(), 1);
+        assert_eq!(second.get_tracks().len(), 1);
+        assert_eq!(second.get_tracks()[0].get_record_system().get_record_count(), 0);
+    }
+
+    #[test]
+    fn reverb_adds_its_tail() {
+        let mut composition = composition_with(&[vec![line(0.0, 2.0, 0.0)]]);
+        let dry = composition.render_samples_f32();
+        composition.set_reverb(Reverb { wet: 0.0, room_size: 0.9 });
+        assert_eq!(composition.render_samples_f32(), dry);
+
+        let reverb = Reverb { wet: 0.3, room_size: 0.5 };
+        composition.set_reverb(reverb);
+        let wet = composition.render_samples_f32();
+        let tail_samples = (reverb.tail_secs() * composition.get_sample_rate() as f32).ceil() as usize;
+        assert_eq!(wet.len(), dry.len() + tail_samples);
+        assert_ne!(wet[..dry.len()], dry);
+        assert!(wet[wet.len() - 100..].iter().all(|sample| sample.abs() < 1e-3));
+    }
+
+    #[test]
+    fn splines_pass_through_the_drawn_points() {
+        let point = |x: f32, y: f32| Point2::new(x, y);
+        let drawn = [point(0.0, 0.0), point(0.5, 3.0), point(1.0, 1.0), point(2.0, 5.0)];
+        let lines: Vec<Line> = drawn.windows(2).map(|ends| Line { start: ends[0], end: ends[1], velocity: 0.0 }).collect();
+
+        let smoothed = spline_lines(&lines);
+        assert!(smoothed.windows(2).all(|pair| pair[0].end == pair[1].start));
+        assert!(smoothed.iter().all(|line| line.end.x - line.start.x <= 1.0 / 64.0 + 1e-6));
+        for drawn in drawn {
+            assert!(smoothed.iter().any(|line| line.start == drawn || line.end == drawn));
+        }
+
+        let single = Line { start: point(0.0, 0.0), end: point(1.0, 2.0), velocity: 0.0 };
+        for line in spline_lines(std::slice::from_ref(&single)) {
+            for end in [line.start, line.end] {
+                assert!((end.y - end.x * 2.0).abs() < 1e-5);
+            }
+        }
+
+        let mut composition = composition_with(&[lines]);
+        let linear = composition.render_samples_f32();
+        composition.set_pitch_interpolation(PitchInterpolation::Spline);
+        assert_ne!(composition.render_samples_f32(), linear);
+    }
+
+    #[test]
+    fn triplet_swing_delays_the_off_beat() {
+        let mut composition = Composition::default();
+        let lines = [line(0.0, 2.0, 0.0)];
+        assert!(matches!(composition.prepare_record(&lines), Cow::Borrowed(_)));
+
+        composition.set_swing(1.0 / 3.0, 1.0);
+        assert!((composition.get_played_beat(0.5) - 2.0 / 3.0).abs() < 1e-6);
+        assert!((composition.get_played_beat(1.5) - 5.0 / 3.0).abs() < 1e-6);
+        assert_eq!(composition.get_played_beat(1.0), 1.0);
+        for beat in [0.0, 0.25, 0.5, 0.6, 1.2, 3.9] {
+            assert!((composition.get_drawn_beat(composition.get_played_beat(beat)) - beat).abs() < 1e-5);
+        }
+
+        // Split at every off-beat and pair start, each piece moved to where it plays.
+        let swung = composition.prepare_record(&lines);
+        let ends: Vec<f32> = swung.iter().map(|line| line.end.x).collect();
+        let expected = [2.0 / 3.0, 1.0, 5.0 / 3.0, 2.0];
+        assert_eq!(ends.len(), expected.len());
+        assert!(ends.iter().zip(expected).all(|(end, expected)| (end - expected).abs() < 1e-5));
+    }
+
+    #[test]
+    fn skipped_tremolo_stays_in_phase() {
+        let mut composition = composition_with(&[vec![line(0.0, 4.0, 0.0)]]);
+        let dry = composition.render_samples_f32();
+        composition.set_tremolo(Tremolo { rate: TremoloRate::Beats(1.0), depth: 0.0 });
+        assert_eq!(composition.render_samples_f32(), dry);
+
+        composition.set_tremolo(Tremolo { rate: TremoloRate::Beats(1.0), depth: 1.0 });
+        let rendered = composition.render_samples_f32();
+        let mut source = composition.render_audio();
+        source.skip_samples(10000);
+        // The phase is summed in a different order than playing adds it up, out of phase would be off by far more.
+        for (skipped, rendered) in source.zip(&rendered[10000..]) {
+            assert!((skipped - rendered).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn moving_a_record_is_undoable() {
+        let mut system = RecordSystem::default();
+        system.add_record(vec![line(0.0, 1.0, 0.0)], StrokeParams::default());
+        system.add_record(vec![line(2.0, 2.5, 4.0), line(2.5, 3.0, 5.0)], StrokeParams::default());
+        system.add_record(vec![line(4.0, 5.0, 0.0)], StrokeParams::default());
+        let drawn = records_of(&system);
+        let radius = Vector2::new(0.05, 0.05);
+
+        system.move_record(1, Vector2::new(0.5, 2.0));
+        let moved = records_of(&system);
+        assert_eq!(moved[1], [[2.5, 6.0, 3.0, 6.0, 0.0], [3.0, 7.0, 3.5, 7.0, 0.0]]);
+        assert_eq!((&moved[0], &moved[2]), (&drawn[0], &drawn[2]));
+        assert_eq!(system.find_record_near(Point2::new(2.75, 6.0), radius), Some(1));
+        assert_eq!(system.find_record_near(Point2::new(2.25, 4.0), radius), None);
+        assert_indexed(&system);
+
+        system.undo();
+        assert_eq!(records_of(&system), drawn);
+        assert_eq!(system.find_record_near(Point2::new(2.25, 4.0), radius), Some(1));
+        assert_indexed(&system);
+        system.redo();
+        assert_eq!(records_of(&system), moved);
+        assert_indexed(&system);
+    }
+
+    #[test]
+    fn undo_history_is_capped() {
+        let mut composition = Composition::default();
+        composition.set_undo_limit(3);
+        let system = composition.get_track_mut(0).get_record_system_mut();
+        for i in 0..10 {
+            system.add_record(vec![line(i as f32, i as f32 + 0.5, 0.0)], StrokeParams::default());
+        }
+
+        for _ in 0..5 {
+            system.undo();
+        }
+        assert_eq!(system.get_record_count(), 7);
+        for _ in 0..5 {
+            system.redo();
+        }
+        assert_eq!(system.get_record_count(), 10);
+
+        assert_eq!(composition.add_track().get_record_system().get_undo_limit(), 3);
+        assert_eq!(composition.get_tracks()[0].get_automation().get_undo_limit(), 3);
+    }
+
+    #[test]
+    fn clearing_is_one_undoable_edit() {
+        // Clearing nothing leaves no edit, so the redo history survives it.
+        let mut system = RecordSystem::default();
+        system.add_record(vec![line(0.0, 0.5, 0.0)], StrokeParams::default());
+        system.undo();
+        system.clear();
+        system.redo();
+        assert_eq!(system.get_record_count(), 1);
+        system.undo();
+
+        for i in 0..4 {
+            system.add_record(vec![line(i as f32, i as f32 + 0.5, i as f32)], StrokeParams::default());
+        }
+        let drawn = records_of(&system);
+
+        system.clear();
+        assert_eq!(system.get_record_count(), 0);
+        assert_eq!(system.take_dirty_from(), Some(0));
+        system.undo();
+        assert_eq!(records_of(&system), drawn);
+        assert_indexed(&system);
+        system.redo();
+        assert_eq!(system.get_record_count(), 0);
+
+        system.undo();
+        system.add_record(vec![line(5.0, 6.0, 0.0)], StrokeParams::default());
+        system.redo();
+        assert_eq!(system.get_record_count(), 5);
+    }
+
+    #[test]
+    fn tone_samples_yield_one_sample_per_tone() {
+        let tone = |amplitude: f32| Tone { frequency: 440.0, amplitude };
+        let immediate = Envelope { attack: 0.0, release: 0.0 };
+        for tones in [vec![], vec![tone(0.5)], vec![tone(0.5), tone(0.0), tone(0.25), tone(0.25), tone(0.0)]] {
+            let mut tone_samples = ToneSamples::new(tones.clone().into(), Instrument::Sine, 0, false, immediate, 44100);
+            assert_eq!(tone_samples.last_amplitude(), 0.0);
+
+            for tone in &tones {
+                assert!(tone_samples.next().is_some());
+                assert_eq!(tone_samples.last_amplitude(), tone.amplitude);
+            }
+            assert!(tone_samples.next().is_none());
+            assert!(tone_samples.next().is_none());
+        }
+    }
+
+    #[test]
+    fn samples_need_a_positive_base_frequency() {
+        for base_frequency in [0.0, -440.0, f32::NAN, f32::INFINITY] {
+            let error = Sample::load(Path::new("kick.wav"), base_frequency).err().unwrap();
+            assert!(error.contains("base frequency"), "{}", error);
+        }
+
+        let mut sample = Sample { path: PathBuf::new(), data: (0..64).map(|i| (i as f32 * 0.3).sin()).collect(), sample_rate: 44100, base_frequency: 440.0 };
+        sample.set_base_frequency(0.0);
+        assert_eq!(sample.get_base_frequency(), Sample::MIN_BASE_FREQUENCY);
+        sample.set_base_frequency(f32::NAN);
+        assert_eq!(sample.get_base_frequency(), Sample::DEFAULT_BASE_FREQUENCY);
+        sample.set_base_frequency(-1.0);
+
+        let mut composition = composition_with(&[vec![line(0.0, 1.0, 0.0)]]);
+        composition.get_track_mut(0).set_instrument(Instrument::Sample(Arc::new(sample)));
+        let rendered = composition.render_samples_f32();
+        assert!(rendered.iter().all(|sample| sample.is_finite()));
+        assert!(rendered.iter().any(|sample| *sample != 0.0));
+    }
+
+    #[test]
+    fn clicking_without_dragging_doesnt_swallow_an_undo() {
+        let mut system = RecordSystem::default();
+        system.add_record(vec![line(0.0, 1.0, 0.0)], StrokeParams::default());
+        let before = records_of(&system);
+
+        system.new_record(StrokeParams::default());
+        system.add_line(Point2::new(2.0, 1.0), Point2::new(3.0, 1.0), 0.1);
+        system.add_line(Point2::new(3.0, 1.0), Point2::new(4.0, 2.0), 0.1);
+        let drawn = records_of(&system);
+        system.new_record(StrokeParams::default());
+        assert_eq!(system.get_record_count(), 2);
+
+        system.undo();
+        assert_eq!(records_of(&system), before);
+        assert_indexed(&system);
+
+        system.redo();
+        assert_eq!(records_of(&system), drawn);
+        assert_indexed(&system);
+
+        system.undo();
+        system.undo();
+        assert_eq!(system.get_record_count(), 0);
+    }
+
+    #[test]
+    fn degenerate_lines_are_dropped() {
+        let good = line(0.0, 1.0, 0.0);
+        let degenerate = [
+            Line { start: Point2::new(f32::NAN, 0.0), end: Point2::new(1.0, 0.0), velocity: 0.0 },
+            Line { start: Point2::new(0.0, 0.0), end: Point2::new(1.0, f32::INFINITY), velocity: 0.0 },
+            Line { start: Point2::new(0.0, 0.0), end: Point2::new(1.0, 0.0), velocity: f32::NAN },
+            line(-2.0, 1.0, 0.0),
+            line(1.0, -2.0, 0.0),
+        ];
+        assert!(good.is_renderable());
+        assert!(degenerate.iter().all(|line| !line.is_renderable()));
+
+        let mut lines = vec![good.clone()];
+        lines.extend(degenerate.iter().cloned());
+        let composition = composition_with(&[lines.clone()]);
+        assert_eq!(composition.prepare_record(&lines).len(), 1);
+
+        let rendered = composition.render_samples_f32();
+        assert!(rendered.iter().all(|sample| sample.is_finite()));
+        assert_eq!(rendered, composition_with(&[vec![good]]).render_samples_f32());
+        assert!(composition_with(&[degenerate.to_vec()]).render_samples_f32().is_empty());
+    }
+
+    #[test]
+    fn huge_lines_render_at_most_the_longest_song() {
+        let mut composition = Composition::new(TimelineConfig { sample_rate: 100, ..Default::default() });
+        composition.get_track_mut(0).push_record(vec![line(0.0, 1.0e9, 0.0)], StrokeParams::default());
+        composition.get_track_mut(0).push_record(vec![line(f32::MAX / 2.0, f32::MAX, 3.0)], StrokeParams::default());
+
+        let rendered = composition.render_samples_f32();
+        let max_length = (Composition::MAX_RENDER_SECS * composition.get_sample_rate() as f32) as usize + 1;
+        assert_eq!(rendered.len(), max_length);
+        assert!(rendered.iter().all(|sample| sample.is_finite()));
+    }
+
+    #[test]
+    fn band_limiting_cuts_the_aliases_of_a_high_saw() {
+        const LENGTH: usize = 4096;
+        const SAMPLE_RATE: u32 = 44100;
+        // A whole number of bins, so only aliases land between the harmonics.
+        const FUNDAMENTAL_BIN: usize = 467;
+        let frequency = FUNDAMENTAL_BIN as f32 * SAMPLE_RATE as f32 / LENGTH as f32;
+
+        let alias_energy = |band_limited: bool| {
+            let tones: Arc<[Tone]> = vec![Tone { frequency, amplitude: 0.5 }; LENGTH].into();
+            let tone_samples = ToneSamples::new(tones, Instrument::Saw, 0, band_limited, Envelope { attack: 0.0, release: 0.0 }, SAMPLE_RATE);
+            let mut buffer: Vec<nalgebra::Complex<f32>> = tone_samples
+                .enumerate()
+                .map(|(i, sample)| {
+                    let window = 0.5 - 0.5 * f32::cos(f32::consts::TAU * i as f32 / LENGTH as f32);
+                    nalgebra::Complex::new(sample * window, 0.0)
+                })
+                .collect();
+            crate::spectrogram::fft(&mut buffer);
+
+            buffer[..LENGTH / 2]
+                .iter()
+                .enumerate()
+                .filter(|(bin, _)| {
+                    let harmonic = (*bin + FUNDAMENTAL_BIN / 2) % FUNDAMENTAL_BIN;
+                    harmonic.abs_diff(FUNDAMENTAL_BIN / 2) > 3
+                })
+                .map(|(_, value)| value.norm_sqr())
+                .sum::<f32>()
+        };
+
+        let naive = alias_energy(false);
+        let band_limited = alias_energy(true);
+        assert!(band_limited < naive * 0.1, "band limited {} naive {}", band_limited, naive);
+    }
+
+    #[test]
+    fn removing_a_middle_record_keeps_the_index_in_step() {
+        let radius = Vector2::new(0.01, 0.01);
+        let mut system = RecordSystem::default();
+        system.add_record(vec![line(0.0, 1.0, 0.0), line(1.0, 2.0, 1.0)], StrokeParams::default());
+        system.add_record(vec![line(2.0, 3.0, 5.0), line(3.0, 4.0, 6.0), line(4.0, 5.0, 7.0)], StrokeParams::default());
+        system.add_record(vec![line(5.0, 6.0, 2.0), line(6.0, 7.0, 3.0)], StrokeParams::default());
+        let drawn = records_of(&system);
+        let (middle, last) = (Point2::new(3.5, 6.0), Point2::new(6.5, 3.0));
+
+        let removed = system.find_record_near(middle, radius).unwrap();
+        assert_eq!(removed, 1);
+        system.remove_record(removed);
+        assert_eq!(system.history, [0..2, 2..4]);
+        assert_eq!(system.find_record_near(middle, radius), None);
+        assert_eq!(system.find_record_near(last, radius), Some(1));
+        assert_indexed(&system);
+
+        system.undo();
+        assert_eq!(system.history, [0..2, 2..5, 5..7]);
+        assert_eq!(records_of(&system), drawn);
+        assert_eq!(system.find_record_near(middle, radius), Some(1));
+        assert_eq!(system.find_record_near(last, radius), Some(2));
+        assert_indexed(&system);
+
+        system.redo();
+        assert_eq!(system.history, [0..2, 2..4]);
+        assert_eq!(system.find_record_near(middle, radius), None);
+        assert_eq!(system.find_record_near(last, radius), Some(1));
+        assert_indexed(&system);
+
+        system.new_record(StrokeParams::default());
+        system.add_line(Point2::new(8.0, 4.0), Point2::new(9.0, 4.0), 0.1);
+        assert_eq!(system.history, [0..2, 2..4, 4..5]);
+        assert_eq!(system.find_record_near(Point2::new(8.5, 4.0), radius), Some(2));
+        assert_indexed(&system);
+
+        system.undo();
+        system.undo();
+        assert_eq!(system.history, [0..2, 2..5, 5..7]);
+        assert_eq!(records_of(&system), drawn);
+        assert_eq!(system.find_record_near(Point2::new(8.5, 4.0), radius), None);
+        assert_indexed(&system);
+    }
+
+    #[test]
+    fn snapped_points_follow_the_grid_and_the_scale() {
+        let mut composition = Composition::default();
+        let position = Point2::new(1.1, tuning::y_from_pitch(1.2));
+        assert_eq!(composition.get_snapped_point(position), position);
+
+        composition.set_scale(Some(Scale::Major));
+        let snapped = composition.get_snapped_point(position);
+        assert_eq!(snapped, Point2::new(1.1, tuning::y_from_pitch(2.0)));
+        assert_eq!(tuning::pitch_from_y(snapped.y), composition.get_pitch_at(position.y));
+
+        composition.set_time_quantize(Some(0.5));
+        assert_eq!(composition.get_snapped_point(position), Point2::new(1.0, tuning::y_from_pitch(2.0)));
+        composition.set_scale(None);
+        assert_eq!(composition.get_snapped_point(position), Point2::new(1.0, position.y));
+    }
+}
@@ -0,0 +1,276 @@
+use std::{fs, io::ErrorKind, path::Path, str::FromStr};
+
+use nalgebra::Vector4;
+
+use crate::{composition::Instrument, effects::{DelayTime, TremoloRate}, export::ExportSettings, spectrogram::SpectrogramSettings, timeline::{GridConfig, PlaylineStyle, TimelineConfig, ToneLineStyle, ViewConfig}};
+
+/// Window settings, applied to the [crate::engine::window::WindowBuilder] at startup.
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    /// F9 toggles it at runtime.
+    pub vsync: bool,
+    /// 0 doesn't limit the frame rate.
+    pub max_fps: u32,
+    /// Samples per pixel, 0 disables multisampling.
+    pub msaa: u32,
+    /// Size of Shift+F12 screenshots, 0 is twice the window size.
+    pub screenshot_width: u32,
+    pub screenshot_height: u32,
+}
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: String::from("Note painter"),
+            width: 800,
+            height: 600,
+            vsync: false,
+            max_fps: 200,
+            msaa: 0,
+            screenshot_width: 0,
+            screenshot_height: 0,
+        }
+    }
+}
+impl WindowConfig {
+    /// Screenshot size for a window of `width` by `height`.
+    pub fn get_screenshot_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let or_double = |size: u32, window_size: u32| if size > 0 { size } else { window_size.max(1) * 2 };
+        (or_double(self.screenshot_width, width), or_double(self.screenshot_height, height))
+    }
+}
+
+/// User settings from `config.toml`, anything missing keeps its default.
+///
+/// Only the subset of TOML the settings need is understood: `[section]` headers, `key = value` pairs,
+/// numbers, booleans, quoted strings, arrays of numbers and `#` comments.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub timeline: TimelineConfig,
+    pub view: ViewConfig,
+    pub grid: GridConfig,
+    pub playline: PlaylineStyle,
+    pub tone_lines: ToneLineStyle,
+    pub spectrogram: SpectrogramSettings,
+    pub export: ExportSettings,
+}
+impl Config {
+    pub const DEFAULT_PATH: &str = "config.toml";
+
+    /// A missing file gives the defaults, broken lines are reported and skipped.
+    pub fn load(path: &Path) -> Self {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Self::default(),
+            Err(error) => {
+                eprintln!("Failed to open config at: {}. Error: {}. Using the defaults.", path.display(), error);
+                return Self::default();
+            }
+        };
+
+        Self::parse(&text, path)
+    }
+    /// `path` only names the file in the warnings.
+    fn parse(text: &str, path: &Path) -> Self {
+        let mut config = Self::default();
+        let mut section = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let result = if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = String::from(name.trim());
+                Ok(())
+            } else if let Some((key, value)) = line.split_once('=') {
+                config.set(&section, key.trim(), value.trim())
+            } else {
+                Err(String::from("Expected `key = value` or a `[section]`."))
+            };
+
+            if let Err(error) = result {
+                eprintln!("Ignoring line {} of config at: {}. Error: {}", i + 1, path.display(), error);
+            }
+        }
+
+        config
+    }
+
+    fn set(&mut self, section: &str, key: &str, value: &str) -> Result<(), String> {
+        match (section, key) {
+            ("window", "title") => self.window.title = parse_string(value)?,
+            ("window", "width") => self.window.width = parse_value(value)?,
+            ("window", "height") => self.window.height = parse_value(value)?,
+            ("window", "vsync") => self.window.vsync = parse_value(value)?,
+            ("window", "max_fps") => self.window.max_fps = parse_value(value)?,
+            ("window", "msaa") => self.window.msaa = parse_value(value)?,
+            ("window", "screenshot_width") => self.window.screenshot_width = parse_value(value)?,
+            ("window", "screenshot_height") => self.window.screenshot_height = parse_value(value)?,
+
+            ("timeline", "bpm") => self.timeline.bpm = parse_value(value)?,
+            ("timeline", "sample_rate") => self.timeline.sample_rate = parse_value(value)?,
+            ("timeline", "master_gain") => self.timeline.master_gain = parse_value(value)?,
+            ("timeline", "instrument") => self.timeline.default_instrument = match parse_string(value)?.as_str() {
+                "sine" => Instrument::Sine,
+                "square" => Instrument::Square,
+                "saw" => Instrument::Saw,
+                "noise" => Instrument::Noise,
+                name => return Err(format!("Unknown instrument: {}.", name)),
+            },
+            ("timeline", "attack") => self.timeline.envelope.attack = parse_value(value)?,
+            ("timeline", "release") => self.timeline.envelope.release = parse_value(value)?,
+            ("timeline", "glide") => self.timeline.glide_secs = parse_value(value)?,
+            ("timeline", "undo_limit") => self.timeline.undo_limit = parse_value(value)?,
+            ("timeline", "bitcrush_bits") => self.timeline.bitcrusher.bits = parse_value(value)?,
+            ("timeline", "bitcrush_hold") => self.timeline.bitcrusher.hold = parse_value(value)?,
+            ("timeline", "delay_ms") => self.timeline.delay.time = DelayTime::Millis(parse_value(value)?),
+            ("timeline", "delay_beats") => self.timeline.delay.time = DelayTime::Beats(parse_value(value)?),
+            ("timeline", "delay_feedback") => self.timeline.delay.feedback = parse_value(value)?,
+            ("timeline", "delay_wet") => self.timeline.delay.wet = parse_value(value)?,
+            ("timeline", "tremolo_hz") => self.timeline.tremolo.rate = TremoloRate::Hz(parse_value(value)?),
+            ("timeline", "tremolo_beats") => self.timeline.tremolo.rate = TremoloRate::Beats(parse_value(value)?),
+            ("timeline", "tremolo_depth") => self.timeline.tremolo.depth = parse_value(value)?,
+            ("timeline", "reverb_wet") => self.timeline.reverb.wet = parse_value(value)?,
+            ("timeline", "reverb_room_size") => self.timeline.reverb.room_size = parse_value(value)?,
+
+            ("view", "sharpness") => self.view.sharpness = parse_value(value)?,
+            ("view", "scale_speed") => self.view.scale_speed = parse_value(value)?,
+            ("view", "scroll_speed_x") => self.view.scroll_speed_x = parse_value(value)?,
+            ("view", "scroll_speed_y") => self.view.scroll_speed_y = parse_value(value)?,
+            ("view", "gamepad_pan_speed") => self.view.gamepad_pan_speed = parse_value(value)?,
+            ("view", "scale_x_min") => self.view.scale_x_min = parse_value(value)?,
+            ("view", "scale_x_max") => self.view.scale_x_max = parse_value(value)?,
+            ("view", "scale_y_min") => self.view.scale_y_min = parse_value(value)?,
+            ("view", "scale_y_max") => self.view.scale_y_max = parse_value(value)?,
+
+            ("grid", "semitone_color") => self.grid.semitone_color = parse_color(value)?,
+            ("grid", "octave_color") => self.grid.octave_color = parse_color(value)?,
+            ("grid", "semitone_width") => self.grid.semitone_width = parse_value(value)?,
+            ("grid", "octave_width") => self.grid.octave_width = parse_value(value)?,
+            ("grid", "bar_color") => self.grid.bar_color = parse_color(value)?,
+            ("grid", "beat_color") => self.grid.beat_color = parse_color(value)?,
+            ("grid", "subdivision_color") => self.grid.subdivision_color = parse_color(value)?,
+            ("grid", "bar_width") => self.grid.bar_width = parse_value(value)?,
+            ("grid", "beat_width") => self.grid.beat_width = parse_value(value)?,
+            ("grid", "subdivision_width") => self.grid.subdivision_width = parse_value(value)?,
+            ("grid", "beat_subdivisions") => self.grid.beat_subdivisions = parse_value(value)?,
+
+            ("playline", "color") => self.playline.color = parse_color(value)?,
+            ("playline", "base_color") => self.playline.base_color = parse_color(value)?,
+            ("playline", "width") => self.playline.width = parse_value(value)?,
+            ("playline", "loop_region_color") => self.playline.loop_region_color = parse_color(value)?,
+
+            ("tone_lines", "width") => self.tone_lines.width = parse_value(value)?,
+            ("tone_lines", "feather") => self.tone_lines.feather = parse_value(value)?,
+
+            ("spectrogram", "fft_size") => self.spectrogram.fft_size = parse_value(value)?,
+            ("spectrogram", "hop") => self.spectrogram.hop = parse_value(value)?,
+            ("spectrogram", "floor_db") => self.spectrogram.floor_db = parse_value(value)?,
+
+            ("export", "loop_count") => self.export.loop_count = parse_value(value)?,
+            // 0 keeps the rendered length.
+            ("export", "duration") => self.export.duration_secs = Some(parse_value(value)?).filter(|secs: &f32| *secs > 0.0),
+
+            _ => return Err(format!("Unknown key {} in section [{}].", key, section)),
+        }
+
+        Ok(())
+    }
+}
+
+/// Cuts a `#` comment off, unless the `#` is inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, character) in line.char_indices() {
+        match character {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+
+    line
+}
+fn parse_string(value: &str) -> Result<String, String> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .map(String::from)
+        .ok_or_else(|| format!("Expected a quoted string, got: {}.", value))
+}
+fn parse_value<T: FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value: {}.", value))
+}
+/// `[r, g, b, a]` with every component from 0 to 1.
+fn parse_color(value: &str) -> Result<Vector4<f32>, String> {
+    let components = value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .ok_or_else(|| format!("Expected an [r, g, b, a] array, got: {}.", value))?
+        .split(',')
+        .map(|component| parse_value::<f32>(component.trim()))
+        .collect::<Result<Vec<f32>, String>>()?;
+
+    match components[..] {
+        [r, g, b, a] => Ok(Vector4::new(r, g, b, a)),
+        _ => Err(format!("Expected 4 color components, got: {}.", components.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Config {
+        Config::parse(text, Path::new(Config::DEFAULT_PATH))
+    }
+
+    #[test]
+    fn hashes_in_strings_arent_comments() {
+        let config = parse("[window]\ntitle = \"Song #1\" # the title\n");
+        assert_eq!(config.window.title, "Song #1");
+    }
+
+    #[test]
+    fn broken_lines_keep_their_defaults() {
+        let defaults = Config::default();
+        let config = parse(concat!(
+            "[window]\n",
+            "width = wide\n",
+            "height = 720\n",
+            "colour = 3\n",
+            "[unknown]\n",
+            "width = 1\n",
+            "[grid]\n",
+            "bar_color = [1.0, 0.5, 0.25]\n",
+            "beat_color = [0.1, 0.2, 0.3, 0.4]\n",
+            "just some words\n",
+        ));
+
+        assert_eq!(config.window.width, defaults.window.width);
+        assert_eq!(config.window.height, 720);
+        assert_eq!(config.grid.bar_color, defaults.grid.bar_color);
+        assert_eq!(config.grid.beat_color, Vector4::new(0.1, 0.2, 0.3, 0.4));
+        assert_eq!(config.window.title, defaults.window.title);
+        assert_eq!(config.timeline.bpm, defaults.timeline.bpm);
+    }
+
+    #[test]
+    fn spaces_and_comments_are_optional() {
+        let config = parse("# settings\n[timeline] # tempo and such\nbpm=90\nmaster_gain =0.5\n\n[ window ]\nvsync= true\n");
+        assert_eq!(config.timeline.bpm, 90.0);
+        assert_eq!(config.timeline.master_gain, 0.5);
+        assert!(config.window.vsync);
+        assert_eq!(config.window.max_fps, Config::default().window.max_fps);
+    }
+
+    #[test]
+    fn missing_files_give_the_defaults() {
+        let config = Config::load(Path::new("missing/config.toml"));
+        assert_eq!(config.window.title, Config::default().window.title);
+    }
+}
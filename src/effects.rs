@@ -0,0 +1,517 @@
+use std::f32::consts::TAU;
+
+/// Converts decibels to a linear gain.
+pub fn gain_from_db(db: f32) -> f32 {
+    f32::powf(10.0, db / 20.0)
+}
+/// Converts a linear gain to decibels, silence is clamped to -120 dB.
+pub fn db_from_gain(gain: f32) -> f32 {
+    20.0 * gain.max(1e-6).log10()
+}
+
+/// Smoothing coefficient of a one pole filter reaching ~63% of a step after `secs`.
+fn time_coefficient(secs: f32, sample_rate: u32) -> f32 {
+    if secs <= 0.0 {
+        0.0
+    } else {
+        f32::exp(-1.0 / (secs * sample_rate as f32))
+    }
+}
+
+/// Feed-forward peak compressor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Compressor {
+    /// Level in dB above which the gain is reduced.
+    pub threshold: f32,
+    /// How many dB of input above the threshold give 1 dB of output.
+    pub ratio: f32,
+    /// Seconds the gain reduction takes to react to a louder input.
+    pub attack: f32,
+    /// Seconds the gain reduction takes to recover after the input gets quieter.
+    pub release: f32,
+    /// Gain in dB applied after compressing.
+    pub makeup: f32,
+}
+impl Default for Compressor {
+    fn default() -> Self {
+        Self {
+            threshold: -12.0,
+            ratio: 4.0,
+            attack: 0.01,
+            release: 0.15,
+            makeup: 3.0,
+        }
+    }
+}
+
+/// Running state of a [Compressor], one per stream of samples.
+pub struct CompressorState {
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    slope: f32,
+    threshold: f32,
+    makeup: f32,
+
+    envelope: f32,
+}
+impl CompressorState {
+    pub fn new(compressor: &Compressor, sample_rate: u32) -> Self {
+        Self {
+            attack_coefficient: time_coefficient(compressor.attack, sample_rate),
+            release_coefficient: time_coefficient(compressor.release, sample_rate),
+            slope: 1.0 - 1.0 / compressor.ratio.max(1.0),
+            threshold: compressor.threshold,
+            makeup: compressor.makeup,
+
+            envelope: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let level = sample.abs();
+        let coefficient = if level > self.envelope { self.attack_coefficient } else { self.release_coefficient };
+        self.envelope = level + coefficient * (self.envelope - level);
+
+        let over = db_from_gain(self.envelope) - self.threshold;
+        let reduction = if over > 0.0 { over * self.slope } else { 0.0 };
+
+        sample * gain_from_db(self.makeup - reduction)
+    }
+}
+
+/// Freeverb style reverb: damped comb filters in parallel, followed by allpass filters in series.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reverb {
+    /// Share of the reverb in the output from 0 to 1, 0 leaves the mix untouched.
+    pub wet: f32,
+    /// From 0 to 1, bigger rooms ring longer.
+    pub room_size: f32,
+}
+impl Default for Reverb {
+    fn default() -> Self {
+        Self {
+            wet: 0.0,
+            room_size: 0.5,
+        }
+    }
+}
+impl Reverb {
+    /// Delays in samples at [Self::DELAY_SAMPLE_RATE], spread apart so the combs don't resonate together.
+    const COMB_DELAYS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+    const ALLPASS_DELAYS: [usize; 4] = [556, 441, 341, 225];
+    const DELAY_SAMPLE_RATE: f32 = 44100.0;
+
+    pub fn is_enabled(&self) -> bool {
+        self.wet > 0.0
+    }
+
+    fn feedback(&self) -> f32 {
+        0.7 + 0.28 * self.room_size.clamp(0.0, 1.0)
+    }
+    /// Seconds the reverb keeps ringing after the input goes silent, until it's 60 dB down. 0 when disabled.
+    pub fn tail_secs(&self) -> f32 {
+        if !self.is_enabled() {
+            return 0.0;
+        }
+
+        let longest_comb_secs = Self::COMB_DELAYS[Self::COMB_DELAYS.len() - 1] as f32 / Self::DELAY_SAMPLE_RATE;
+        let allpass_secs = Self::ALLPASS_DELAYS.iter().sum::<usize>() as f32 / Self::DELAY_SAMPLE_RATE;
+        60.0 / -db_from_gain(self.feedback()) * longest_comb_secs + allpass_secs
+    }
+}
+
+/// Circular buffer holding the last `delay` samples.
+struct DelayLine {
+    buffer: Box<[f32]>,
+    position: usize,
+}
+impl DelayLine {
+    fn new(delay: usize) -> Self {
+        Self { buffer: vec![0.0; delay.max(1)].into_boxed_slice(), position: 0 }
+    }
+
+    /// The sample written `delay` samples ago, which the next [Self::push] overwrites.
+    fn delayed(&self) -> f32 {
+        self.buffer[self.position]
+    }
+    fn push(&mut self, sample: f32) {
+        // Subnormal floats are slow on many CPUs and the tail decays into them.
+        self.buffer[self.position] = if sample.abs() < f32::MIN_POSITIVE { 0.0 } else { sample };
+        self.position = (self.position + 1) % self.buffer.len();
+    }
+}
+
+/// Running state of a [Reverb], one per stream of samples.
+pub struct ReverbState {
+    combs: Box<[(DelayLine, f32)]>,
+    allpasses: Box<[DelayLine]>,
+    feedback: f32,
+    wet: f32,
+}
+impl ReverbState {
+    /// Keeps the input of the combs from piling up past full scale.
+    const INPUT_GAIN: f32 = 0.015;
+    /// Brings the reverb back to about the loudness of the input.
+    const WET_GAIN: f32 = 3.0;
+    /// Share of the high frequencies each pass through a comb loses.
+    const DAMPING: f32 = 0.2;
+    const ALLPASS_FEEDBACK: f32 = 0.5;
+
+    pub fn new(reverb: &Reverb, sample_rate: u32) -> Self {
+        let scaled = |delay: usize| (delay as f32 * sample_rate as f32 / Reverb::DELAY_SAMPLE_RATE).round() as usize;
+        Self {
+            combs: Reverb::COMB_DELAYS.iter().map(|&delay| (DelayLine::new(scaled(delay)), 0.0)).collect(),
+            allpasses: Reverb::ALLPASS_DELAYS.iter().map(|&delay| DelayLine::new(scaled(delay))).collect(),
+            feedback: reverb.feedback(),
+            wet: reverb.wet.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let input = sample * Self::INPUT_GAIN;
+
+        let mut reverb = 0.0;
+        for (line, filtered) in &mut self.combs {
+            let delayed = line.delayed();
+            *filtered = delayed * (1.0 - Self::DAMPING) + *filtered * Self::DAMPING;
+            line.push(input + *filtered * self.feedback);
+            reverb += delayed;
+        }
+        for line in &mut self.allpasses {
+            let delayed = line.delayed();
+            line.push(reverb + delayed * Self::ALLPASS_FEEDBACK);
+            reverb = delayed - reverb;
+        }
+
+        sample * (1.0 - self.wet) + reverb * Self::WET_GAIN * self.wet
+    }
+}
+
+/// How long a [Delay] waits before each repeat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DelayTime {
+    Millis(f32),
+    /// Quarter note beats at the song's bpm, 0.5 is an eighth note.
+    Beats(f32),
+}
+impl DelayTime {
+    pub fn secs(&self, bpm: f32) -> f32 {
+        match *self {
+            Self::Millis(millis) => millis / 1000.0,
+            Self::Beats(beats) => beats * 60.0 / bpm,
+        }
+    }
+}
+
+/// Echo repeating the input after a fixed time, each repeat quieter by the feedback.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Delay {
+    pub time: DelayTime,
+    /// Gain of each repeat relative to the previous one, kept below [Self::MAX_FEEDBACK] so the repeats die out.
+    pub feedback: f32,
+    /// Share of the repeats in the output from 0 to 1, 0 leaves the mix untouched.
+    pub wet: f32,
+}
+impl Default for Delay {
+    fn default() -> Self {
+        Self {
+            time: DelayTime::Beats(0.5),
+            feedback: 0.35,
+            wet: 0.0,
+        }
+    }
+}
+impl Delay {
+    pub const MAX_FEEDBACK: f32 = 0.95;
+
+    pub fn is_enabled(&self) -> bool {
+        self.wet > 0.0
+    }
+
+    /// Seconds the repeats go on after the input goes silent, until they're 60 dB down. 0 when disabled.
+    pub fn tail_secs(&self, bpm: f32) -> f32 {
+        if !self.is_enabled() {
+            return 0.0;
+        }
+
+        let feedback = self.feedback.clamp(0.0, Self::MAX_FEEDBACK);
+        let repeats = if feedback > 0.0 { (60.0 / -db_from_gain(feedback)).ceil() } else { 0.0 };
+        (repeats + 1.0) * self.time.secs(bpm).max(0.0)
+    }
+}
+
+/// Running state of a [Delay], one per stream of samples.
+pub struct DelayState {
+    line: DelayLine,
+    feedback: f32,
+    wet: f32,
+}
+impl DelayState {
+    /// The delay time is rounded to whole samples, at least one.
+    pub fn new(delay: &Delay, bpm: f32, sample_rate: u32) -> Self {
+        let delay_samples = (delay.time.secs(bpm) * sample_rate as f32).round().max(1.0) as usize;
+        Self {
+            line: DelayLine::new(delay_samples),
+            feedback: delay.feedback.clamp(0.0, Delay::MAX_FEEDBACK),
+            wet: delay.wet.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let delayed = self.line.delayed();
+        self.line.push(sample + delayed * self.feedback);
+
+        sample * (1.0 - self.wet) + delayed * self.wet
+    }
+}
+
+/// Lowers the bit depth of the samples and holds each one for several samples, a lower sample rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bitcrusher {
+    /// Bits per sample from 1 to [Self::FULL_BITS], full bits keep the samples as they are.
+    pub bits: u32,
+    /// Samples each kept sample is repeated for, 1 keeps the sample rate.
+    pub hold: u32,
+}
+impl Default for Bitcrusher {
+    fn default() -> Self {
+        Self {
+            bits: Self::FULL_BITS,
+            hold: 1,
+        }
+    }
+}
+impl Bitcrusher {
+    /// Precision of an f32 sample, quantizing to more bits changes nothing.
+    pub const FULL_BITS: u32 = 24;
+
+    pub fn is_enabled(&self) -> bool {
+        self.bits < Self::FULL_BITS || self.hold > 1
+    }
+}
+
+/// Running state of a [Bitcrusher], one per stream of samples.
+pub struct BitcrusherState {
+    /// Distance between the levels a sample is rounded to, 0 keeps the bit depth.
+    step: f32,
+    hold: u32,
+
+    held: f32,
+    held_for: u32,
+}
+impl BitcrusherState {
+    pub fn new(bitcrusher: &Bitcrusher) -> Self {
+        let bits = bitcrusher.bits.clamp(1, Bitcrusher::FULL_BITS);
+        Self {
+            // One bit is the sign, full scale is 1.
+            step: if bits < Bitcrusher::FULL_BITS { 1.0 / (1u32 << (bits - 1)) as f32 } else { 0.0 },
+            hold: bitcrusher.hold.max(1),
+
+            held: 0.0,
+            held_for: 0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        if self.held_for == 0 {
+            self.held = if self.step > 0.0 { (sample / self.step).round() * self.step } else { sample };
+        }
+        self.held_for = (self.held_for + 1) % self.hold;
+
+        self.held
+    }
+}
+
+/// How fast a [Tremolo] goes up and down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TremoloRate {
+    Hz(f32),
+    /// Quarter note beats per cycle at the song's bpm.
+    Beats(f32),
+}
+impl TremoloRate {
+    pub fn hz(&self, bpm: f32) -> f32 {
+        match *self {
+            Self::Hz(hz) => hz,
+            Self::Beats(beats) if beats > 0.0 => bpm / 60.0 / beats,
+            Self::Beats(_) => 0.0,
+        }
+    }
+}
+
+/// Moves the loudness of the whole mix up and down with a sine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tremolo {
+    pub rate: TremoloRate,
+    /// How far the gain dips from 0 to 1, 0 leaves the mix untouched and 1 dips to silence.
+    pub depth: f32,
+}
+impl Default for Tremolo {
+    fn default() -> Self {
+        Self {
+            rate: TremoloRate::Hz(5.0),
+            depth: 0.0,
+        }
+    }
+}
+impl Tremolo {
+    pub fn is_enabled(&self) -> bool {
+        self.depth > 0.0
+    }
+}
+
+/// Running state of a [Tremolo], one per stream of samples.
+pub struct TremoloState {
+    phase: f32,
+    /// Phase added every sample, in radians.
+    increment: f32,
+    depth: f32,
+}
+impl TremoloState {
+    pub fn new(tremolo: &Tremolo, bpm: f32, sample_rate: u32) -> Self {
+        Self {
+            phase: 0.0,
+            increment: TAU * tremolo.rate.hz(bpm).max(0.0) / sample_rate as f32,
+            depth: tremolo.depth.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Moves the phase `n` samples on, like processing them would.
+    pub fn skip(&mut self, n: usize) {
+        self.phase = (self.phase + self.increment * n as f32) % TAU;
+    }
+
+    /// The gain starts at full and dips once per cycle.
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let gain = 1.0 - self.depth * (0.5 - 0.5 * self.phase.cos());
+        self.phase = (self.phase + self.increment) % TAU;
+
+        sample * gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressor_attack_follows_its_time_constant() {
+        const SAMPLE_RATE: u32 = 44100;
+        const QUIET: f32 = 0.01;
+
+        let compressor = Compressor { threshold: -12.0, ratio: 4.0, attack: 0.01, release: 0.15, makeup: 0.0 };
+        let mut state = CompressorState::new(&compressor, SAMPLE_RATE);
+        for _ in 0..SAMPLE_RATE {
+            assert_eq!(state.process(QUIET), QUIET);
+        }
+
+        // The envelope recovered from the gain reduction must rise like a one pole filter towards the step.
+        let attack_samples = (compressor.attack * SAMPLE_RATE as f32) as usize;
+        let slope = 1.0 - 1.0 / compressor.ratio;
+        let mut last_reduction = 0.0;
+        for n in 1..=attack_samples * 5 {
+            let reduction = -db_from_gain(state.process(1.0));
+            let envelope = gain_from_db(compressor.threshold + reduction / slope);
+            let expected = 1.0 - (1.0 - QUIET) * f32::exp(-(n as f32) / attack_samples as f32);
+
+            assert!(reduction >= last_reduction);
+            if expected > gain_from_db(compressor.threshold) {
+                assert!((envelope - expected).abs() < 1e-3, "sample {n}: envelope {envelope}, expected {expected}");
+            }
+            last_reduction = reduction;
+        }
+        assert!((last_reduction - -compressor.threshold * slope).abs() < 0.1);
+    }
+
+    #[test]
+    fn reverb_tail_dies_out() {
+        const SAMPLE_RATE: u32 = 44100;
+
+        let dry = Reverb { wet: 0.0, ..Default::default() };
+        let mut state = ReverbState::new(&dry, SAMPLE_RATE);
+        for i in 0..SAMPLE_RATE {
+            let sample = (i as f32 * 0.05).sin();
+            assert_eq!(state.process(sample), sample);
+        }
+
+        let reverb = Reverb { wet: 0.3, room_size: 0.5 };
+        assert!((reverb.tail_secs() - 1.49).abs() < 0.01);
+        let mut state = ReverbState::new(&reverb, SAMPLE_RATE);
+        let tail_samples = (reverb.tail_secs() * SAMPLE_RATE as f32) as usize;
+        let response: Vec<f32> = (0..tail_samples).map(|i| state.process(if i == 0 { 1.0 } else { 0.0 })).collect();
+
+        let peak = |samples: &[f32]| samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let wet = peak(&response[1..]);
+        assert!(wet > 0.0);
+        assert!(peak(&response[tail_samples * 9 / 10..]) < wet * 0.01);
+    }
+
+    #[test]
+    fn delay_echoes_an_impulse() {
+        const SAMPLE_RATE: u32 = 44100;
+        let impulse_response = |delay: &Delay, bpm: f32, length: usize| {
+            let mut state = DelayState::new(delay, bpm, SAMPLE_RATE);
+            (0..length).map(|i| state.process(if i == 0 { 1.0 } else { 0.0 })).collect::<Vec<f32>>()
+        };
+
+        let delay = Delay { time: DelayTime::Millis(10.0), feedback: 0.5, wet: 0.4 };
+        let response = impulse_response(&delay, 120.0, 2000);
+        assert_eq!(response[0], 1.0 - delay.wet);
+        for (i, sample) in response.iter().enumerate().skip(1) {
+            match i % 441 {
+                0 => assert!((sample - delay.wet * delay.feedback.powi(i as i32 / 441 - 1)).abs() < 1e-6, "sample {i}"),
+                _ => assert_eq!(*sample, 0.0, "sample {i}"),
+            }
+        }
+
+        let eighth = Delay { time: DelayTime::Beats(0.5), feedback: 0.5, wet: 0.4 };
+        let response = impulse_response(&eighth, 120.0, 11025 * 3 + 1);
+        let echoes: Vec<usize> = (1..response.len()).filter(|&i| response[i] != 0.0).collect();
+        assert_eq!(echoes, [11025, 22050, 33075]);
+
+        let runaway = Delay { time: DelayTime::Millis(10.0), feedback: 5.0, wet: 1.0 };
+        let tail_samples = (runaway.tail_secs(120.0) * SAMPLE_RATE as f32) as usize;
+        let response = impulse_response(&runaway, 120.0, tail_samples);
+        // Clamped below 1, so every echo is quieter than the one before and they're about 60 dB down at the tail's end.
+        let echoes: Vec<f32> = response.iter().skip(441).step_by(441).copied().collect();
+        assert!(echoes.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(response[tail_samples - 441..].iter().all(|sample| sample.abs() < 2e-3));
+    }
+
+    #[test]
+    fn bitcrusher_steps_and_holds() {
+        let input: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.013).sin() * 0.9).collect();
+
+        let mut state = BitcrusherState::new(&Bitcrusher::default());
+        assert!(input.iter().all(|&sample| state.process(sample) == sample));
+
+        let mut state = BitcrusherState::new(&Bitcrusher { bits: 4, hold: 4 });
+        let crushed: Vec<f32> = input.iter().map(|&sample| state.process(sample)).collect();
+        for (i, sample) in crushed.iter().enumerate() {
+            assert_eq!((sample * 8.0).fract(), 0.0, "sample {i}");
+            assert_eq!(*sample, crushed[i - i % 4], "sample {i}");
+            assert!((sample - input[i - i % 4]).abs() <= 1.0 / 16.0);
+        }
+    }
+
+    #[test]
+    fn tremolo_dips_once_per_cycle() {
+        const SAMPLE_RATE: u32 = 1000;
+
+        let tremolo = Tremolo { rate: TremoloRate::Beats(1.0), depth: 1.0 };
+        let mut state = TremoloState::new(&tremolo, 120.0, SAMPLE_RATE);
+        let gains: Vec<f32> = (0..SAMPLE_RATE).map(|_| state.process(1.0)).collect();
+
+        // Two beats a second at 120 bpm, full at every beat and silent halfway between.
+        assert_eq!(gains[0], 1.0);
+        for beat in [0, 500] {
+            assert!((gains[beat + 250]).abs() < 1e-4);
+            assert!(gains[beat + 1..beat + 250].windows(2).all(|pair| pair[1] < pair[0]));
+            assert!(gains[beat + 250..beat + 499].windows(2).all(|pair| pair[1] > pair[0]));
+        }
+
+        let mut skipped = TremoloState::new(&tremolo, 120.0, SAMPLE_RATE);
+        skipped.skip(300);
+        assert!((0..700).all(|i| (skipped.process(1.0) - gains[300 + i]).abs() < 1e-4));
+    }
+}
@@ -0,0 +1,120 @@
+use gl::types::{GLsizei, GLuint};
+
+use super::texture::Texture;
+
+/// An offscreen render target with an RGBA color [Texture] and a depth/stencil renderbuffer.
+pub struct Framebuffer {
+    id: GLuint,
+    color: Texture,
+    depth_stencil: GLuint,
+
+    width: u32,
+    height: u32,
+}
+
+impl Framebuffer {
+    fn create_color(width: u32, height: u32) -> Texture {
+        Texture::empty(width, height, gl::RGBA8, gl::LINEAR, gl::CLAMP_TO_EDGE)
+    }
+
+    fn attach(&mut self) {
+        unsafe {
+            gl::NamedRenderbufferStorage(self.depth_stencil, gl::DEPTH24_STENCIL8, self.width as GLsizei, self.height as GLsizei);
+
+            gl::NamedFramebufferTexture(self.id, gl::COLOR_ATTACHMENT0, self.color.get_id(), 0);
+            gl::NamedFramebufferRenderbuffer(self.id, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, self.depth_stencil);
+
+            let status = gl::CheckNamedFramebufferStatus(self.id, gl::FRAMEBUFFER);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                panic!("Failed to create {}x{} framebuffer. Status: 0x{:X}.", self.width, self.height, status);
+            }
+        }
+    }
+
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut id = 0;
+        let mut depth_stencil = 0;
+
+        unsafe {
+            gl::CreateFramebuffers(1, &mut id);
+            gl::CreateRenderbuffers(1, &mut depth_stencil);
+        }
+
+        let mut framebuffer = Self {
+            id,
+            color: Self::create_color(width, height),
+            depth_stencil,
+
+            width,
+            height,
+        };
+        framebuffer.attach();
+
+        framebuffer
+    }
+
+    /// Also sets the viewport to cover the whole framebuffer.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+    }
+    /// Binds the default framebuffer back and restores its viewport.
+    pub fn unbind(width: u32, height: u32) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+    }
+
+    /// Recreates the attachments, their previous contents are lost.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if self.width == width && self.height == height {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.color = Self::create_color(width, height);
+        self.attach();
+    }
+
+    /// Reads the color attachment as tightly packed RGBA rows, bottom row first.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0u8; self.width as usize * self.height as usize * 4];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTextureImage(
+                self.color.get_id(),
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.len() as GLsizei,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        }
+
+        pixels
+    }
+
+    pub const fn get_color_texture(&self) -> &Texture {
+        &self.color
+    }
+
+    pub const fn get_width(&self) -> u32 {
+        self.width
+    }
+    pub const fn get_height(&self) -> u32 {
+        self.height
+    }
+}
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            gl::DeleteRenderbuffers(1, &self.depth_stencil);
+        }
+    }
+}
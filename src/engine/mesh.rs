@@ -1,5 +1,5 @@
 use std::f32::consts::PI;
-use gl::types::{GLenum, GLint, GLsizei, GLsizeiptr, GLuint};
+use gl::types::{GLenum, GLint, GLintptr, GLsizei, GLsizeiptr, GLuint};
 
 #[repr(u8)]
 #[derive(Clone, Copy)]
@@ -88,6 +88,61 @@ impl Layout {
     pub fn attributes(&self) -> &[Attribute] {
         &self.attributes
     }
+    pub fn stride(&self) -> usize {
+        self.attributes.iter().map(Attribute::size_in_bytes).sum()
+    }
+}
+
+/// Describes the attributes of `layout` as reading from `binding`, starting at attribute `first_index`.
+unsafe fn setup_attributes(vao: GLuint, binding: GLuint, first_index: GLuint, layout: &Layout) {
+    let mut offset: GLuint = 0;
+    for (i, attribute) in layout.attributes().iter().enumerate() {
+        let index = first_index + i as GLuint;
+        gl::VertexArrayAttribBinding(vao, index, binding);
+        gl::EnableVertexArrayAttrib(vao, index);
+
+        match attribute {
+            Attribute::Float | Attribute::Vec2 | Attribute::Vec3 | Attribute::Vec4 => {
+                gl::VertexArrayAttribFormat(
+                    vao,
+                    index,
+                    *attribute as GLint + 1,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    offset,
+                );
+            }
+            Attribute::Double | Attribute::DVec2 | Attribute::DVec3 | Attribute::DVec4 => {
+                gl::VertexArrayAttribLFormat(
+                    vao,
+                    index,
+                    *attribute as GLint - Attribute::Double as GLint + 1,
+                    gl::FLOAT,
+                    offset,
+                );
+            }
+            Attribute::Int | Attribute::IVec2 | Attribute::IVec3 | Attribute::IVec4 => {
+                gl::VertexArrayAttribIFormat(
+                    vao,
+                    index,
+                    *attribute as GLint - Attribute::Int as GLint + 1,
+                    gl::INT,
+                    offset,
+                );
+            }
+            Attribute::UInt | Attribute::UVec2 | Attribute::UVec3 | Attribute::UVec4 => {
+                gl::VertexArrayAttribIFormat(
+                    vao,
+                    index,
+                    *attribute as GLint - Attribute::UInt as GLint + 1,
+                    gl::UNSIGNED_INT,
+                    offset,
+                );
+            }
+        }
+
+        offset += attribute.size_in_bytes() as GLuint;
+    }
 }
 
 #[derive(Clone)]
@@ -96,6 +151,7 @@ pub struct Mesh {
     vbo: GLuint,
 
     num_vertices: GLsizei,
+    num_attributes: GLuint,
     render_mode: GLenum,
 }
 impl Mesh {
@@ -330,14 +386,22 @@ impl Mesh {
         Self::new::<f32>(&[-1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0], &Layout::basic_2d(), gl::TRIANGLE_FAN)
     }
 
+    /// Loads a Wavefront OBJ file. Faces with positions, uvs and normals are interleaved as
+    /// [Layout::default_3d], faces with only positions fall back to [Layout::basic_3d].
+    pub fn load_obj(path: &str) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read OBJ file at: {}. Error: {}", path, error))?;
+        let (vertices, layout) = parse_obj(&source)
+            .map_err(|error| format!("Failed to parse OBJ file at: {}. Error: {}", path, error))?;
+
+        Ok(Self::new::<f32>(&vertices, &layout, gl::TRIANGLES))
+    }
+
     pub fn new<T>(vertices: &[T], layout: &Layout, render_mode: GLenum) -> Self {
         let mut vao: GLuint = 0;
         let mut vbo: GLuint = 0;
 
-        let mut stride = 0;
-        for attribute in layout.attributes() {
-            stride += attribute.size_in_bytes();
-        }
+        let stride = layout.stride();
 
         unsafe {
             gl::CreateVertexArrays(1, &mut vao);
@@ -346,57 +410,16 @@ impl Mesh {
             gl::NamedBufferData(vbo, std::mem::size_of_val(vertices) as GLsizeiptr, vertices.as_ptr() as *const _, gl::STATIC_DRAW);
             gl::VertexArrayVertexBuffer(vao, 0, vbo, 0, stride as GLsizei);
 
-            let mut offset: GLuint = 0;
-            for (i, attribute) in layout.attributes().iter().enumerate() {
-                let index = i as GLuint;
-                gl::VertexArrayAttribBinding(vao, index, 0);
-                gl::EnableVertexArrayAttrib(vao, index);
-
-                match attribute {
-                    Attribute::Float | Attribute::Vec2 | Attribute::Vec3 | Attribute::Vec4 => {
-                        gl::VertexArrayAttribFormat(
-                            vao,
-                            i as GLuint,
-                            *attribute as GLint + 1,
-                            gl::FLOAT,
-                            gl::FALSE,
-                            offset,
-                        );
-                    }
-                    Attribute::Double | Attribute::DVec2 | Attribute::DVec3 | Attribute::DVec4 => {
-                        gl::VertexArrayAttribLFormat(
-                            vao,
-                            i as GLuint,
-                            *attribute as GLint - Attribute::Double as GLint + 1,
-                            gl::FLOAT,
-                            offset,
-                        );
-                    }
-                    Attribute::Int | Attribute::IVec2 | Attribute::IVec3 | Attribute::IVec4 => {
-                        gl::VertexArrayAttribIFormat(
-                            vao,
-                            i as GLuint,
-                            *attribute as GLint - Attribute::Int as GLint + 1,
-                            gl::INT,
-                            offset,
-                        );
-                    }
-                    Attribute::UInt | Attribute::UVec2 | Attribute::UVec3 | Attribute::UVec4 => {
-                        gl::VertexArrayAttribIFormat(
-                            vao,
-                            i as GLuint,
-                            *attribute as GLint - Attribute::UInt as GLint + 1,
-                            gl::UNSIGNED_INT,
-                            offset,
-                        );
-                    }
-                }
-
-                offset += attribute.size_in_bytes() as GLuint;
-            }
+            setup_attributes(vao, 0, 0, layout);
         }
 
-        Self { vao, vbo, num_vertices: (std::mem::size_of_val(vertices) / stride) as GLsizei, render_mode }
+        Self {
+            vao,
+            vbo,
+            num_vertices: (std::mem::size_of_val(vertices) / stride) as GLsizei,
+            num_attributes: layout.attributes().len() as GLuint,
+            render_mode,
+        }
     }
 
     pub fn draw(&self) {
@@ -412,6 +435,9 @@ impl Mesh {
     pub const fn get_vbo(&self) -> GLuint {
         self.vbo
     }
+    pub const fn get_render_mode(&self) -> GLenum {
+        self.render_mode
+    }
 }
 impl Drop for Mesh {
     fn drop(&mut self) {
@@ -420,4 +446,237 @@ impl Drop for Mesh {
             gl::DeleteBuffers(1, &self.vbo);
         }
     }
-}
\ No newline at end of file
+}
+
+/// A base [Mesh] drawn once per instance, with per-instance attributes read from a separate buffer.
+pub struct InstancedMesh {
+    mesh: Mesh,
+    instance_vbo: GLuint,
+    instance_stride: usize,
+
+    vertices_per_instance: GLsizei,
+    num_instances: GLsizei,
+}
+impl InstancedMesh {
+    /// Instance attributes are placed right after the attributes of `mesh`.
+    pub fn new(mesh: Mesh, instance_layout: &Layout, vertices_per_instance: usize) -> Self {
+        let mut instance_vbo: GLuint = 0;
+        let instance_stride = instance_layout.stride();
+
+        unsafe {
+            gl::CreateBuffers(1, &mut instance_vbo);
+            gl::VertexArrayVertexBuffer(mesh.vao, 1, instance_vbo, 0, instance_stride as GLsizei);
+            gl::VertexArrayBindingDivisor(mesh.vao, 1, 1);
+
+            setup_attributes(mesh.vao, 1, mesh.num_attributes, instance_layout);
+        }
+
+        Self {
+            mesh,
+            instance_vbo,
+            instance_stride,
+
+            vertices_per_instance: vertices_per_instance as GLsizei,
+            num_instances: 0,
+        }
+    }
+
+    pub fn update_instances<T>(&mut self, instances: &[T]) {
+        unsafe {
+            gl::NamedBufferData(
+                self.instance_vbo,
+                std::mem::size_of_val(instances) as GLsizeiptr,
+                instances.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+        }
+
+        self.num_instances = (std::mem::size_of_val(instances) / self.instance_stride) as GLsizei;
+    }
+    /// Allocates room for `capacity` instances, discarding the current ones.
+    pub fn reserve_instances(&mut self, capacity: usize) {
+        unsafe {
+            gl::NamedBufferData(
+                self.instance_vbo,
+                (capacity * self.instance_stride) as GLsizeiptr,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+        }
+
+        self.num_instances = 0;
+    }
+    /// Overwrites instances starting at `first` and drops the ones after them.  
+    /// The buffer must have been reserved large enough beforehand.
+    pub fn write_instances<T>(&mut self, first: usize, instances: &[T]) {
+        unsafe {
+            gl::NamedBufferSubData(
+                self.instance_vbo,
+                (first * self.instance_stride) as GLintptr,
+                std::mem::size_of_val(instances) as GLsizeiptr,
+                instances.as_ptr() as *const _,
+            );
+        }
+
+        self.num_instances = (first + std::mem::size_of_val(instances) / self.instance_stride) as GLsizei;
+    }
+
+    pub fn draw(&self) {
+        unsafe {
+            gl::BindVertexArray(self.mesh.vao);
+            gl::DrawArraysInstanced(self.mesh.render_mode, 0, self.vertices_per_instance, self.num_instances);
+        }
+    }
+
+    pub const fn get_mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+    pub const fn get_num_instances(&self) -> usize {
+        self.num_instances as usize
+    }
+}
+impl Drop for InstancedMesh {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.instance_vbo); }
+    }
+}
+fn parse_obj_floats<const N: usize>(parts: &mut std::str::SplitWhitespace, line_number: usize) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        let part = parts.next().ok_or_else(|| format!("Line {}: expected {} components.", line_number, N))?;
+        *value = part.parse().map_err(|_| format!("Line {}: invalid number \"{}\".", line_number, part))?;
+    }
+
+    Ok(values)
+}
+fn resolve_obj_index(index: &str, count: usize, line_number: usize) -> Result<usize, String> {
+    let value: isize = index.parse().map_err(|_| format!("Line {}: invalid index \"{}\".", line_number, index))?;
+    let resolved = match value {
+        0 => None,
+        value if value > 0 => Some(value as usize - 1),
+        value => count.checked_sub(value.unsigned_abs()),
+    };
+
+    match resolved {
+        Some(resolved) if resolved < count => Ok(resolved),
+        _ => Err(format!("Line {}: index {} is out of range.", line_number, value)),
+    }
+}
+
+/// Parses OBJ source into interleaved vertices and the layout describing them.  
+/// Polygons are triangulated as fans, negative (relative) indices are supported.
+fn parse_obj(source: &str) -> Result<(Vec<f32>, Layout), String> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut vertices = Vec::new();
+    let mut format: Option<(bool, bool)> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("v") => positions.push(parse_obj_floats::<3>(&mut parts, line_number)?),
+            Some("vt") => uvs.push(parse_obj_floats::<2>(&mut parts, line_number)?),
+            Some("vn") => normals.push(parse_obj_floats::<3>(&mut parts, line_number)?),
+            Some("f") => {
+                let mut face = Vec::new();
+                for corner in parts {
+                    let mut indices = corner.split('/');
+
+                    let position = resolve_obj_index(indices.next().unwrap_or(""), positions.len(), line_number)?;
+                    let uv = match indices.next() {
+                        Some(index) if !index.is_empty() => Some(resolve_obj_index(index, uvs.len(), line_number)?),
+                        _ => None,
+                    };
+                    let normal = match indices.next() {
+                        Some(index) if !index.is_empty() => Some(resolve_obj_index(index, normals.len(), line_number)?),
+                        _ => None,
+                    };
+
+                    let corner_format = (uv.is_some(), normal.is_some());
+                    if *format.get_or_insert(corner_format) != corner_format {
+                        return Err(format!("Line {}: faces mix different vertex formats.", line_number));
+                    }
+
+                    let mut vertex = positions[position].to_vec();
+                    if let Some(uv) = uv {
+                        vertex.extend_from_slice(&uvs[uv]);
+                    }
+                    if let Some(normal) = normal {
+                        vertex.extend_from_slice(&normals[normal]);
+                    }
+                    face.push(vertex);
+                }
+
+                if face.len() < 3 {
+                    return Err(format!("Line {}: face has less than 3 vertices.", line_number));
+                }
+                for j in 1..face.len() - 1 {
+                    vertices.extend_from_slice(&face[0]);
+                    vertices.extend_from_slice(&face[j]);
+                    vertices.extend_from_slice(&face[j + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let layout = match format {
+        Some((true, true)) => Layout::default_3d(),
+        Some((false, true)) => Layout::simple_3d(),
+        Some((true, false)) => Layout::basic_3d().next_attribute(Attribute::Vec2),
+        Some((false, false)) | None => Layout::basic_3d(),
+    };
+    Ok((vertices, layout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "\
+# A unit quad facing +z.
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+    #[test]
+    fn quads_are_triangulated_as_fans() {
+        let (vertices, layout) = parse_obj(QUAD).unwrap();
+        assert_eq!(layout.stride(), Layout::default_3d().stride());
+
+        let corners: Vec<&[f32]> = vertices.chunks_exact(8).collect();
+        assert_eq!(corners.len(), 6);
+        assert_eq!(corners[0], [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(corners[2], [1.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0]);
+        assert_eq!((corners[3], corners[4], corners[5]), (corners[0], corners[2], &[0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0][..]));
+    }
+
+    #[test]
+    fn relative_indices_count_back_from_the_last_vertex() {
+        let source = QUAD.replace("f 1/1/1 2/2/1 3/3/1 4/4/1", "f -4 -3 -2");
+        let (vertices, layout) = parse_obj(&source).unwrap();
+        assert_eq!(layout.stride(), Layout::basic_3d().stride());
+        assert_eq!(vertices, [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn broken_faces_are_errors() {
+        for face in ["f 1 2", "f 1 2 5", "f 1 2 0", "f 1/1 2/2/1 3/3/1", "f 1 2 x"] {
+            let source = QUAD.replace("f 1/1/1 2/2/1 3/3/1 4/4/1", face);
+            assert!(parse_obj(&source).err().is_some_and(|error| error.starts_with("Line 11:")), "{face}");
+        }
+        assert!(parse_obj("v 1 2").is_err());
+    }
+}
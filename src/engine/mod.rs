@@ -1,4 +1,6 @@
 pub mod window;
 pub mod shader;
 pub mod texture;
-pub mod mesh;
\ No newline at end of file
+pub mod mesh;
+pub mod framebuffer;
+pub mod text;
\ No newline at end of file
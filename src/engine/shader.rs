@@ -40,41 +40,31 @@ impl Shader {
             shader
         }
     }
-    fn delete_shaders(vertex_shader: GLuint, fragment_shader: GLuint) {
-        unsafe {
-            gl::DeleteShader(vertex_shader);
-            gl::DeleteShader(fragment_shader);
+    fn read_source(path: &str, typename: &str) -> String {
+        match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => panic!("Failed to read {} shader source at: {}. Error: {}", typename, path, error),
         }
     }
-
-    pub fn new(vertex_path: &str, fragment_path: &str) -> Self {
-        let vertex_source = std::fs::read_to_string(vertex_path);
-        if let Err(error) = vertex_source {
-            panic!("Failed to read vertex shader source at: {}. Error: {}", vertex_path, error);
-        }
-
-        let fragment_source = std::fs::read_to_string(fragment_path);
-        if let Err(error) = fragment_source {
-            panic!("Failed to read fragment shader source at: {}. Error: {}", fragment_path, error);
+    fn delete_shaders(shaders: &[GLuint]) {
+        unsafe {
+            for &shader in shaders {
+                gl::DeleteShader(shader);
+            }
         }
+    }
+    /// Compiles and links `(path, typename, type)` stages into one program.
+    fn from_stages(stages: &[(&str, &str, u32)]) -> Self {
+        let shaders: Vec<GLuint> = stages
+            .iter()
+            .map(|&(path, typename, type_)| Self::load_shader(&Self::read_source(path, typename), path, typename, type_))
+            .collect();
 
         unsafe {
-            let vertex_shader = Self::load_shader(
-                vertex_source.unwrap().as_str(),
-                vertex_path,
-                "vertex",
-                gl::VERTEX_SHADER
-            );
-            let fragment_shader = Self::load_shader(
-                fragment_source.unwrap().as_str(),
-                fragment_path,
-                "fragment",
-                gl::FRAGMENT_SHADER
-            );
-
             let program = gl::CreateProgram();
-            gl::AttachShader(program, vertex_shader);
-            gl::AttachShader(program, fragment_shader);
+            for &shader in &shaders {
+                gl::AttachShader(program, shader);
+            }
             gl::LinkProgram(program);
 
             let mut log_length: GLint = 0;
@@ -89,20 +79,38 @@ impl Shader {
             gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
 
             if success == gl::FALSE as GLint {
-                Self::delete_shaders(vertex_shader, fragment_shader);
+                Self::delete_shaders(&shaders);
+                let stage_names: Vec<String> = stages
+                    .iter()
+                    .map(|(path, typename, _)| format!("{}{}({})", typename[..1].to_uppercase(), &typename[1..], path))
+                    .collect();
                 panic!(
-                    "Failed to link program with shaders: Vertex({}), Fragment({}). Error: {}.",
-                    vertex_path,
-                    fragment_path,
+                    "Failed to link program with shaders: {}. Error: {}.",
+                    stage_names.join(", "),
                     log,
                 );
             }
 
-            Self::delete_shaders(vertex_shader, fragment_shader);
+            Self::delete_shaders(&shaders);
             Self { program }
         }
     }
 
+    pub fn new(vertex_path: &str, fragment_path: &str) -> Self {
+        Self::from_stages(&[
+            (vertex_path, "vertex", gl::VERTEX_SHADER),
+            (fragment_path, "fragment", gl::FRAGMENT_SHADER),
+        ])
+    }
+    /// Like [Self::new] with a geometry stage between the vertex and fragment ones.
+    pub fn new_with_geometry(vertex_path: &str, geometry_path: &str, fragment_path: &str) -> Self {
+        Self::from_stages(&[
+            (vertex_path, "vertex", gl::VERTEX_SHADER),
+            (geometry_path, "geometry", gl::GEOMETRY_SHADER),
+            (fragment_path, "fragment", gl::FRAGMENT_SHADER),
+        ])
+    }
+
     pub fn bind(&self) {
         unsafe { gl::UseProgram(self.program); }
     }
@@ -0,0 +1,123 @@
+use nalgebra::Vector2;
+
+use super::{mesh::{Attribute, InstancedMesh, Layout, Mesh}, texture::Texture};
+
+/// Built-in 5x7 pixel font, only the characters note names, numbers, the cursor readout and the ruler need.
+pub struct BitmapFont {
+    texture: Texture,
+}
+impl BitmapFont {
+    pub const CHARACTERS: &str = "ABCDEFG#-0123456789.HTZS:";
+    pub const GLYPH_WIDTH: u32 = 5;
+    pub const GLYPH_HEIGHT: u32 = 7;
+
+    /// Rows top to bottom, the highest of the 5 bits is the leftmost pixel.
+    const GLYPHS: [[u8; 7]; 25] = [
+        [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010],
+        [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+    ];
+
+    /// Glyphs are laid out left to right in a single row of white pixels with the shape in alpha.
+    pub fn new() -> Self {
+        let width = Self::GLYPH_WIDTH * Self::GLYPHS.len() as u32;
+        let mut data = Vec::with_capacity((width * Self::GLYPH_HEIGHT * 4) as usize);
+
+        // Textures start at the bottom row.
+        for row in (0..Self::GLYPH_HEIGHT as usize).rev() {
+            for glyph in &Self::GLYPHS {
+                for column in 0..Self::GLYPH_WIDTH {
+                    let is_set = glyph[row] >> (Self::GLYPH_WIDTH - 1 - column) & 1 == 1;
+                    data.extend_from_slice(&[255, 255, 255, if is_set { 255 } else { 0 }]);
+                }
+            }
+        }
+
+        let texture = Texture::from_rgba(width, Self::GLYPH_HEIGHT, &data, gl::NEAREST, gl::CLAMP_TO_EDGE)
+            .expect("Built-in font matches its texture size.");
+        Self { texture }
+    }
+
+    pub fn glyph_index(character: char) -> Option<usize> {
+        Self::CHARACTERS.chars().position(|other| other == character.to_ascii_uppercase())
+    }
+    pub fn glyph_count() -> usize {
+        Self::GLYPHS.len()
+    }
+
+    pub fn bind(&self, slot: u32) {
+        self.texture.bind(slot);
+    }
+}
+impl Default for BitmapFont {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[repr(C)]
+struct GlyphInstance {
+    position: Vector2<f32>,
+    glyph: f32,
+}
+
+/// Lines of [BitmapFont] text drawn as one instanced quad per character.
+pub struct TextMesh {
+    mesh: InstancedMesh,
+}
+impl TextMesh {
+    pub fn new() -> Self {
+        let instance_layout = Layout::default()
+            .next_attribute(Attribute::Vec2)
+            .next_attribute(Attribute::Float);
+
+        Self { mesh: InstancedMesh::new(Mesh::basic_square(), &instance_layout, 4) }
+    }
+
+    /// Each text starts with its bottom left corner at a position in pixels, characters are `advance` pixels apart.
+    /// Characters the font doesn't have are left blank.
+    pub fn set_texts(&mut self, texts: &[(Vector2<f32>, String)], advance: f32) {
+        let instances: Vec<GlyphInstance> = texts
+            .iter()
+            .flat_map(|(position, text)| text.chars().enumerate().filter_map(move |(i, character)| {
+                BitmapFont::glyph_index(character).map(|glyph| GlyphInstance {
+                    position: position + Vector2::new(i as f32 * advance, 0.0),
+                    glyph: glyph as f32,
+                })
+            }))
+            .collect();
+
+        self.mesh.update_instances(&instances);
+    }
+
+    pub fn draw(&self) {
+        self.mesh.draw();
+    }
+}
+impl Default for TextMesh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
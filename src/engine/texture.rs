@@ -1,12 +1,17 @@
 use gl::types::{GLenum, GLint, GLsizei, GLuint};
 use image::GenericImageView;
 
+// Core in OpenGL 4.6 (same values as EXT_texture_filter_anisotropic), not exported by the 4.5 `gl` bindings.
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
 pub struct Texture {
     id: GLuint,
 }
 
 impl Texture {
-    pub fn load_from_file(path: &str, filter: GLenum, wrap: GLenum) -> Self {
+    /// With `srgb` the image is uploaded as [gl::SRGB8_ALPHA8] so sampling returns linear colors.
+    pub fn load_from_file(path: &str, filter: GLenum, wrap: GLenum, srgb: bool) -> Self {
         let image = image::open(path);
         if let Err(error) = image { panic!("Failed to load texture at: {}. Error: {}.", path, error); }
 
@@ -14,6 +19,42 @@ impl Texture {
         let (width, height) = image.dimensions();
         let data = image.to_rgba8();
 
+        let internal_format = if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA };
+        match Self::from_raw(width, height, internal_format, gl::RGBA, gl::UNSIGNED_BYTE, &data, filter, wrap) {
+            Ok(texture) => texture,
+            Err(error) => panic!("Failed to load texture at: {}. Error: {}.", path, error),
+        }
+    }
+    /// `data` is tightly packed 8-bit RGBA, row by row.
+    pub fn from_rgba(width: u32, height: u32, data: &[u8], filter: GLenum, wrap: GLenum) -> Result<Self, String> {
+        Self::from_raw(width, height, gl::RGBA, gl::RGBA, gl::UNSIGNED_BYTE, data, filter, wrap)
+    }
+    /// `data` is tightly packed (unpack alignment of 1) and its length must match the dimensions, `format` and `type_`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_raw(
+        width: u32,
+        height: u32,
+        internal_format: GLenum,
+        format: GLenum,
+        type_: GLenum,
+        data: &[u8],
+        filter: GLenum,
+        wrap: GLenum,
+    ) -> Result<Self, String> {
+        let pixel_size = Self::pixel_size(format, type_)
+            .ok_or_else(|| format!("Unsupported pixel format: 0x{:X} with type: 0x{:X}.", format, type_))?;
+        let expected_length = width as usize * height as usize * pixel_size;
+        if data.len() != expected_length {
+            return Err(format!(
+                "Data length {} doesn't match {}x{} texture with {} bytes per pixel ({} expected).",
+                data.len(),
+                width,
+                height,
+                pixel_size,
+                expected_length,
+            ));
+        }
+
         let mut id = 0;
         unsafe {
             gl::GenTextures(1, &mut id);
@@ -27,24 +68,77 @@ impl Texture {
 
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 4);
 
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA as GLint,
+                internal_format as GLint,
                 width as GLsizei,
                 height as GLsizei,
                 0,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
+                format,
+                type_,
                 data.as_ptr() as *const std::ffi::c_void,
             );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
             gl::GenerateMipmap(gl::TEXTURE_2D);
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
 
+        Ok(Self { id })
+    }
+
+    /// Allocates storage without data or mipmaps, e.g. for framebuffer attachments.
+    pub fn empty(width: u32, height: u32, internal_format: GLenum, filter: GLenum, wrap: GLenum) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as GLint);
+
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as GLint);
+
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
         Self { id }
     }
 
+    const fn pixel_size(format: GLenum, type_: GLenum) -> Option<usize> {
+        let components = match format {
+            gl::RED | gl::RED_INTEGER | gl::DEPTH_COMPONENT => 1,
+            gl::RG | gl::RG_INTEGER => 2,
+            gl::RGB | gl::BGR | gl::RGB_INTEGER => 3,
+            gl::RGBA | gl::BGRA | gl::RGBA_INTEGER => 4,
+            _ => return None,
+        };
+        let component_size = match type_ {
+            gl::UNSIGNED_BYTE | gl::BYTE => 1,
+            gl::UNSIGNED_SHORT | gl::SHORT | gl::HALF_FLOAT => 2,
+            gl::UNSIGNED_INT | gl::INT | gl::FLOAT => 4,
+            _ => return None,
+        };
+
+        Some(components * component_size)
+    }
+
     pub fn bind(&self, slot: GLenum) {
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0 + slot);
@@ -54,6 +148,25 @@ impl Texture {
     pub fn unbind() {
         unsafe { gl::BindTexture(gl::TEXTURE_2D, 0); }
     }
+
+    /// Enables anisotropic filtering up to `level` (clamped to what the driver supports).  
+    /// Does nothing when the driver reports no anisotropy support.
+    pub fn set_anisotropy(&self, level: f32) {
+        let mut max_level: f32 = 0.0;
+        unsafe {
+            while gl::GetError() != gl::NO_ERROR {}
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_level);
+            if gl::GetError() != gl::NO_ERROR || max_level <= 1.0 {
+                return;
+            }
+
+            gl::TextureParameterf(self.id, TEXTURE_MAX_ANISOTROPY, level.clamp(1.0, max_level));
+        }
+    }
+
+    pub const fn get_id(&self) -> GLuint {
+        self.id
+    }
 }
 impl Drop for Texture {
     fn drop(&mut self) {
@@ -1,4 +1,4 @@
-use std::time::{Duration, Instant};
+use std::{path::PathBuf, time::{Duration, Instant}};
 
 use glfw::{self, Context};
 use gl;
@@ -12,8 +12,17 @@ pub struct Window {
     keys: [u64; glfw::ffi::KEY_LAST as usize + 1],
     mouse_buttons: [u64; glfw::ffi::MOUSE_BUTTON_LAST as usize + 1],
 
+    gamepad: Option<glfw::JoystickId>,
+    gamepad_buttons: [u64; glfw::ffi::GAMEPAD_BUTTON_LAST as usize + 1],
+    gamepad_axes: [f32; glfw::ffi::GAMEPAD_AXIS_LAST as usize + 1],
+
     current_frame: u64,
 
+    title: String,
+    vsync: bool,
+    max_fps: u32,
+    msaa_samples: u32,
+
     frame_duration: Duration,
     last_time: Instant,
     sleeper: spin_sleep::SpinSleeper,
@@ -25,6 +34,7 @@ pub struct Window {
     last_height: u32,
 
     aspect: f32,
+    minimized: bool,
 
     mouse_x: f32,
     mouse_y: f32,
@@ -38,6 +48,9 @@ pub struct Window {
     scroll_dx: f32,
     scroll_dy: f32,
 
+    dropped_files: Vec<PathBuf>,
+    typed_chars: String,
+
     frame_time: Instant,
     delta_time: Duration,
 }
@@ -67,10 +80,18 @@ impl Window {
 
         self.scroll_dx = 0.0;
         self.scroll_dy = 0.0;
+        self.dropped_files.clear();
+        self.typed_chars.clear();
 
         for (_, event) in glfw::flush_messages(&self.events) {
             match event {
                 glfw::WindowEvent::FramebufferSize(width, height) => {
+                    // Minimizing reports a zero sized framebuffer, keep the last usable size until it's restored.
+                    self.minimized = width <= 0 || height <= 0;
+                    if self.minimized {
+                        continue;
+                    }
+
                     self.last_width = self.width;
                     self.last_height = self.height;
 
@@ -106,11 +127,21 @@ impl Window {
                     self.scroll_dx += x as f32;
                     self.scroll_dy += y as f32;
                 }
+                glfw::WindowEvent::Char(character) => {
+                    self.typed_chars.push(character);
+                }
+                glfw::WindowEvent::FileDrop(paths) => {
+                    self.dropped_files.extend(paths);
+                }
                 
                 _ => {}
             }
         }
 
+        debug_assert!(self.aspect.is_finite(), "Window aspect must stay finite.");
+
+        self.poll_gamepad();
+
         let cursor_pos = self.handle.get_cursor_pos();
 
         self.mouse_x = cursor_pos.0 as f32;
@@ -123,6 +154,73 @@ impl Window {
         self.last_mouse_y = self.mouse_y;
     }
 
+    /// Follows the first connected gamepad, a disconnected one reads as released buttons and centered axes.
+    fn poll_gamepad(&mut self) {
+        if let Some(id) = self.gamepad {
+            if !self.glfw.get_joystick(id).is_gamepad() {
+                println!("Gamepad disconnected.");
+                self.gamepad = None;
+            }
+        }
+        if self.gamepad.is_none() {
+            self.gamepad = (0..=glfw::ffi::JOYSTICK_LAST)
+                .filter_map(glfw::JoystickId::from_i32)
+                .find(|id| self.glfw.get_joystick(*id).is_gamepad());
+
+            if let Some(id) = self.gamepad {
+                let name = self.glfw.get_joystick(id).get_gamepad_name().unwrap_or_default();
+                println!("Gamepad connected: {}.", name);
+            }
+        }
+
+        let state = self.gamepad.and_then(|id| self.glfw.get_joystick(id).get_gamepad_state());
+        for (i, pressed_frame) in self.gamepad_buttons.iter_mut().enumerate() {
+            let button = glfw::GamepadButton::from_i32(i as i32).unwrap();
+            let is_pressed = state.is_some_and(|state| state.get_button_state(button) == glfw::Action::Press);
+
+            if !is_pressed {
+                *pressed_frame = 0;
+            } else if *pressed_frame == 0 {
+                *pressed_frame = self.current_frame;
+            }
+        }
+        for (i, value) in self.gamepad_axes.iter_mut().enumerate() {
+            let axis = glfw::GamepadAxis::from_i32(i as i32).unwrap();
+            *value = state.map_or(0.0, |state| state.get_axis(axis));
+        }
+    }
+
+    /// Files dropped onto the window this frame.
+    pub fn take_dropped_files(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.dropped_files)
+    }
+
+    /// Text typed this frame, with keyboard layout and modifiers applied.  
+    /// Key presses are still reported by [Window::is_key_pressed], text fields should ignore those while focused.
+    pub fn take_typed_chars(&mut self) -> String {
+        std::mem::take(&mut self.typed_chars)
+    }
+
+    /// Reads the back buffer as RGBA, bottom row first.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0u8; self.width as usize * self.height as usize * 4];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::ReadBuffer(gl::BACK);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0, 0,
+                self.width as i32, self.height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        }
+
+        pixels
+    }
+
     pub fn swap_buffers(&mut self) {
         self.handle.swap_buffers();
     }
@@ -163,6 +261,20 @@ impl Window {
         self.mouse_buttons[button as usize] == self.current_frame
     }
 
+    pub const fn is_gamepad_connected(&self) -> bool {
+        self.gamepad.is_some()
+    }
+    pub const fn is_gamepad_button_pressed(&self, button: glfw::GamepadButton) -> bool {
+        self.gamepad_buttons[button as usize] > 0
+    }
+    pub const fn is_gamepad_button_just_pressed(&self, button: glfw::GamepadButton) -> bool {
+        self.gamepad_buttons[button as usize] == self.current_frame
+    }
+    /// From -1 to 1, up and left are negative. Triggers rest at -1, everything else at 0.
+    pub const fn get_gamepad_axis(&self, axis: glfw::GamepadAxis) -> f32 {
+        self.gamepad_axes[axis as usize]
+    }
+
     pub const fn get_mouse_x(&self) -> f32 {
         self.mouse_x
     }
@@ -201,6 +313,10 @@ impl Window {
     pub const fn get_aspect(&self) -> f32 {
         self.aspect
     }
+    /// Whether the framebuffer is zero sized, the size getters keep returning the last usable size meanwhile.
+    pub const fn is_minimized(&self) -> bool {
+        self.minimized
+    }
 
     pub const fn get_delta(&self) -> Duration {
         self.delta_time
@@ -216,8 +332,47 @@ impl Window {
     pub fn close(&mut self) {
         self.handle.set_should_close(true);
     }
+
+    pub const fn is_vsync(&self) -> bool {
+        self.vsync
+    }
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.vsync = vsync;
+        self.glfw.set_swap_interval(if vsync { glfw::SwapInterval::Sync(1) } else { glfw::SwapInterval::None });
+        self.update_frame_duration();
+    }
+    /// Samples per pixel the context actually got, 0 without multisampling. The driver may round the requested
+    /// [WindowBuilder::with_msaa] up or ignore it. It's fixed for the life of the context, see [Self::to_builder].
+    pub const fn get_msaa_samples(&self) -> u32 {
+        self.msaa_samples
+    }
+
+    /// The settings this window runs with, at its current size.  
+    /// Multisampling can only change with a new context: drop this window, build one from this with
+    /// [WindowBuilder::with_msaa], then recreate every shader, mesh and texture since they don't carry over.
+    pub fn to_builder(&self) -> WindowBuilder {
+        let (width, height) = self.handle.get_size();
+        WindowBuilder {
+            width: width.max(1) as u32,
+            height: height.max(1) as u32,
+            title: self.title.clone(),
+            vsync: self.vsync,
+            max_fps: self.max_fps,
+            msaa: self.msaa_samples,
+        }
+    }
+
+    /// With vsync the swap already waits for the display, sleeping on top of that only adds latency. So the limiter
+    /// is only kept when `max_fps` is below the refresh rate, or when the refresh rate is unknown.
+    fn update_frame_duration(&mut self) {
+        let refresh_rate = self.glfw.with_primary_monitor(|_, monitor| monitor.and_then(|monitor| monitor.get_video_mode()).map(|mode| mode.refresh_rate));
+        let is_limited = self.max_fps != WindowBuilder::NO_MAX_FPS && !(self.vsync && refresh_rate.is_some_and(|refresh_rate| self.max_fps >= refresh_rate));
+
+        self.frame_duration = if is_limited { Duration::from_secs_f32(1.0 / self.max_fps as f32) } else { Duration::ZERO };
+    }
 }
 
+#[derive(Clone, Debug)]
 pub struct WindowBuilder {
     width: u32,
     height: u32,
@@ -282,18 +437,25 @@ impl WindowBuilder {
         handle.set_mouse_button_polling(true);
         handle.set_scroll_polling(true);
         handle.set_framebuffer_size_polling(true);
+        handle.set_char_polling(true);
+        handle.set_drag_and_drop_polling(true);
 
         glfw.set_swap_interval(if self.vsync { glfw::SwapInterval::Sync(1) } else { glfw::SwapInterval::None });
 
         let framebuffer_size: (i32, i32) = handle.get_framebuffer_size();
+        let framebuffer_size = (framebuffer_size.0.max(1), framebuffer_size.1.max(1));
         gl::load_with(|procname| handle.get_proc_address(procname));
         
         unsafe { gl::Viewport(0, 0, framebuffer_size.0, framebuffer_size.1); }
+        let mut msaa_samples = 0;
         if self.msaa > 0 {
-            unsafe { gl::Enable(gl::MULTISAMPLE); }
+            unsafe {
+                gl::Enable(gl::MULTISAMPLE);
+                gl::GetIntegerv(gl::SAMPLES, &mut msaa_samples);
+            }
         }
 
-        Window {
+        let mut window = Window {
             glfw,
             handle,
             events,
@@ -301,9 +463,18 @@ impl WindowBuilder {
             keys: [0; glfw::ffi::KEY_LAST as usize + 1],
             mouse_buttons: [0; glfw::ffi::MOUSE_BUTTON_LAST as usize + 1],
 
+            gamepad: None,
+            gamepad_buttons: [0; glfw::ffi::GAMEPAD_BUTTON_LAST as usize + 1],
+            gamepad_axes: [0.0; glfw::ffi::GAMEPAD_AXIS_LAST as usize + 1],
+
             current_frame: 0,
 
-            frame_duration: if self.max_fps == Self::NO_MAX_FPS { Duration::ZERO } else { Duration::from_secs_f32(1.0 / self.max_fps as f32) },
+            title: self.title.clone(),
+            vsync: self.vsync,
+            max_fps: self.max_fps,
+            msaa_samples: msaa_samples.max(0) as u32,
+
+            frame_duration: Duration::ZERO,
             last_time: Instant::now(),
             sleeper: SpinSleeper::default(),
 
@@ -314,6 +485,7 @@ impl WindowBuilder {
             last_height: framebuffer_size.1 as u32,
 
             aspect: framebuffer_size.0 as f32 / framebuffer_size.1 as f32,
+            minimized: false,
 
             mouse_x: 0.0,
             mouse_y: 0.0,
@@ -327,9 +499,15 @@ impl WindowBuilder {
             scroll_dx: 0.0,
             scroll_dy: 0.0,
 
+            dropped_files: Vec::new(),
+            typed_chars: String::new(),
+
             frame_time: Instant::now(),
             delta_time: Duration::ZERO,
-        }
+        };
+        window.update_frame_duration();
+
+        window
     }
 }
 impl Default for WindowBuilder {
@@ -0,0 +1,238 @@
+use std::ops::Range;
+
+use crate::{composition::Composition, effects};
+
+/// Post-processing applied to the rendered samples before they are written to a file.  
+/// None of it affects live playback.
+#[derive(Clone, Copy, Debug)]
+pub struct ExportSettings {
+    /// Strips leading and trailing samples quieter than `silence_threshold`.  
+    /// A fully silent project exports an empty (but valid) file.
+    pub trim_silence: bool,
+    /// Absolute sample value below which a sample counts as silence.
+    pub silence_threshold: f32,
+    /// Scales the whole file so its loudest sample hits `normalize_target_db`, after every effect.
+    pub normalize: bool,
+    /// Peak level in dBFS for `normalize`.
+    pub normalize_target_db: f32,
+    /// Exports only the composition's loop region, so the file can repeat without a click at the wrap.  
+    /// Silence isn't trimmed, the file is always exactly as long as the region. Without a region the whole song is exported.
+    pub seamless_loop: bool,
+    /// How many times a `seamless_loop` export repeats the region back to back, 1 plays it once.
+    pub loop_count: u32,
+    /// Pads the file with silence or cuts it to this length, the cut fading out so it doesn't click.  
+    /// `None` keeps the rendered length.
+    pub duration_secs: Option<f32>,
+}
+impl ExportSettings {
+    /// Kept around the trimmed audio so the first attack and the last release aren't clipped.
+    pub const TRIM_PAD_SECS: f32 = 0.01;
+
+    /// Audio past the end of a seamless loop is faded over its start in this time.
+    pub const LOOP_CROSSFADE_SECS: f32 = 0.01;
+
+    /// Renders `composition` and applies every setting.
+    pub fn render(&self, composition: &Composition) -> Vec<f32> {
+        let mut samples = composition.render_samples_f32();
+        let sample_rate = composition.get_sample_rate();
+
+        match composition.get_loop_region_samples().filter(|_| self.seamless_loop) {
+            Some(region) => samples = self.repeat(&Self::seamless_loop(&samples, region, sample_rate)),
+            None if self.trim_silence => self.trim(&mut samples, sample_rate),
+            None => {}
+        }
+        self.fit_duration(&mut samples, sample_rate);
+        if self.normalize {
+            self.normalize(std::slice::from_mut(&mut samples));
+        }
+
+        samples
+    }
+
+    /// Renders every track alone, the others muted, padded with silence to the length of the longest one so the stems
+    /// line up when imported together. Silence is never trimmed and normalizing applies one gain to all of them,
+    /// which keeps the balance between the tracks. A single track gives a single stem.
+    pub fn render_stems(&self, composition: &Composition) -> Vec<Vec<f32>> {
+        let sample_rate = composition.get_sample_rate();
+        let region = composition.get_loop_region_samples().filter(|_| self.seamless_loop);
+
+        let mut stems: Vec<Vec<f32>> = (0..composition.get_tracks().len())
+            .map(|index| {
+                let samples = composition.render_track_samples_f32(index);
+                let mut samples = match region.clone() {
+                    Some(region) => self.repeat(&Self::seamless_loop(&samples, region, sample_rate)),
+                    None => samples,
+                };
+                self.fit_duration(&mut samples, sample_rate);
+                samples
+            })
+            .collect();
+
+        let length = stems.iter().map(Vec::len).max().unwrap_or(0);
+        for stem in &mut stems {
+            stem.resize(length, 0.0);
+        }
+        if self.normalize {
+            self.normalize(&mut stems);
+        }
+
+        stems
+    }
+
+    /// Cuts `region` out of `samples`, with what plays after its end (the releases and phases of the notes ringing
+    /// over it) faded over its start. The last sample then leads into the first one like it leads into the audio after
+    /// the region, so repeating the result is continuous at the wrap.
+    fn seamless_loop(samples: &[f32], region: Range<usize>, sample_rate: u32) -> Vec<f32> {
+        let sample = |index: usize| samples.get(index).copied().unwrap_or(0.0);
+        let crossfade = ((Self::LOOP_CROSSFADE_SECS * sample_rate as f32) as usize).clamp(1, region.len());
+
+        let mut looped: Vec<f32> = region.clone().map(sample).collect();
+        for (i, head) in looped.iter_mut().take(crossfade).enumerate() {
+            let fade_in = i as f32 / crossfade as f32;
+            *head = *head * fade_in + sample(region.end + i) * (1.0 - fade_in);
+        }
+
+        looped
+    }
+
+    /// [Self::seamless_loop] output `loop_count` times in a row. Each repeat leads into the next like the region leads
+    /// into itself, so the seams need no extra fading.
+    fn repeat(&self, looped: &[f32]) -> Vec<f32> {
+        looped.repeat(self.loop_count.max(1) as usize)
+    }
+
+    fn fit_duration(&self, samples: &mut Vec<f32>, sample_rate: u32) {
+        let Some(duration_secs) = self.duration_secs else { return; };
+        let length = (duration_secs.max(0.0) * sample_rate as f32).round() as usize;
+
+        if length < samples.len() {
+            samples.truncate(length);
+            let fade_out = ((Self::LOOP_CROSSFADE_SECS * sample_rate as f32) as usize).clamp(1, length.max(1));
+            for (i, sample) in samples.iter_mut().rev().take(fade_out).enumerate() {
+                *sample *= i as f32 / fade_out as f32;
+            }
+        }
+        samples.resize(length, 0.0);
+    }
+
+    /// One gain for every buffer, set by the loudest sample of all of them. Silence is left untouched.
+    fn normalize(&self, buffers: &mut [Vec<f32>]) {
+        let peak = buffers.iter().flatten().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        if peak <= 0.0 || !peak.is_finite() {
+            return;
+        }
+
+        let gain = effects::gain_from_db(self.normalize_target_db) / peak;
+        for sample in buffers.iter_mut().flatten() {
+            *sample *= gain;
+        }
+    }
+
+    fn trim(&self, samples: &mut Vec<f32>, sample_rate: u32) {
+        let is_audible = |sample: &f32| sample.abs() >= self.silence_threshold;
+        let (Some(first), Some(last)) = (samples.iter().position(is_audible), samples.iter().rposition(is_audible)) else {
+            samples.clear();
+            return;
+        };
+
+        let pad = (Self::TRIM_PAD_SECS * sample_rate as f32) as usize;
+        let end = (last + 1 + pad).min(samples.len());
+
+        samples.truncate(end);
+        samples.drain(..first.saturating_sub(pad));
+    }
+}
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            trim_silence: false,
+            silence_threshold: 0.001,
+            normalize: false,
+            normalize_target_db: -1.0,
+            seamless_loop: false,
+            loop_count: 1,
+            duration_secs: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point2;
+
+    use super::*;
+    use crate::composition::{Line, MixMode, StrokeParams};
+
+    /// A sine sliding up an octave over 8 beats, with a loop region from beat 1 to 3.
+    fn sliding_loop() -> Composition {
+        let mut composition = Composition::default();
+        let line = Line { start: Point2::new(0.0, 0.0), end: Point2::new(8.0, 12.0), velocity: 0.0 };
+        composition.get_track_mut(0).push_record(vec![line], StrokeParams::default());
+        composition.set_loop_region(Some((1.0, 3.0)));
+        composition
+    }
+    fn largest_step(samples: &[f32]) -> f32 {
+        samples.windows(2).fold(0.0, |step, pair| step.max((pair[1] - pair[0]).abs()))
+    }
+
+    #[test]
+    fn seamless_loops_are_continuous_at_the_seam() {
+        let composition = sliding_loop();
+        let settings = ExportSettings { seamless_loop: true, ..Default::default() };
+        let looped = settings.render(&composition);
+        assert_eq!(looped.len(), composition.get_loop_region_samples().unwrap().len());
+
+        let twice = looped.repeat(2);
+        let seam = (twice[looped.len()] - twice[looped.len() - 1]).abs();
+        assert!(seam <= largest_step(&looped), "seam step {seam}, largest step {}", largest_step(&looped));
+
+        let rendered = composition.render_samples_f32();
+        let cut = &rendered[composition.get_loop_region_samples().unwrap()];
+        assert!((cut[0] - cut[cut.len() - 1]).abs() > seam);
+    }
+
+    #[test]
+    fn stems_add_up_to_the_mix() {
+        let mut composition = Composition::default();
+        composition.set_mix_mode(MixMode::Sum);
+        composition.set_compressor_bypass(true);
+        let stem_line = |start: f32, y: f32| Line { start: Point2::new(start, y), end: Point2::new(start + 1.0, y), velocity: 0.0 };
+        composition.get_track_mut(0).push_record(vec![stem_line(0.0, 0.0)], StrokeParams::default());
+        let settings = ExportSettings::default();
+        assert_eq!(settings.render_stems(&composition).len(), 1);
+
+        composition.add_track().push_record(vec![stem_line(0.5, 7.0), stem_line(2.0, 4.0)], StrokeParams::default());
+        let stems = settings.render_stems(&composition);
+        let mix = settings.render(&composition);
+        assert_eq!(stems.len(), 2);
+        assert!(stems.iter().all(|stem| stem.len() == mix.len()));
+        for (i, mixed) in mix.iter().enumerate() {
+            assert!((stems[0][i] + stems[1][i] - mixed).abs() < 3e-8, "sample {i}");
+        }
+    }
+
+    #[test]
+    fn loop_count_and_duration_set_the_length() {
+        let composition = sliding_loop();
+        let once = ExportSettings { seamless_loop: true, ..Default::default() }.render(&composition);
+
+        let thrice = ExportSettings { seamless_loop: true, loop_count: 3, ..Default::default() }.render(&composition);
+        assert_eq!(thrice.len(), once.len() * 3);
+        assert_eq!(thrice[..once.len()], once);
+        for seam in [once.len(), once.len() * 2] {
+            assert!((thrice[seam] - thrice[seam - 1]).abs() <= largest_step(&once));
+        }
+
+        let sample_rate = composition.get_sample_rate() as f32;
+        let rendered = ExportSettings::default().render(&composition);
+        let padded = ExportSettings { duration_secs: Some(rendered.len() as f32 / sample_rate + 1.0), ..Default::default() }.render(&composition);
+        assert_eq!(padded.len(), rendered.len() + sample_rate as usize);
+        assert_eq!(padded[..rendered.len()], rendered);
+        assert!(padded[rendered.len()..].iter().all(|&sample| sample == 0.0));
+
+        let cut = ExportSettings { duration_secs: Some(0.5), ..Default::default() }.render(&composition);
+        assert_eq!(cut.len(), (0.5 * sample_rate) as usize);
+        assert_eq!(cut[cut.len() - 1], 0.0);
+        assert_eq!(cut[..cut.len() / 2], rendered[..cut.len() / 2]);
+    }
+}
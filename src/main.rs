@@ -1,61 +1,322 @@
 pub mod engine;
+pub mod composition;
+pub mod config;
+pub mod effects;
+pub mod export;
+pub mod midi;
+pub mod musicxml;
+pub mod note_list;
+pub mod osc;
+pub mod project;
 pub mod timeline;
 pub mod resources;
+pub mod spectrogram;
+pub mod tuning;
 
-use std::{path::PathBuf, time::Instant};
+use std::{path::{Path, PathBuf}, sync::Arc, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 
-use engine::window::WindowBuilder;
-use glfw::Key;
+use engine::{framebuffer::Framebuffer, window::WindowBuilder};
+use export::ExportSettings;
+use glfw::{GamepadButton, Key};
 use hound::{WavSpec, WavWriter};
+use image::RgbaImage;
+use midi::MidiInput;
+use note_list::NoteListContent;
+use osc::OscSender;
+use project::{Autosave, Project};
 use resources::Resources;
-use rfd::FileDialog;
+use rfd::{FileDialog, MessageButtons, MessageDialog, MessageDialogResult};
+use composition::{Composition, Instrument, Sample};
+use config::Config;
 use timeline::Timeline;
 use rodio::{OutputStream, Sink};
 
-fn save_to_file(path: &PathBuf, samples: &[i16]) {
+/// A file that fails halfway is removed rather than left truncated.
+fn save_to_file(path: &PathBuf, samples: &[i16], sample_rate: u32) -> Result<(), String> {
     let spec = WavSpec {
         channels: 1,
-        sample_rate: 44100,
+        sample_rate,
         bits_per_sample: 16,
         sample_format: hound::SampleFormat::Int,
     };
     
-    let mut writer = match WavWriter::create(path, spec) {
-        Ok(value) => value,
-        Err(error) => {
-            eprintln!("Failed to create writer for file at: {}. Error: {}", path.display(), error);
-            return;
-        },
-    };
+    let mut writer = WavWriter::create(path, spec)
+        .map_err(|error| format!("Failed to create writer for file at: {}. Error: {}", path.display(), error))?;
+
+    let mut result = Ok(());
     for &sample in samples {
-        writer.write_sample(sample).unwrap();
+        if let Err(error) = writer.write_sample(sample) {
+            result = Err(format!("Failed to write file at: {}. Error: {}", path.display(), error));
+            break;
+        }
     }
-
     if let Err(error) = writer.finalize() {
-        eprintln!("Failed to save file at: {}. Error: {}", path.display(), error);
+        result = result.and(Err(format!("Failed to save file at: {}. Error: {}", path.display(), error)));
+    }
+
+    if result.is_err() {
+        if let Err(error) = std::fs::remove_file(path) {
+            eprintln!("Failed to remove partial file at: {}. Error: {}", path.display(), error);
+        }
+    }
+    result
+}
+
+/// `pixels` are RGBA rows, bottom row first, as OpenGL reads them.
+fn save_screenshot(path: &Path, width: u32, height: u32, mut pixels: Vec<u8>) {
+    // Blending leaves the alpha channel below 1, the window shows it opaque.
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+    let Some(mut image) = RgbaImage::from_raw(width, height, pixels) else {
+        eprintln!("Failed to save screenshot at: {}. Error: Pixel buffer doesn't match its size.", path.display());
+        return;
+    };
+    image::imageops::flip_vertical_in_place(&mut image);
+
+    match image.save(path) {
+        Ok(()) => println!("Saved screenshot to: {}.", path.display()),
+        Err(error) => eprintln!("Failed to save screenshot at: {}. Error: {}", path.display(), error),
+    }
+}
+
+/// Renders `composition` through `settings` into a 16 bit WAV file.
+fn export_wav(composition: &Composition, settings: &ExportSettings, path: &PathBuf) -> Result<(), String> {
+    save_rendered(path, &settings.render(composition), composition.get_sample_rate())
+}
+
+/// Renders every track of `composition` through `settings` into `track_<number>.wav` files in `folder`.
+/// See [ExportSettings::render_stems], returns how many files were written.
+fn export_stems(composition: &Composition, settings: &ExportSettings, folder: &Path) -> Result<usize, String> {
+    let stems = settings.render_stems(composition);
+    for (i, stem) in stems.iter().enumerate() {
+        save_rendered(&folder.join(format!("track_{}.wav", i + 1)), stem, composition.get_sample_rate())?;
+    }
+
+    Ok(stems.len())
+}
+
+/// Clamps `rendered` to full scale and writes it as 16 bit samples.
+fn save_rendered(path: &PathBuf, rendered: &[f32], sample_rate: u32) -> Result<(), String> {
+    let mut samples = Vec::new();
+    for sample in rendered {
+        samples.push((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16);
+    }
+
+    save_to_file(path, &samples, sample_rate)
+}
+
+/// Loads a project for the headless commands, `--bpm <bpm>` and `--sample-rate <hz>` override its settings.
+fn load_headless(input: &Path, config: &Config) -> Result<Composition, String> {
+    let mut config = config.timeline.clone();
+    if let Some(sample_rate) = arg_value("--sample-rate") {
+        config.sample_rate = sample_rate.parse::<u32>().ok().filter(|sample_rate| *sample_rate > 0)
+            .ok_or_else(|| format!("Invalid sample rate: {}.", sample_rate))?;
+    }
+
+    let mut composition = Project::load(input)?.into_composition(config);
+    if let Some(bpm) = arg_value("--bpm") {
+        composition.set_bpm(bpm.parse::<f32>().ok().filter(|bpm| bpm.is_finite() && *bpm > 0.0)
+            .ok_or_else(|| format!("Invalid bpm: {}.", bpm))?);
+    }
+
+    Ok(composition)
+}
+
+/// `--render <input.song> <output.wav> [--bpm <bpm>] [--sample-rate <hz>]`, never opens a window or an audio device.
+fn render_headless(input: &Path, output: &PathBuf, config: &Config) -> Result<(), String> {
+    let composition = load_headless(input, config)?;
+
+    export_wav(&composition, &config.export, output)?;
+    println!("Rendered: {} to: {}.", input.display(), output.display());
+    Ok(())
+}
+
+/// `--spectrogram <input.song> <output.png> [--fft-size <samples>] [--hop <samples>]`, plus the options of `--render`.
+fn spectrogram_headless(input: &Path, output: &Path, config: &Config) -> Result<(), String> {
+    let mut settings = config.spectrogram;
+    if let Some(fft_size) = arg_value("--fft-size") {
+        settings.fft_size = fft_size.parse().map_err(|_| format!("Invalid FFT size: {}.", fft_size))?;
+    }
+    if let Some(hop) = arg_value("--hop") {
+        settings.hop = hop.parse().map_err(|_| format!("Invalid hop: {}.", hop))?;
+    }
+
+    let composition = load_headless(input, config)?;
+
+    settings.save(&composition.render_samples_f32(), output)?;
+    println!("Saved spectrogram of: {} to: {}.", input.display(), output.display());
+    Ok(())
+}
+
+/// `--notes <input.song> <output.json|csv> [--lines]`, plus the options of `--render`. See [note_list::export].
+fn notes_headless(input: &Path, output: &Path, config: &Config) -> Result<(), String> {
+    let content = if std::env::args().any(|arg| arg == "--lines") { NoteListContent::Lines } else { NoteListContent::Notes };
+    let composition = load_headless(input, config)?;
+
+    note_list::export(&composition, content, output)?;
+    println!("Exported notes of: {} to: {}.", input.display(), output.display());
+    Ok(())
+}
+
+fn load_sample(timeline: &mut Timeline, path: &Path) {
+    match Sample::load(path, Sample::DEFAULT_BASE_FREQUENCY) {
+        Ok(sample) => timeline.get_active_track_mut().set_instrument(Instrument::Sample(Arc::new(sample))),
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+fn open_project(path: &Path, config: &Config) -> Option<Timeline> {
+    match Project::load(path) {
+        Ok(project) => {
+            let mut timeline = project.into_timeline(config.timeline.clone());
+            timeline.set_view_config(config.view);
+            timeline.set_grid_config(config.grid);
+            timeline.set_playline_style(config.playline);
+            timeline.set_tone_line_style(config.tone_lines);
+            timeline.mark_saved();
+            Some(timeline)
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            None
+        }
     }
 }
 
+/// Seconds between autosaves, from `--autosave-interval <secs>`.
+fn autosave_interval() -> Duration {
+    arg_value("--autosave-interval")
+        .and_then(|secs| secs.parse::<f32>().ok())
+        .filter(|secs| secs.is_finite() && *secs > 0.0)
+        .map_or(Autosave::DEFAULT_INTERVAL, Duration::from_secs_f32)
+}
+
+/// Value following `name` on the command line.
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).cloned()
+}
+
 fn main() {
+    // `--config <path>` reads the settings from somewhere else than `config.toml` in the working directory.
+    let config = Config::load(Path::new(&arg_value("--config").unwrap_or_else(|| String::from(Config::DEFAULT_PATH))));
+
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--render") {
+        let (Some(input), Some(output)) = (args.get(i + 1), args.get(i + 2)) else {
+            eprintln!("Usage: song_painter --render <input.{}> <output.wav> [--bpm <bpm>] [--sample-rate <hz>]", Project::EXTENSION);
+            std::process::exit(2);
+        };
+        if let Err(error) = render_headless(Path::new(input), &PathBuf::from(output), &config) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--spectrogram") {
+        let (Some(input), Some(output)) = (args.get(i + 1), args.get(i + 2)) else {
+            eprintln!("Usage: song_painter --spectrogram <input.{}> <output.png> [--fft-size <samples>] [--hop <samples>] [--bpm <bpm>] [--sample-rate <hz>]", Project::EXTENSION);
+            std::process::exit(2);
+        };
+        if let Err(error) = spectrogram_headless(Path::new(input), Path::new(output), &config) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--notes") {
+        let (Some(input), Some(output)) = (args.get(i + 1), args.get(i + 2)) else {
+            eprintln!("Usage: song_painter --notes <input.{}> <output.json|csv> [--lines] [--bpm <bpm>] [--sample-rate <hz>]", Project::EXTENSION);
+            std::process::exit(2);
+        };
+        if let Err(error) = notes_headless(Path::new(input), Path::new(output), &config) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let (_stream, stream_handle) = OutputStream::try_default().unwrap();
     let sink = Sink::try_new(&stream_handle).unwrap();
 
     let mut window = WindowBuilder::default()
-        .with_title("Note painter")
-        .with_size(800, 600)
-        .with_vsync(false)
-        .with_max_fps(200)
+        .with_title(&config.window.title)
+        .with_size(config.window.width, config.window.height)
+        .with_vsync(config.window.vsync)
+        .with_max_fps(config.window.max_fps)
+        .with_msaa(config.window.msaa)
         .build();
+    if config.window.msaa > 0 {
+        println!("Multisampling: {} samples per pixel.", window.get_msaa_samples());
+    }
     unsafe {
         gl::Enable(gl::BLEND);
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
         gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+        // Only the cursor still draws GL lines, tone lines are quads with their own width.
         gl::LineWidth(2.0);
     }
 
     let resources = Resources::default();
-    let mut timeline = Timeline::default();
+    let mut screenshot_framebuffer: Option<Framebuffer> = None;
+    // `--osc host:port` sends the notes to an external synth, `--osc-only` also mutes the built-in one. Ctrl+Q toggles it.
+    let osc_sender = arg_value("--osc").and_then(|address| match OscSender::new(&address) {
+        Ok(sender) => Some(Arc::new(sender)),
+        Err(error) => {
+            eprintln!("{}", error);
+            None
+        }
+    });
+    let osc_only = std::env::args().any(|arg| arg == "--osc-only");
+
+    let mut timeline = Timeline::new(config.timeline.clone());
+    timeline.set_view_config(config.view);
+    timeline.set_grid_config(config.grid);
+    timeline.set_playline_style(config.playline);
+    timeline.set_tone_line_style(config.tone_lines);
+    timeline.set_osc_output(osc_sender.clone(), osc_only);
+    let mut project_path: Option<PathBuf> = None;
+
+    // `--midi [device]` listens to a MIDI keyboard, Ctrl+R toggles recording from it.
+    let midi_input = std::env::args().any(|arg| arg == "--midi").then(|| {
+        match arg_value("--midi").filter(|value| !value.starts_with("--")) {
+            Some(path) => MidiInput::open(Path::new(&path)),
+            None => MidiInput::open_first(),
+        }
+    }).and_then(|midi_input| match midi_input {
+        Ok(midi_input) => {
+            println!("Listening to MIDI device: {}.", midi_input.get_path().display());
+            Some(midi_input)
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            None
+        }
+    });
+
+    let mut autosave = Autosave::new(autosave_interval());
+    if let Some(project) = autosave.find_recovery() {
+        let recover = MessageDialog::new()
+            .set_title("Recover unsaved changes")
+            .set_description("The last session ended with unsaved changes. Recover them from the autosave?")
+            .set_buttons(MessageButtons::YesNo)
+            .show();
+
+        if recover == MessageDialogResult::Yes {
+            project_path = project.source.clone();
+            timeline = project.into_timeline(config.timeline.clone());
+            timeline.set_view_config(config.view);
+            timeline.set_grid_config(config.grid);
+            timeline.set_playline_style(config.playline);
+            timeline.set_tone_line_style(config.tone_lines);
+            timeline.set_osc_output(osc_sender.clone(), osc_only);
+        } else {
+            autosave.discard();
+        }
+    }
+    let export_settings = config.export;
 
     let mut fps_timer = Instant::now();
     let mut fps_counter = 0u64;
@@ -69,33 +330,202 @@ fn main() {
         }
         fps_counter += 1;
 
-        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::S) {
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::S) {
+            let file_chooser: Option<PathBuf> = FileDialog::new()
+                .set_title("Save project")
+                .add_filter("Song project", &[Project::EXTENSION])
+                .set_file_name(format!("untitled.{}", Project::EXTENSION))
+                .save_file();
+
+            if let Some(path) = file_chooser {
+                match Project::from_timeline(&timeline).save(&path) {
+                    Ok(()) => {
+                        timeline.mark_saved();
+                        autosave.discard();
+                        project_path = Some(path);
+                    }
+                    Err(error) => eprintln!("{}", error),
+                }
+            }
+        } else if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::S) {
+            // With Alt only the loop region is exported, ready to loop seamlessly.
+            let seamless_loop = window.is_key_pressed(Key::LeftAlt) && timeline.get_composition().get_loop_region().is_some();
             let file_chooser: Option<PathBuf> = FileDialog::new()
-                .set_title("Save as WAV File")
+                .set_title(if seamless_loop { "Save loop as WAV File" } else { "Save as WAV File" })
                 .add_filter("WAV Audio", &["wav"])
-                .set_file_name("output.wav")
+                .set_file_name(if seamless_loop { "loop.wav" } else { "output.wav" })
                 .save_file();
 
             if let Some(path) = file_chooser {
-                let player_source = timeline.render_audio();
-                
-                let mut samples = Vec::new();
-                for sample in player_source {
-                    samples.push((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16);
+                let settings = ExportSettings { seamless_loop, ..export_settings };
+                if let Err(error) = export_wav(timeline.get_composition(), &settings, &path) {
+                    eprintln!("{}", error);
                 }
-    
-                save_to_file(&path, &samples);
             };
         }
-        if window.is_key_just_pressed(Key::Space) {
+        if window.is_key_just_pressed(Key::F9) {
+            window.set_vsync(!window.is_vsync());
+            println!("Vsync: {}.", window.is_vsync());
+        }
+        if window.is_key_just_pressed(Key::F10) {
+            // With Alt only the loop region is exported, like Ctrl+Alt+S.
+            let seamless_loop = window.is_key_pressed(Key::LeftAlt) && timeline.get_composition().get_loop_region().is_some();
+            let file_chooser: Option<PathBuf> = FileDialog::new()
+                .set_title("Export stems to folder")
+                .pick_folder();
+
+            if let Some(folder) = file_chooser {
+                let settings = ExportSettings { seamless_loop, ..export_settings };
+                match export_stems(timeline.get_composition(), &settings, &folder) {
+                    Ok(count) => println!("Exported {} stems to: {}.", count, folder.display()),
+                    Err(error) => eprintln!("{}", error),
+                }
+            }
+        }
+        if window.is_key_just_pressed(Key::F11) {
+            let file_chooser: Option<PathBuf> = FileDialog::new()
+                .set_title("Save spectrogram")
+                .add_filter("PNG Image", &["png"])
+                .set_file_name("spectrogram.png")
+                .save_file();
+
+            if let Some(path) = file_chooser {
+                match config.spectrogram.save(&timeline.render_samples_f32(), &path) {
+                    Ok(()) => println!("Saved spectrogram to: {}.", path.display()),
+                    Err(error) => eprintln!("{}", error),
+                }
+            }
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::X) {
+            // With Alt every drawn line is listed instead of the notes.
+            let content = if window.is_key_pressed(Key::LeftAlt) { NoteListContent::Lines } else { NoteListContent::Notes };
+            let file_chooser: Option<PathBuf> = FileDialog::new()
+                .set_title("Export note list")
+                .add_filter("JSON", &["json"])
+                .add_filter("CSV", &["csv"])
+                .set_file_name("notes.json")
+                .save_file();
+
+            if let Some(path) = file_chooser {
+                match note_list::export(timeline.get_composition(), content, &path) {
+                    Ok(()) => println!("Exported notes to: {}.", path.display()),
+                    Err(error) => eprintln!("{}", error),
+                }
+            }
+        } else if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::X) {
+            let file_chooser: Option<PathBuf> = FileDialog::new()
+                .set_title("Export MusicXML")
+                .add_filter("MusicXML", &["musicxml"])
+                .set_file_name("score.musicxml")
+                .save_file();
+
+            if let Some(path) = file_chooser {
+                match timeline.export_musicxml(&path) {
+                    Ok(()) => println!("Exported score to: {}.", path.display()),
+                    Err(error) => eprintln!("{}", error),
+                }
+            }
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::L) {
+            let file_chooser: Option<PathBuf> = FileDialog::new()
+                .set_title("Load WAV sample")
+                .add_filter("WAV Audio", &["wav"])
+                .pick_file();
+
+            if let Some(path) = file_chooser {
+                load_sample(&mut timeline, &path);
+            }
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::O) {
+            let file_chooser: Option<PathBuf> = FileDialog::new()
+                .set_title("Open project")
+                .add_filter("Song project", &[Project::EXTENSION])
+                .pick_file();
+
+            if let Some(timeline_from_file) = file_chooser.as_deref().and_then(|path| open_project(path, &config)) {
+                timeline = timeline_from_file;
+                timeline.set_osc_output(osc_sender.clone(), osc_only);
+                autosave.discard();
+                project_path = file_chooser;
+            }
+        }
+        for path in window.take_dropped_files() {
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("wav") => load_sample(&mut timeline, &path),
+                Some(Project::EXTENSION) => {
+                    if let Some(timeline_from_file) = open_project(&path, &config) {
+                        timeline = timeline_from_file;
+                        timeline.set_osc_output(osc_sender.clone(), osc_only);
+                        autosave.discard();
+                        project_path = Some(path);
+                    }
+                }
+                _ => eprintln!("Failed to open dropped file at: {}. Error: Unsupported file type.", path.display()),
+            }
+        }
+        if window.is_key_just_pressed(Key::Space) || window.is_key_just_pressed(Key::Enter) || window.is_gamepad_button_just_pressed(GamepadButton::ButtonStart) {
             timeline.play(&sink);
         }
+        // With a record selected, Escape only deselects it in timeline.update.
+        let escape = window.is_key_just_pressed(Key::Escape) && timeline.get_selected_record().is_none();
+        if (escape || window.is_gamepad_button_just_pressed(GamepadButton::ButtonBack)) && timeline.is_playing() {
+            timeline.stop(&sink);
+        }
+        if window.is_key_just_pressed(Key::Backspace) {
+            timeline.panic();
+            println!("All notes off.");
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::Delete) {
+            let track = timeline.get_active_track_index();
+            let confirm = MessageDialog::new()
+                .set_title("Delete track")
+                .set_description(format!("Delete track {}? Ctrl+Shift+T brings it back.", track + 1))
+                .set_buttons(MessageButtons::YesNo)
+                .show();
 
+            if confirm == MessageDialogResult::Yes {
+                // The sink still holds audio rendered with the track.
+                if timeline.is_playing() {
+                    timeline.stop(&sink);
+                }
+                timeline.delete_track(track);
+            }
+        }
+
+        if let Some(midi_input) = &midi_input {
+            for message in midi_input.poll() {
+                timeline.handle_midi(message);
+            }
+        }
         timeline.update(&window);
+        autosave.update(&timeline, project_path.as_deref());
         
         unsafe { gl::Clear(gl::COLOR_BUFFER_BIT); }
         timeline.draw(&resources);
 
+        if window.is_key_just_pressed(Key::F12) {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            let path = PathBuf::from(format!("screenshot-{}.png", timestamp));
+
+            // With Shift the timeline is drawn again offscreen, at the configured resolution.
+            if window.is_key_pressed(Key::LeftShift) {
+                let (width, height) = config.window.get_screenshot_size(window.get_width(), window.get_height());
+                let framebuffer = screenshot_framebuffer.get_or_insert_with(|| Framebuffer::new(width, height));
+                framebuffer.resize(width, height);
+
+                framebuffer.bind();
+                unsafe { gl::Clear(gl::COLOR_BUFFER_BIT); }
+                timeline.draw(&resources);
+                Framebuffer::unbind(window.get_width(), window.get_height());
+
+                save_screenshot(&path, width, height, framebuffer.read_pixels());
+            } else {
+                save_screenshot(&path, window.get_width(), window.get_height(), window.read_pixels());
+            }
+        }
+
         window.swap_buffers();
     }
-}
\ No newline at end of file
+
+    autosave.flush(&timeline, project_path.as_deref());
+}
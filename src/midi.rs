@@ -0,0 +1,158 @@
+use std::{fs::{self, File}, io::Read, path::{Path, PathBuf}, sync::mpsc::{self, Receiver}};
+
+#[derive(Clone, Copy, Debug)]
+pub enum MidiMessage {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+}
+
+/// Reads a raw MIDI device (`/dev/snd/midiC*D*` on Linux) on a background thread.
+pub struct MidiInput {
+    path: PathBuf,
+    receiver: Receiver<MidiMessage>,
+}
+impl MidiInput {
+    const DEVICE_DIRECTORY: &str = "/dev/snd";
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = File::open(path)
+            .map_err(|error| format!("Failed to open MIDI device at: {}. Error: {}", path.display(), error))?;
+        let (sender, receiver) = mpsc::channel();
+
+        let device_path = path.to_path_buf();
+        std::thread::spawn(move || {
+            let mut parser = MidiParser::default();
+            let mut buffer = [0u8; 256];
+            loop {
+                let count = match file.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(count) => count,
+                    Err(error) => {
+                        eprintln!("Failed to read MIDI device at: {}. Error: {}", device_path.display(), error);
+                        break;
+                    }
+                };
+
+                for &byte in &buffer[..count] {
+                    if let Some(message) = parser.push(byte) {
+                        if sender.send(message).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self { path: path.to_path_buf(), receiver })
+    }
+    /// Opens the first raw MIDI device found.
+    pub fn open_first() -> Result<Self, String> {
+        let mut devices: Vec<PathBuf> = fs::read_dir(Self::DEVICE_DIRECTORY)
+            .map_err(|error| format!("Failed to list MIDI devices at: {}. Error: {}", Self::DEVICE_DIRECTORY, error))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("midi")))
+            .collect();
+        devices.sort();
+
+        match devices.first() {
+            Some(path) => Self::open(path),
+            None => Err(format!("No MIDI devices found at: {}.", Self::DEVICE_DIRECTORY)),
+        }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+    /// Messages received since the last call.
+    pub fn poll(&self) -> Vec<MidiMessage> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+/// Turns a MIDI byte stream into note messages, handling running status and skipping everything else.
+#[derive(Default)]
+struct MidiParser {
+    status: u8,
+    data: Vec<u8>,
+}
+impl MidiParser {
+    fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        if byte >= 0xF8 {
+            // Realtime messages may appear anywhere and don't affect the running status.
+            return None;
+        }
+        if byte & 0x80 != 0 {
+            self.status = if byte < 0xF0 { byte } else { 0 };
+            self.data.clear();
+            return None;
+        }
+        if self.status == 0 {
+            return None;
+        }
+
+        self.data.push(byte);
+        let length = match self.status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            _ => 2,
+        };
+        if self.data.len() < length {
+            return None;
+        }
+
+        let message = match (self.status & 0xF0, self.data[0], self.data.get(1).copied().unwrap_or(0)) {
+            (0x90, note, 0) | (0x80, note, _) => Some(MidiMessage::NoteOff { note }),
+            (0x90, note, velocity) => Some(MidiMessage::NoteOn { note, velocity }),
+            _ => None,
+        };
+        self.data.clear();
+
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<MidiMessage> {
+        let mut parser = MidiParser::default();
+        bytes.iter().filter_map(|&byte| parser.push(byte)).collect()
+    }
+    /// [MidiMessage] as plain values, since it doesn't compare.
+    fn notes(messages: &[MidiMessage]) -> Vec<(u8, Option<u8>)> {
+        messages.iter().map(|message| match *message {
+            MidiMessage::NoteOn { note, velocity } => (note, Some(velocity)),
+            MidiMessage::NoteOff { note } => (note, None),
+        }).collect()
+    }
+
+    #[test]
+    fn running_status_repeats_the_last_status() {
+        let messages = parse(&[0x90, 60, 100, 64, 90, 0x81, 60, 0, 64, 0]);
+        assert_eq!(notes(&messages), [(60, Some(100)), (64, Some(90)), (60, None), (64, None)]);
+    }
+
+    #[test]
+    fn realtime_bytes_dont_break_a_message() {
+        let messages = parse(&[0x90, 0xF8, 60, 0xFE, 100, 0xFA, 62, 0xFC, 80]);
+        assert_eq!(notes(&messages), [(60, Some(100)), (62, Some(80))]);
+    }
+
+    #[test]
+    fn note_on_without_velocity_is_a_note_off() {
+        assert_eq!(notes(&parse(&[0x9F, 60, 0])), [(60, None)]);
+    }
+
+    #[test]
+    fn other_messages_are_skipped() {
+        // Program change and channel pressure take one data byte, control change two.
+        let messages = parse(&[0xC0, 5, 7, 0xD3, 40, 0xB0, 64, 127, 0x90, 60, 100, 0xD0, 10]);
+        assert_eq!(notes(&messages), [(60, Some(100))]);
+
+        // System messages cancel the running status until the next status byte.
+        let messages = parse(&[0x90, 60, 100, 0xF0, 1, 2, 3, 0xF7, 62, 80, 0x90, 64, 70]);
+        assert_eq!(notes(&messages), [(60, Some(100)), (64, Some(70))]);
+
+        assert!(parse(&[60, 100]).is_empty());
+    }
+}
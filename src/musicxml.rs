@@ -0,0 +1,387 @@
+use std::{fmt::Write, path::Path};
+
+use crate::{composition::{Composition, Line}, tuning};
+
+/// Exports the composition as a MusicXML 4.0 score for notation software, one part per track.
+///
+/// The conversion is lossy:
+/// - Times snap to a 16th note grid (a 32nd one for x/32 signatures), every note lasts at least one step.
+/// - Each line plays the semitone nearest its middle, after scale snapping. Detune, tuning and temperament are dropped.
+/// - Touching lines of a stroke on the same semitone become one note, a change of semitone is marked as a glissando.
+///   Steps of a sloped stroke too short for the grid are left out, the glissando runs over them.
+/// - Overlapping notes with the same start and end form chords, other overlaps go to extra voices.
+pub fn export(composition: &Composition, time_signature: (u32, u32), path: &Path) -> Result<(), String> {
+    std::fs::write(path, score(composition, time_signature))
+        .map_err(|error| format!("Failed to save MusicXML at: {}. Error: {}", path.display(), error))
+}
+
+/// A note on the grid, times in divisions of a quarter note.
+#[derive(Clone, Copy)]
+struct Note {
+    start: u32,
+    end: u32,
+    midi_note: u8,
+    /// Glides into the next note of its stroke.
+    glissando: bool,
+    /// The previous note of its stroke glides into it.
+    glided_into: bool,
+}
+
+/// Divisions per quarter note, fine enough for a 16th grid and for a beat of every note value the timeline allows.
+fn divisions(beat_value: u32) -> u32 {
+    (beat_value / 4).max(4)
+}
+
+/// Notes of one record, see [export] for how lines are turned into notes.
+fn record_notes(composition: &Composition, lines: &[Line], divisions: u32) -> Vec<Note> {
+    const TOUCH_BEATS: f32 = 1e-3;
+
+    let lines: Vec<&Line> = lines.iter().filter(|line| line.is_renderable()).collect();
+    let mut notes: Vec<Note> = Vec::new();
+    let mut connected_from_previous = false;
+    for (i, line) in lines.iter().enumerate() {
+        let connected = lines.get(i + 1).is_some_and(|next| (next.start.x - line.end.x).abs() < TOUCH_BEATS);
+
+        let pitch = composition.get_pitch_at((line.start.y + line.end.y) * 0.5).round();
+        let midi_note = (tuning::MIDI_NOTE_PITCH_ZERO as f32 + pitch).clamp(0.0, 127.0) as u8;
+        let start = (line.start.x.min(line.end.x) * divisions as f32).round() as u32;
+        let end = (line.start.x.max(line.end.x) * divisions as f32).round() as u32;
+
+        let previous = notes.last_mut().filter(|_| connected_from_previous);
+        match previous {
+            // Too short for the grid, the stroke carries on from the previous note.
+            Some(previous) if start == end => previous.glissando = connected,
+            Some(previous) if previous.midi_note == midi_note => {
+                previous.end = previous.end.max(end);
+                previous.glissando = connected;
+            }
+            _ if start == end && connected => {}
+            _ => notes.push(Note { start, end: end.max(start + 1), midi_note, glissando: connected, glided_into: false }),
+        }
+        connected_from_previous = connected && !notes.is_empty();
+    }
+
+    if let Some(last) = notes.last_mut() {
+        last.glissando = false;
+    }
+    for i in 1..notes.len() {
+        notes[i].glided_into = notes[i - 1].glissando;
+    }
+    notes
+}
+
+/// Notes sharing a start and end, drawn as one chord.
+struct Chord {
+    start: u32,
+    end: u32,
+    notes: Vec<Note>,
+}
+
+/// Splits the notes of a part into voices where chords never overlap.
+fn voices(mut notes: Vec<Note>) -> Vec<Vec<Chord>> {
+    notes.sort_by_key(|note| (note.start, note.end, note.midi_note));
+    notes.dedup_by_key(|note| (note.start, note.end, note.midi_note));
+
+    let mut voices: Vec<Vec<Chord>> = Vec::new();
+    for note in notes {
+        if let Some(chord) = voices.iter_mut().filter_map(|voice| voice.last_mut()).find(|chord| (chord.start, chord.end) == (note.start, note.end)) {
+            chord.notes.push(note);
+            continue;
+        }
+
+        let chord = Chord { start: note.start, end: note.end, notes: vec![note] };
+        match voices.iter_mut().find(|voice| voice.last().is_none_or(|last| last.end <= note.start)) {
+            Some(voice) => voice.push(chord),
+            None => voices.push(vec![chord]),
+        }
+    }
+
+    voices
+}
+
+/// Standard note values that add up to `duration`, longest first. Each is a type name and whether it's dotted.
+fn note_values(mut duration: u32, divisions: u32) -> Vec<(u32, &'static str, bool)> {
+    /// Lengths in 32nd notes.
+    const TYPES: [(u32, &str); 6] = [(32, "whole"), (16, "half"), (8, "quarter"), (4, "eighth"), (2, "16th"), (1, "32nd")];
+
+    // A value only exists when it's a whole number of divisions, so there are no 32nds on a 16th grid.
+    let candidates: Vec<(u32, &str, bool)> = TYPES
+        .iter()
+        .filter(|(thirty_seconds, _)| (thirty_seconds * divisions).is_multiple_of(8))
+        .flat_map(|&(thirty_seconds, name)| {
+            let length = thirty_seconds * divisions / 8;
+            let dotted = length.is_multiple_of(2).then_some((length * 3 / 2, name, true));
+            dotted.into_iter().chain([(length, name, false)])
+        })
+        .collect();
+
+    let mut values = Vec::new();
+    while duration > 0 {
+        let Some(&value) = candidates.iter().find(|(length, _, _)| *length <= duration) else { break; };
+        values.push(value);
+        duration -= value.0;
+    }
+
+    values
+}
+
+/// `C`, `D`... with the alteration and octave MusicXML wants, sharps for black keys.
+fn pitch(midi_note: u8) -> (char, i32, i32) {
+    const STEPS: [(char, i32); 12] = [
+        ('C', 0), ('C', 1), ('D', 0), ('D', 1), ('E', 0), ('F', 0),
+        ('F', 1), ('G', 0), ('G', 1), ('A', 0), ('A', 1), ('B', 0),
+    ];
+
+    let (step, alter) = STEPS[midi_note as usize % 12];
+    (step, alter, midi_note as i32 / 12 - 1)
+}
+
+/// Writes one voice of a measure from `start` to `end`, filling the gaps with rests.
+fn write_voice(xml: &mut String, voice: &[Chord], number: usize, start: u32, end: u32, divisions: u32) {
+    let mut time = start;
+    let chords = voice.iter().filter(|chord| chord.start < end && chord.end > start);
+    for chord in chords.map(Some).chain([None]) {
+        let rest_end = chord.map_or(end, |chord| chord.start.max(start));
+        for (duration, name, dotted) in note_values(rest_end.saturating_sub(time), divisions) {
+            write_note(xml, None, false, duration, name, dotted, number, (false, false), (false, false));
+        }
+
+        let Some(chord) = chord else { break; };
+        let (note_start, note_end) = (chord.start.max(start), chord.end.min(end));
+        let values = note_values(note_end - note_start, divisions);
+        for (i, &(duration, name, dotted)) in values.iter().enumerate() {
+            let tie = (i > 0 || chord.start < start, i + 1 < values.len() || chord.end > end);
+            for (j, note) in chord.notes.iter().enumerate() {
+                // Glissandos leave from the very end of a note and arrive at its very start.
+                let glissando = (!tie.0 && note.glided_into, !tie.1 && note.glissando);
+                write_note(xml, Some(note.midi_note), j > 0, duration, name, dotted, number, tie, glissando);
+            }
+        }
+        time = note_end;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_note(
+    xml: &mut String,
+    midi_note: Option<u8>,
+    chord: bool,
+    duration: u32,
+    name: &str,
+    dotted: bool,
+    voice: usize,
+    (tie_stop, tie_start): (bool, bool),
+    (glissando_stop, glissando_start): (bool, bool),
+) {
+    xml.push_str("      <note>\n");
+    if chord {
+        xml.push_str("        <chord/>\n");
+    }
+    match midi_note {
+        Some(midi_note) => {
+            let (step, alter, octave) = pitch(midi_note);
+            xml.push_str("        <pitch>\n");
+            let _ = writeln!(xml, "          <step>{}</step>", step);
+            if alter != 0 {
+                let _ = writeln!(xml, "          <alter>{}</alter>", alter);
+            }
+            let _ = writeln!(xml, "          <octave>{}</octave>", octave);
+            xml.push_str("        </pitch>\n");
+        }
+        None => xml.push_str("        <rest/>\n"),
+    }
+    let _ = writeln!(xml, "        <duration>{}</duration>", duration);
+    for (is_set, kind) in [(tie_stop, "stop"), (tie_start, "start")] {
+        if is_set {
+            let _ = writeln!(xml, "        <tie type=\"{}\"/>", kind);
+        }
+    }
+    let _ = writeln!(xml, "        <voice>{}</voice>", voice);
+    let _ = writeln!(xml, "        <type>{}</type>", name);
+    if dotted {
+        xml.push_str("        <dot/>\n");
+    }
+
+    let notations: Vec<String> = [(tie_stop, "tied", "stop"), (tie_start, "tied", "start")]
+        .into_iter()
+        .chain([(glissando_stop, "glissando", "stop"), (glissando_start, "glissando", "start")])
+        .filter(|(is_set, _, _)| *is_set)
+        .map(|(_, element, kind)| match element {
+            "glissando" => format!("          <glissando type=\"{}\" line-type=\"wavy\" number=\"1\"/>", kind),
+            _ => format!("          <{} type=\"{}\"/>", element, kind),
+        })
+        .collect();
+    if !notations.is_empty() {
+        xml.push_str("        <notations>\n");
+        for notation in notations {
+            xml.push_str(&notation);
+            xml.push('\n');
+        }
+        xml.push_str("        </notations>\n");
+    }
+    xml.push_str("      </note>\n");
+}
+
+fn score(composition: &Composition, (beats_per_bar, beat_value): (u32, u32)) -> String {
+    let divisions = divisions(beat_value);
+    let bar = beats_per_bar * 4 * divisions / beat_value;
+
+    let parts: Vec<Vec<Note>> = composition
+        .get_tracks()
+        .iter()
+        .map(|track| track.get_record_system().records().flat_map(|lines| record_notes(composition, lines, divisions)).collect())
+        .collect();
+    let last_end = parts.iter().flatten().map(|note| note.end).max().unwrap_or(0);
+    let measures = last_end.div_ceil(bar).max(1);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+    xml.push_str("<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \"http://www.musicxml.org/dtds/partwise.dtd\">\n");
+    xml.push_str("<score-partwise version=\"4.0\">\n");
+
+    xml.push_str("  <part-list>\n");
+    for i in 0..parts.len() {
+        let _ = writeln!(xml, "    <score-part id=\"P{}\">", i + 1);
+        let _ = writeln!(xml, "      <part-name>Track {}</part-name>", i + 1);
+        xml.push_str("    </score-part>\n");
+    }
+    xml.push_str("  </part-list>\n");
+
+    for (i, notes) in parts.into_iter().enumerate() {
+        // Mostly low parts read better in the bass clef.
+        let is_low = !notes.is_empty() && notes.iter().map(|note| note.midi_note as u32).sum::<u32>() < 60 * notes.len() as u32;
+        let voices = voices(notes);
+
+        let _ = writeln!(xml, "  <part id=\"P{}\">", i + 1);
+        for measure in 0..measures {
+            let _ = writeln!(xml, "    <measure number=\"{}\">", measure + 1);
+            if measure == 0 {
+                xml.push_str("      <attributes>\n");
+                let _ = writeln!(xml, "        <divisions>{}</divisions>", divisions);
+                xml.push_str("        <key><fifths>0</fifths></key>\n");
+                let _ = writeln!(xml, "        <time><beats>{}</beats><beat-type>{}</beat-type></time>", beats_per_bar, beat_value);
+                let (sign, line) = if is_low { ("F", 4) } else { ("G", 2) };
+                let _ = writeln!(xml, "        <clef><sign>{}</sign><line>{}</line></clef>", sign, line);
+                xml.push_str("      </attributes>\n");
+
+                if i == 0 {
+                    let bpm = composition.get_bpm();
+                    xml.push_str("      <direction placement=\"above\">\n");
+                    let _ = writeln!(xml, "        <direction-type><metronome><beat-unit>quarter</beat-unit><per-minute>{}</per-minute></metronome></direction-type>", bpm.round());
+                    let _ = writeln!(xml, "        <sound tempo=\"{}\"/>", bpm);
+                    xml.push_str("      </direction>\n");
+                }
+            }
+
+            let (start, end) = (measure * bar, (measure + 1) * bar);
+            let playing: Vec<(usize, &Vec<Chord>)> = voices
+                .iter()
+                .enumerate()
+                .filter(|(_, voice)| voice.iter().any(|chord| chord.start < end && chord.end > start))
+                .collect();
+            if playing.is_empty() {
+                xml.push_str("      <note>\n        <rest measure=\"yes\"/>\n");
+                let _ = writeln!(xml, "        <duration>{}</duration>\n        <voice>1</voice>", bar);
+                xml.push_str("      </note>\n");
+            }
+            for (j, (number, voice)) in playing.into_iter().enumerate() {
+                if j > 0 {
+                    let _ = writeln!(xml, "      <backup><duration>{}</duration></backup>", bar);
+                }
+                write_voice(&mut xml, voice, number + 1, start, end, divisions);
+            }
+            xml.push_str("    </measure>\n");
+        }
+        xml.push_str("  </part>\n");
+    }
+
+    xml.push_str("</score-partwise>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nalgebra::Point2;
+
+    use super::*;
+    use crate::composition::StrokeParams;
+
+    fn line(start: f32, end: f32, y: f32) -> Line {
+        Line { start: Point2::new(start, y), end: Point2::new(end, y), velocity: 0.0 }
+    }
+    /// A chord, an overlap for a second voice, a glissando, a note over a barline and a dotted length, on two tracks.
+    fn composition() -> Composition {
+        let mut composition = Composition::default();
+        let track = composition.get_track_mut(0);
+        track.push_record(vec![line(0.0, 1.0, 0.0)], StrokeParams::default());
+        track.push_record(vec![line(0.0, 1.0, 4.0)], StrokeParams::default());
+        track.push_record(vec![line(0.5, 2.25, 7.0)], StrokeParams::default());
+        track.push_record(vec![line(3.0, 3.5, 2.0), line(3.5, 5.0, 5.0)], StrokeParams::default());
+        composition.add_track().push_record(vec![line(1.25, 7.0, -12.0)], StrokeParams::default());
+        composition
+    }
+
+    /// Checks every tag is closed in order and returns the summed durations of each voice of each measure of each part.
+    fn voice_durations(xml: &str) -> Vec<HashMap<(usize, u32), u32>> {
+        let mut open: Vec<&str> = Vec::new();
+        for tag in xml.split('<').skip(1).map(|tag| &tag[..tag.find('>').unwrap()]) {
+            if tag.starts_with('?') || tag.starts_with('!') || tag.ends_with('/') {
+                continue;
+            }
+            match tag.strip_prefix('/') {
+                Some(name) => assert_eq!(open.pop(), Some(name)),
+                None => open.push(tag.split(' ').next().unwrap()),
+            }
+        }
+        assert!(open.is_empty());
+
+        let mut parts = Vec::new();
+        for part in xml.split("<part id=").skip(1) {
+            let mut durations = HashMap::new();
+            for (measure, measure_xml) in part.split("<measure ").skip(1).enumerate() {
+                for note in measure_xml.split("<note>").skip(1).filter(|note| !note.contains("<chord/>")) {
+                    let value = |tag: &str| note.split(&format!("<{tag}>")).nth(1).and_then(|rest| rest.split('<').next()).unwrap().parse::<u32>().unwrap();
+                    *durations.entry((measure, value("voice"))).or_insert(0) += value("duration");
+                }
+            }
+            parts.push(durations);
+        }
+        parts
+    }
+
+    #[test]
+    fn every_voice_fills_its_measures() {
+        for (composition, parts) in [(composition(), 2), (Composition::default(), 1)] {
+            for time_signature in [(4, 4), (6, 8), (7, 8), (3, 32)] {
+                let divisions = divisions(time_signature.1);
+                let bar = time_signature.0 * 4 * divisions / time_signature.1;
+
+                let xml = score(&composition, time_signature);
+                if parts > 1 {
+                    assert!(xml.contains("<chord/>") && xml.contains("<glissando") && xml.contains("<tie ") && xml.contains("<voice>2</voice>"));
+                }
+
+                let durations = voice_durations(&xml);
+                assert_eq!(durations.len(), parts);
+                for durations in durations {
+                    assert!(!durations.is_empty());
+                    for ((measure, voice), duration) in durations {
+                        assert_eq!(duration, bar, "{time_signature:?}, measure {measure}, voice {voice}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn note_values_add_up() {
+        for divisions in [4, 8] {
+            for duration in 1..=64 {
+                let values = note_values(duration, divisions);
+                assert_eq!(values.iter().map(|(length, _, _)| length).sum::<u32>(), duration);
+            }
+        }
+    }
+}
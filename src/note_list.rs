@@ -0,0 +1,352 @@
+use std::{fmt::Write, path::Path};
+
+use crate::{composition::{Composition, Line}, tuning};
+
+/// What [export] lists for each record.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NoteListContent {
+    /// Every drawn line with the pitch at both of its ends.
+    Lines,
+    /// Lines rounded to the nearest semitone, touching ones on the same semitone merged into one note.
+    #[default] Notes,
+}
+
+/// Writes the records of every track as JSON or CSV, picked by the extension of `path`.
+///
+/// Times are in quarter note beats and pitches go through the same time quantizing, scale snapping, tuning and
+/// track detune as [Composition::render_audio]. The bpm and tuning are in the metadata, a `#` comment line for CSV.
+pub fn export(composition: &Composition, content: NoteListContent, path: &Path) -> Result<(), String> {
+    let rows = rows(composition, content);
+    let text = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => json(composition, content, &rows),
+        Some("csv") => csv(composition, content, &rows),
+        _ => return Err(format!("Failed to export notes at: {}. Error: Expected a .json or .csv file.", path.display())),
+    };
+
+    std::fs::write(path, text)
+        .map_err(|error| format!("Failed to export notes at: {}. Error: {}", path.display(), error))
+}
+
+/// A played note as the note list exports it, see [notes].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    pub pitch_midi: u8,
+    /// Quarter note beats, swing included.
+    pub start_beat: f32,
+    pub duration_beats: f32,
+    pub track: usize,
+}
+
+/// Every note of every track, in track then record order, exactly as [NoteListContent::Notes] lists them.  
+/// Goes through the same quantizing, scale snapping and tuning as playback, without touching GL or audio.
+pub fn notes(composition: &Composition) -> Vec<Note> {
+    rows(composition, NoteListContent::Notes)
+        .into_iter()
+        .map(|row| Note {
+            pitch_midi: Row::midi_note(row.start_pitch),
+            start_beat: row.start_beat,
+            duration_beats: row.end_beat - row.start_beat,
+            track: row.track,
+        })
+        .collect()
+}
+
+/// A line or a note, pitches are semitones above C5 like [Composition::get_pitch_at].
+struct Row {
+    track: usize,
+    record: usize,
+    start_beat: f32,
+    end_beat: f32,
+    start_pitch: f32,
+    end_pitch: f32,
+    /// Track detune as a frequency ratio.
+    detune: f32,
+}
+impl Row {
+    fn note_name(pitch: f32) -> String {
+        tuning::midi_note_name(Self::midi_note(pitch))
+    }
+    fn midi_note(pitch: f32) -> u8 {
+        (tuning::MIDI_NOTE_PITCH_ZERO as f32 + pitch.round()).clamp(0.0, 127.0) as u8
+    }
+    fn frequency(&self, composition: &Composition, pitch: f32) -> f32 {
+        composition.get_frequency(pitch) * self.detune
+    }
+}
+
+/// Empty records are skipped without using up a record number.
+fn rows(composition: &Composition, content: NoteListContent) -> Vec<Row> {
+    const TOUCH_BEATS: f32 = 1e-3;
+
+    let mut rows: Vec<Row> = Vec::new();
+    for (track_index, track) in composition.get_tracks().iter().enumerate() {
+        let detune = f32::powf(2.0, track.get_detune_cents() / 1200.0);
+        let records = track.get_record_system().records().filter(|lines| !lines.is_empty());
+
+        for (record_index, lines) in records.enumerate() {
+            let first_row = rows.len();
+            for line in composition.prepare_record(lines).iter() {
+                let Line { start, end, .. } = line;
+                let mut row = Row {
+                    track: track_index,
+                    record: record_index,
+                    start_beat: start.x,
+                    end_beat: end.x,
+                    start_pitch: composition.get_pitch_at(start.y),
+                    end_pitch: composition.get_pitch_at(end.y),
+                    detune,
+                };
+                if content == NoteListContent::Lines {
+                    rows.push(row);
+                    continue;
+                }
+
+                row.start_beat = start.x.min(end.x);
+                row.end_beat = start.x.max(end.x);
+                row.start_pitch = composition.get_pitch_at((start.y + end.y) * 0.5).round();
+                row.end_pitch = row.start_pitch;
+
+                match rows[first_row..].last_mut() {
+                    Some(previous) if previous.start_pitch == row.start_pitch && (row.start_beat - previous.end_beat).abs() < TOUCH_BEATS => {
+                        previous.end_beat = previous.end_beat.max(row.end_beat);
+                    }
+                    _ => rows.push(row),
+                }
+            }
+        }
+    }
+
+    rows
+}
+
+fn json(composition: &Composition, content: NoteListContent, rows: &[Row]) -> String {
+    let mut json = String::new();
+    // Writing into a String can't fail.
+    let _ = writeln!(json, "{{");
+    let _ = writeln!(json, "  \"bpm\": {},", composition.get_bpm());
+    let _ = writeln!(json, "  \"tuning_a4\": {},", composition.get_tuning_a4());
+    let _ = writeln!(json, "  \"tonic\": \"{}\",", tuning::pitch_class_name(composition.get_tonic()));
+    let _ = writeln!(json, "  \"tracks\": [");
+
+    let track_count = composition.get_tracks().len();
+    for (track_index, track) in composition.get_tracks().iter().enumerate() {
+        let _ = writeln!(json, "    {{");
+        let _ = writeln!(json, "      \"detune_cents\": {},", track.get_detune_cents());
+        let _ = write!(json, "      \"records\": [");
+
+        let track_rows: Vec<&Row> = rows.iter().filter(|row| row.track == track_index).collect();
+        for (i, row) in track_rows.iter().enumerate() {
+            let is_new_record = i == 0 || track_rows[i - 1].record != row.record;
+            let is_last_of_record = track_rows.get(i + 1).is_none_or(|next| next.record != row.record);
+
+            if is_new_record {
+                let key = match content {
+                    NoteListContent::Lines => "lines",
+                    NoteListContent::Notes => "notes",
+                };
+                let _ = writeln!(json, "{}", if i == 0 { "" } else { "," });
+                let _ = writeln!(json, "        {{ \"{}\": [", key);
+            }
+
+            let _ = write!(json, "          {{ ");
+            match content {
+                NoteListContent::Lines => {
+                    let _ = write!(
+                        json,
+                        "\"start_beat\": {}, \"end_beat\": {}, \"start_pitch\": {}, \"end_pitch\": {}, \"start_note\": \"{}\", \"end_note\": \"{}\", \"start_hz\": {}, \"end_hz\": {}",
+                        row.start_beat,
+                        row.end_beat,
+                        row.start_pitch,
+                        row.end_pitch,
+                        Row::note_name(row.start_pitch),
+                        Row::note_name(row.end_pitch),
+                        row.frequency(composition, row.start_pitch),
+                        row.frequency(composition, row.end_pitch),
+                    );
+                }
+                NoteListContent::Notes => {
+                    let _ = write!(
+                        json,
+                        "\"note\": \"{}\", \"midi_note\": {}, \"hz\": {}, \"start_beat\": {}, \"duration_beats\": {}",
+                        Row::note_name(row.start_pitch),
+                        Row::midi_note(row.start_pitch),
+                        row.frequency(composition, row.start_pitch),
+                        row.start_beat,
+                        row.end_beat - row.start_beat,
+                    );
+                }
+            }
+            let _ = writeln!(json, " }}{}", if is_last_of_record { "" } else { "," });
+
+            if is_last_of_record {
+                let _ = write!(json, "        ] }}");
+            }
+        }
+
+        let _ = writeln!(json, "{}]", if track_rows.is_empty() { "" } else { "\n      " });
+        let _ = writeln!(json, "    }}{}", if track_index + 1 < track_count { "," } else { "" });
+    }
+
+    let _ = writeln!(json, "  ]");
+    let _ = writeln!(json, "}}");
+    json
+}
+
+fn csv(composition: &Composition, content: NoteListContent, rows: &[Row]) -> String {
+    let mut csv = String::new();
+    let _ = writeln!(
+        csv,
+        "# bpm {}, tuning_a4 {}, tonic {}",
+        composition.get_bpm(),
+        composition.get_tuning_a4(),
+        tuning::pitch_class_name(composition.get_tonic()),
+    );
+
+    match content {
+        NoteListContent::Lines => {
+            let _ = writeln!(csv, "track,record,start_beat,end_beat,start_pitch,end_pitch,start_note,end_note,start_hz,end_hz");
+            for row in rows {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{},{},{},{}",
+                    row.track + 1,
+                    row.record + 1,
+                    row.start_beat,
+                    row.end_beat,
+                    row.start_pitch,
+                    row.end_pitch,
+                    Row::note_name(row.start_pitch),
+                    Row::note_name(row.end_pitch),
+                    row.frequency(composition, row.start_pitch),
+                    row.frequency(composition, row.end_pitch),
+                );
+            }
+        }
+        NoteListContent::Notes => {
+            let _ = writeln!(csv, "track,record,note,midi_note,hz,start_beat,duration_beats");
+            for row in rows {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{}",
+                    row.track + 1,
+                    row.record + 1,
+                    Row::note_name(row.start_pitch),
+                    Row::midi_note(row.start_pitch),
+                    row.frequency(composition, row.start_pitch),
+                    row.start_beat,
+                    row.end_beat - row.start_beat,
+                );
+            }
+        }
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point2;
+
+    use super::*;
+    use crate::composition::StrokeParams;
+
+    fn line(start: f32, end: f32, y: f32) -> Line {
+        Line { start: Point2::new(start, y), end: Point2::new(end, y), velocity: 0.0 }
+    }
+    /// Two tracks, the second one detuned, and an empty third one.
+    fn composition() -> Composition {
+        let mut composition = Composition::default();
+        let track = composition.get_track_mut(0);
+        track.push_record(vec![line(0.0, 1.0, 0.0), line(1.0, 2.0, 0.0), line(2.0, 3.0, 4.0)], StrokeParams::default());
+        track.push_record(vec![Line { start: Point2::new(3.0, 7.0), end: Point2::new(4.0, 12.0), velocity: 0.0 }], StrokeParams::default());
+        let track = composition.add_track();
+        track.set_detune_cents(25.0);
+        track.push_record(vec![line(0.5, 1.5, -5.0)], StrokeParams::default());
+        composition.add_track();
+        composition
+    }
+
+    /// Index just past the JSON value starting at `i`, panicking on anything that isn't one.
+    fn skip_json_value(json: &[u8], mut i: usize) -> usize {
+        let skip_space = |mut i: usize| {
+            while json[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            i
+        };
+
+        i = skip_space(i);
+        match json[i] {
+            open @ (b'{' | b'[') => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                i = skip_space(i + 1);
+                if json[i] == close {
+                    return i + 1;
+                }
+                loop {
+                    if open == b'{' {
+                        i = skip_space(i);
+                        assert_eq!(json[i], b'"');
+                        i = skip_space(skip_json_value(json, i));
+                        assert_eq!(json[i], b':');
+                        i += 1;
+                    }
+                    i = skip_space(skip_json_value(json, i));
+                    match json[i] {
+                        b',' => i += 1,
+                        byte if byte == close => return i + 1,
+                        byte => panic!("Unexpected {} at {}", byte as char, i),
+                    }
+                }
+            }
+            b'"' => i + 1 + json[i + 1..].iter().position(|&byte| byte == b'"').unwrap() + 1,
+            _ => {
+                let end = i + json[i..].iter().position(|byte| !(byte.is_ascii_alphanumeric() || b"+-.".contains(byte))).unwrap();
+                let token = std::str::from_utf8(&json[i..end]).unwrap();
+                assert!(["true", "false", "null"].contains(&token) || token.parse::<f64>().is_ok_and(f64::is_finite), "{token}");
+                end
+            }
+        }
+    }
+
+    #[test]
+    fn exports_are_well_formed() {
+        for composition in [composition(), Composition::default()] {
+            for content in [NoteListContent::Lines, NoteListContent::Notes] {
+                let rows = rows(&composition, content);
+
+                let json = json(&composition, content, &rows);
+                let end = skip_json_value(json.as_bytes(), 0);
+                assert!(json[end..].trim().is_empty());
+
+                let csv = csv(&composition, content, &rows);
+                let mut lines = csv.lines().skip(1);
+                let columns = lines.next().unwrap().split(',').count();
+                let lines: Vec<&str> = lines.collect();
+                assert_eq!(lines.len(), rows.len());
+                assert!(lines.iter().all(|line| line.split(',').count() == columns));
+            }
+        }
+    }
+
+    #[test]
+    fn touching_lines_are_one_note() {
+        let notes = notes(&composition());
+        assert_eq!(notes.iter().filter(|note| note.track == 0).count(), 3);
+        assert_eq!(notes[0].start_beat, 0.0);
+        assert_eq!(notes[0].duration_beats, 2.0);
+        assert_eq!(notes[1].pitch_midi, notes[0].pitch_midi + 4);
+        assert!(notes.iter().all(|note| note.track < 2));
+    }
+
+    #[test]
+    fn quantized_notes_start_on_the_grid() {
+        let mut composition = Composition::default();
+        composition.get_track_mut(0).push_record(vec![line(0.2, 0.9, 0.0)], StrokeParams::default());
+        composition.get_track_mut(0).push_record(vec![line(1.3, 1.6, 2.0)], StrokeParams::default());
+        composition.set_time_quantize(Some(0.5));
+
+        let starts: Vec<f32> = notes(&composition).iter().map(|note| note.start_beat).collect();
+        assert_eq!(starts, [0.0, 1.5]);
+    }
+}
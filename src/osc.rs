@@ -0,0 +1,135 @@
+use std::{net::UdpSocket, sync::mpsc::{self, Sender}};
+
+/// Argument of an OSC message, only the types song_painter sends.
+pub enum OscArgument {
+    Int(i32),
+    Float(f32),
+}
+
+/// Sends OSC 1.0 messages over UDP to a single host, from a background thread so a slow network never stalls a frame.
+///
+/// During playback the [crate::timeline::Timeline] sends one message per change of a voice:
+/// - Address `/note`, type tags `,iff`.
+/// - `voice` (int): index of the record playing, stable for the whole playback.
+/// - `frequency` (float): in Hz.
+/// - `amplitude` (float): from 0 to 1, 0 means the voice went silent.
+///
+/// Stopping, or turning the output off, sends amplitude 0 for every voice.
+pub struct OscSender {
+    packets: Sender<Vec<u8>>,
+}
+impl OscSender {
+    /// `address` is a `host:port` pair, like `127.0.0.1:57120`.
+    pub fn new(address: &str) -> Result<Self, String> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|error| format!("Failed to open OSC socket. Error: {}", error))?;
+        socket.connect(address)
+            .map_err(|error| format!("Failed to connect OSC socket to: {}. Error: {}", address, error))?;
+
+        // Ends once the sender is dropped.
+        let (packets, receiver) = mpsc::channel::<Vec<u8>>();
+        std::thread::spawn(move || {
+            for packet in receiver {
+                if let Err(error) = socket.send(&packet) {
+                    eprintln!("Failed to send OSC message. Error: {}", error);
+                }
+            }
+        });
+
+        Ok(Self { packets })
+    }
+
+    /// Queues the message and returns right away.
+    pub fn send(&self, address: &str, arguments: &[OscArgument]) {
+        if self.packets.send(encode_message(address, arguments)).is_err() {
+            eprintln!("Failed to send OSC message {}. Error: Sender thread stopped.", address);
+        }
+    }
+}
+
+/// Strings are null terminated and padded to a multiple of 4 bytes.
+fn push_string(buffer: &mut Vec<u8>, string: &str) {
+    buffer.extend_from_slice(string.as_bytes());
+    buffer.push(0);
+    while !buffer.len().is_multiple_of(4) {
+        buffer.push(0);
+    }
+}
+fn encode_message(address: &str, arguments: &[OscArgument]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    push_string(&mut buffer, address);
+
+    let mut type_tags = String::from(",");
+    for argument in arguments {
+        type_tags.push(match argument {
+            OscArgument::Int(_) => 'i',
+            OscArgument::Float(_) => 'f',
+        });
+    }
+    push_string(&mut buffer, &type_tags);
+
+    for argument in arguments {
+        match argument {
+            OscArgument::Int(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            OscArgument::Float(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+        }
+    }
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn note() -> Vec<u8> {
+        encode_message("/note", &[OscArgument::Int(3), OscArgument::Float(440.0), OscArgument::Float(0.5)])
+    }
+
+    #[test]
+    fn strings_are_padded_to_four_bytes() {
+        let mut buffer = Vec::new();
+        push_string(&mut buffer, "/abc");
+        assert_eq!(buffer, b"/abc\0\0\0\0");
+
+        buffer.clear();
+        push_string(&mut buffer, "");
+        assert_eq!(buffer, b"\0\0\0\0");
+
+        buffer.clear();
+        push_string(&mut buffer, "/ab");
+        assert_eq!(buffer, b"/ab\0");
+    }
+
+    #[test]
+    fn note_messages_match_the_wire_format() {
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"/note\0\0\0");
+        expected.extend_from_slice(b",iff\0\0\0\0");
+        expected.extend_from_slice(&[0x00, 0x00, 0x00, 0x03]);
+        expected.extend_from_slice(&[0x43, 0xdc, 0x00, 0x00]);
+        expected.extend_from_slice(&[0x3f, 0x00, 0x00, 0x00]);
+        assert_eq!(note(), expected);
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"/abc\0\0\0\0");
+        expected.extend_from_slice(b",i\0\0");
+        expected.extend_from_slice(&(-2i32).to_be_bytes());
+        assert_eq!(encode_message("/abc", &[OscArgument::Int(-2)]), expected);
+
+        assert_eq!(encode_message("/stop", &[]), b"/stop\0\0\0,\0\0\0");
+    }
+
+    #[test]
+    fn messages_arrive_over_loopback() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let sender = OscSender::new(&receiver.local_addr().unwrap().to_string()).unwrap();
+
+        sender.send("/note", &[OscArgument::Int(3), OscArgument::Float(440.0), OscArgument::Float(0.5)]);
+        let mut packet = [0; 64];
+        let length = receiver.recv(&mut packet).unwrap();
+        assert_eq!(packet[..length], note());
+    }
+}
@@ -0,0 +1,621 @@
+use std::{fmt::Write, fs, path::{Path, PathBuf}, sync::Arc, thread::JoinHandle, time::{Duration, Instant}};
+
+use nalgebra::Point2;
+
+use crate::{composition::{Composition, Instrument, Line, MixMode, PitchInterpolation, Sample, StrokeParams}, timeline::{Timeline, TimelineConfig}, tuning::{self, Scale, Temperament}};
+
+/// Plain copy of everything a `.song` file stores, cheap to move to another thread.
+pub struct Project {
+    /// Project file an autosave was made from, `None` for regular saves and unsaved projects.
+    pub source: Option<PathBuf>,
+
+    pub bpm: f32,
+    pub tuning_a4: f32,
+    pub temperament: Temperament,
+    pub tonic: u8,
+    pub scale: Option<Scale>,
+    pub velocity_sensitivity: f32,
+    pub band_limited: bool,
+    pub mix_mode: MixMode,
+    pub time_quantize: Option<f32>,
+    pub pitch_interpolation: PitchInterpolation,
+    pub swing: f32,
+    pub loop_region: Option<(f32, f32)>,
+    pub count_in_bars: u32,
+    pub time_signature: (u32, u32),
+
+    pub tracks: Vec<ProjectTrack>,
+}
+pub struct ProjectTrack {
+    pub instrument: Instrument,
+    pub detune_cents: f32,
+    /// Lines of each record and the synth settings it was drawn with.
+    pub records: Vec<(Vec<Line>, StrokeParams)>,
+    /// Volume curve strokes, see [crate::composition::Track::get_automation].
+    pub automation: Vec<Vec<Line>>,
+}
+impl Project {
+    pub const EXTENSION: &str = "song";
+    const HEADER: &str = "song_painter_project 1";
+
+    pub fn from_timeline(timeline: &Timeline) -> Self {
+        Self {
+            count_in_bars: timeline.get_count_in_bars(),
+            time_signature: timeline.get_time_signature(),
+            ..Self::from_composition(timeline.get_composition())
+        }
+    }
+    pub fn from_composition(composition: &Composition) -> Self {
+        Self {
+            source: None,
+
+            bpm: composition.get_bpm(),
+            tuning_a4: composition.get_tuning_a4(),
+            temperament: composition.get_temperament().clone(),
+            tonic: composition.get_tonic(),
+            scale: composition.get_scale(),
+            velocity_sensitivity: composition.get_velocity_sensitivity(),
+            band_limited: composition.is_band_limited(),
+            mix_mode: composition.get_mix_mode(),
+            time_quantize: composition.get_time_quantize(),
+            pitch_interpolation: composition.get_pitch_interpolation(),
+            swing: composition.get_swing(),
+            loop_region: composition.get_loop_region(),
+            count_in_bars: 0,
+            time_signature: (4, 4),
+
+            tracks: composition.get_tracks().iter().map(|track| ProjectTrack {
+                instrument: track.get_instrument().clone(),
+                detune_cents: track.get_detune_cents(),
+                records: track.get_record_system().records()
+                    .zip(track.get_record_system().get_record_params())
+                    .filter(|(lines, _)| !lines.is_empty())
+                    .map(|(lines, params)| (lines.to_vec(), params.clone()))
+                    .collect(),
+                automation: track.get_automation().records().filter(|lines| !lines.is_empty()).map(<[Line]>::to_vec).collect(),
+            }).collect(),
+        }
+    }
+    /// The returned timeline counts as unsaved, call [Timeline::mark_saved] if it matches a file on disk.
+    pub fn into_timeline(self, config: TimelineConfig) -> Timeline {
+        let count_in_bars = self.count_in_bars;
+        let time_signature = self.time_signature;
+        let mut timeline = Timeline::with_composition(self.into_composition(config));
+        timeline.set_count_in_bars(count_in_bars);
+        timeline.set_time_signature(time_signature);
+
+        timeline
+    }
+    /// Drops the playback only settings, like the count-in and the time signature. The project's bpm overrides the one in `config`.
+    pub fn into_composition(self, config: TimelineConfig) -> Composition {
+        let mut composition = Composition::new(config);
+        composition.set_bpm(self.bpm);
+        composition.set_tuning_a4(self.tuning_a4);
+        composition.set_temperament(self.temperament);
+        composition.set_tonic(self.tonic);
+        composition.set_scale(self.scale);
+        composition.set_velocity_sensitivity(self.velocity_sensitivity);
+        composition.set_band_limited(self.band_limited);
+        composition.set_mix_mode(self.mix_mode);
+        composition.set_time_quantize(self.time_quantize);
+        composition.set_pitch_interpolation(self.pitch_interpolation);
+        composition.set_swing(self.swing, 4.0 / self.time_signature.1.clamp(1, 32).next_power_of_two() as f32);
+        composition.set_loop_region(self.loop_region);
+
+        for (i, project_track) in self.tracks.into_iter().enumerate() {
+            let track = if i == 0 { composition.get_track_mut(0) } else { composition.add_track() };
+            track.set_instrument(project_track.instrument);
+            track.set_detune_cents(project_track.detune_cents);
+            for (lines, params) in project_track.records {
+                track.push_record(lines, params);
+            }
+            for lines in project_track.automation {
+                track.push_automation(lines);
+            }
+            // Undoing right after opening shouldn't take loaded records away.
+            track.clear_undo_history();
+        }
+
+        composition
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text())
+            .map_err(|error| format!("Failed to save project at: {}. Error: {}", path.display(), error))
+    }
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to open project at: {}. Error: {}", path.display(), error))?;
+
+        Self::parse(&text).map_err(|error| format!("Failed to read project at: {}. Error: {}", path.display(), error))
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        // Writing into a String can't fail.
+        let _ = self.write_text(&mut text);
+        text
+    }
+    fn write_text(&self, text: &mut String) -> std::fmt::Result {
+        writeln!(text, "{}", Self::HEADER)?;
+        if let Some(source) = &self.source {
+            writeln!(text, "source {}", source.display())?;
+        }
+
+        writeln!(text, "bpm {}", self.bpm)?;
+        writeln!(text, "tuning_a4 {}", self.tuning_a4)?;
+        match &self.temperament {
+            Temperament::EqualTemperament => writeln!(text, "temperament equal")?,
+            Temperament::JustIntonation => writeln!(text, "temperament just")?,
+            Temperament::Pythagorean => writeln!(text, "temperament pythagorean")?,
+            Temperament::Custom(cents) => {
+                write!(text, "temperament custom")?;
+                for cents in cents {
+                    write!(text, " {}", cents)?;
+                }
+                writeln!(text)?;
+            }
+        }
+        writeln!(text, "tonic {}", self.tonic)?;
+        match self.scale {
+            None => writeln!(text, "scale none")?,
+            Some(Scale::Major) => writeln!(text, "scale major")?,
+            Some(Scale::NaturalMinor) => writeln!(text, "scale minor")?,
+            Some(Scale::HarmonicMinor) => writeln!(text, "scale harmonic_minor")?,
+            Some(Scale::Dorian) => writeln!(text, "scale dorian")?,
+            Some(Scale::MajorPentatonic) => writeln!(text, "scale major_pentatonic")?,
+            Some(Scale::MinorPentatonic) => writeln!(text, "scale minor_pentatonic")?,
+            Some(Scale::Blues) => writeln!(text, "scale blues")?,
+        }
+        writeln!(text, "velocity_sensitivity {}", self.velocity_sensitivity)?;
+        writeln!(text, "band_limited {}", self.band_limited)?;
+        match self.mix_mode {
+            MixMode::Sum => writeln!(text, "mix_mode sum")?,
+            MixMode::Average => writeln!(text, "mix_mode average")?,
+            MixMode::RmsNormalized => writeln!(text, "mix_mode rms")?,
+        }
+        match self.time_quantize {
+            Some(grid) => writeln!(text, "time_quantize {}", grid)?,
+            None => writeln!(text, "time_quantize none")?,
+        }
+        match self.pitch_interpolation {
+            PitchInterpolation::Linear => writeln!(text, "pitch_interpolation linear")?,
+            PitchInterpolation::Spline => writeln!(text, "pitch_interpolation spline")?,
+        }
+        writeln!(text, "swing {}", self.swing)?;
+        match self.loop_region {
+            Some((start, end)) => writeln!(text, "loop_region {} {}", start, end)?,
+            None => writeln!(text, "loop_region none")?,
+        }
+        writeln!(text, "count_in_bars {}", self.count_in_bars)?;
+        writeln!(text, "time_signature {} {}", self.time_signature.0, self.time_signature.1)?;
+
+        for track in &self.tracks {
+            writeln!(text, "track")?;
+            write_instrument(text, "instrument", &track.instrument)?;
+            writeln!(text, "detune_cents {}", track.detune_cents)?;
+
+            for (lines, params) in &track.records {
+                writeln!(text, "record")?;
+                if let Some(instrument) = &params.instrument {
+                    write_instrument(text, "stroke_instrument", instrument)?;
+                }
+                if let Some(amplitude) = params.amplitude {
+                    writeln!(text, "stroke_amplitude {}", amplitude)?;
+                }
+                write_lines(text, lines)?;
+            }
+            for lines in &track.automation {
+                writeln!(text, "automation")?;
+                write_lines(text, lines)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, header)) if header.trim() == Self::HEADER => {}
+            _ => return Err(String::from("Not a song_painter project.")),
+        }
+
+        let mut project = Self::default();
+        // Whether `line`s belong to an automation stroke rather than a record.
+        let mut in_automation = false;
+
+        for (i, line) in lines {
+            let line_number = i + 1;
+            let line = line.trim();
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+
+            match key {
+                "" => {}
+                "source" => project.source = Some(PathBuf::from(value)),
+                "bpm" => project.bpm = parse_value(value, line_number)?,
+                "tuning_a4" => project.tuning_a4 = parse_value(value, line_number)?,
+                "temperament" => project.temperament = parse_temperament(value, line_number)?,
+                "tonic" => project.tonic = parse_value(value, line_number)?,
+                "scale" => project.scale = parse_scale(value, line_number)?,
+                "velocity_sensitivity" => project.velocity_sensitivity = parse_value(value, line_number)?,
+                "band_limited" => project.band_limited = parse_value(value, line_number)?,
+                "mix_mode" => project.mix_mode = match value {
+                    "sum" => MixMode::Sum,
+                    "average" => MixMode::Average,
+                    "rms" => MixMode::RmsNormalized,
+                    _ => return Err(format!("Line {}: unknown mix mode \"{}\".", line_number, value)),
+                },
+                "time_quantize" => project.time_quantize = match value {
+                    "none" => None,
+                    value => Some(parse_value(value, line_number)?),
+                },
+                "pitch_interpolation" => project.pitch_interpolation = match value {
+                    "linear" => PitchInterpolation::Linear,
+                    "spline" => PitchInterpolation::Spline,
+                    _ => return Err(format!("Line {}: unknown pitch interpolation \"{}\".", line_number, value)),
+                },
+                "swing" => project.swing = parse_value(value, line_number)?,
+                "loop_region" => project.loop_region = match value.split_once(' ') {
+                    Some((start, end)) => Some((parse_value(start, line_number)?, parse_value(end.trim(), line_number)?)),
+                    None if value == "none" => None,
+                    None => return Err(format!("Line {}: expected 2 values.", line_number)),
+                },
+                "count_in_bars" => project.count_in_bars = parse_value(value, line_number)?,
+                "time_signature" => project.time_signature = match value.split_once(' ') {
+                    Some((beats_per_bar, beat_value)) => (parse_value(beats_per_bar, line_number)?, parse_value(beat_value.trim(), line_number)?),
+                    None => return Err(format!("Line {}: expected 2 values.", line_number)),
+                },
+                "track" => project.tracks.push(ProjectTrack {
+                    instrument: Instrument::default(),
+                    detune_cents: 0.0,
+                    records: Vec::new(),
+                    automation: Vec::new(),
+                }),
+                "instrument" => current_track(&mut project, line_number)?.instrument = parse_instrument(value, line_number)?,
+                "detune_cents" => current_track(&mut project, line_number)?.detune_cents = parse_value(value, line_number)?,
+                "record" => {
+                    current_track(&mut project, line_number)?.records.push((Vec::new(), StrokeParams::default()));
+                    in_automation = false;
+                }
+                "stroke_instrument" => current_record_params(&mut project, line_number)?.instrument = Some(parse_instrument(value, line_number)?),
+                "stroke_amplitude" => current_record_params(&mut project, line_number)?.amplitude = Some(parse_value(value, line_number)?),
+                "automation" => {
+                    current_track(&mut project, line_number)?.automation.push(Vec::new());
+                    in_automation = true;
+                }
+                "line" => {
+                    let values = value
+                        .split_whitespace()
+                        .map(|value| parse_value::<f32>(value, line_number))
+                        .collect::<Result<Vec<f32>, String>>()?;
+                    let [start_x, start_y, end_x, end_y, velocity] = values[..] else {
+                        return Err(format!("Line {}: expected 5 values.", line_number));
+                    };
+
+                    let track = current_track(&mut project, line_number)?;
+                    let lines = if in_automation { track.automation.last_mut() } else { track.records.last_mut().map(|(lines, _)| lines) };
+                    lines
+                        .ok_or_else(|| format!("Line {}: line outside of a record.", line_number))?
+                        .push(Line { start: Point2::new(start_x, start_y), end: Point2::new(end_x, end_y), velocity });
+                }
+                key => return Err(format!("Line {}: unknown key \"{}\".", line_number, key)),
+            }
+        }
+
+        Ok(project)
+    }
+}
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            source: None,
+
+            bpm: Composition::DEFAULT_BPM,
+            tuning_a4: tuning::DEFAULT_TUNING_A4,
+            temperament: Temperament::default(),
+            tonic: 0,
+            scale: None,
+            velocity_sensitivity: Composition::DEFAULT_VELOCITY_SENSITIVITY,
+            band_limited: true,
+            mix_mode: MixMode::default(),
+            time_quantize: None,
+            pitch_interpolation: PitchInterpolation::default(),
+            swing: 0.0,
+            loop_region: None,
+            count_in_bars: 0,
+            time_signature: (4, 4),
+
+            tracks: Vec::new(),
+        }
+    }
+}
+
+fn current_track(project: &mut Project, line_number: usize) -> Result<&mut ProjectTrack, String> {
+    project.tracks.last_mut().ok_or_else(|| format!("Line {}: expected a track first.", line_number))
+}
+fn current_record_params(project: &mut Project, line_number: usize) -> Result<&mut StrokeParams, String> {
+    current_track(project, line_number)?
+        .records
+        .last_mut()
+        .map(|(_, params)| params)
+        .ok_or_else(|| format!("Line {}: expected a record first.", line_number))
+}
+fn write_instrument(text: &mut String, key: &str, instrument: &Instrument) -> std::fmt::Result {
+    match instrument {
+        Instrument::Sine => writeln!(text, "{} sine", key),
+        Instrument::Square => writeln!(text, "{} square", key),
+        Instrument::Saw => writeln!(text, "{} saw", key),
+        Instrument::Noise => writeln!(text, "{} noise", key),
+        // The path goes last so it may contain spaces.
+        Instrument::Sample(sample) => writeln!(text, "{} sample {} {}", key, sample.get_base_frequency(), sample.get_path().display()),
+    }
+}
+fn write_lines(text: &mut String, lines: &[Line]) -> std::fmt::Result {
+    for line in lines {
+        writeln!(text, "line {} {} {} {} {}", line.start.x, line.start.y, line.end.x, line.end.y, line.velocity)?;
+    }
+    Ok(())
+}
+fn parse_value<T: std::str::FromStr>(value: &str, line_number: usize) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Line {}: invalid value \"{}\".", line_number, value))
+}
+fn parse_temperament(value: &str, line_number: usize) -> Result<Temperament, String> {
+    let mut parts = value.split_whitespace();
+    match parts.next() {
+        Some("equal") => Ok(Temperament::EqualTemperament),
+        Some("just") => Ok(Temperament::JustIntonation),
+        Some("pythagorean") => Ok(Temperament::Pythagorean),
+        Some("custom") => {
+            let mut cents = [0.0; 12];
+            for degree in &mut cents {
+                *degree = parse_value(parts.next().unwrap_or(""), line_number)?;
+            }
+            Ok(Temperament::Custom(cents))
+        }
+        _ => Err(format!("Line {}: unknown temperament \"{}\".", line_number, value)),
+    }
+}
+fn parse_scale(value: &str, line_number: usize) -> Result<Option<Scale>, String> {
+    match value {
+        "none" => Ok(None),
+        "major" => Ok(Some(Scale::Major)),
+        "minor" => Ok(Some(Scale::NaturalMinor)),
+        "harmonic_minor" => Ok(Some(Scale::HarmonicMinor)),
+        "dorian" => Ok(Some(Scale::Dorian)),
+        "major_pentatonic" => Ok(Some(Scale::MajorPentatonic)),
+        "minor_pentatonic" => Ok(Some(Scale::MinorPentatonic)),
+        "blues" => Ok(Some(Scale::Blues)),
+        _ => Err(format!("Line {}: unknown scale \"{}\".", line_number, value)),
+    }
+}
+/// A sample that fails to load falls back to the default instrument instead of failing the whole project.
+fn parse_instrument(value: &str, line_number: usize) -> Result<Instrument, String> {
+    let (name, rest) = value.split_once(' ').unwrap_or((value, ""));
+    match name {
+        "sine" => Ok(Instrument::Sine),
+        "square" => Ok(Instrument::Square),
+        "saw" => Ok(Instrument::Saw),
+        "noise" => Ok(Instrument::Noise),
+        "sample" => {
+            let (base_frequency, path) = rest.split_once(' ').unwrap_or((rest, ""));
+            let base_frequency = parse_value(base_frequency, line_number)?;
+            if !Sample::is_valid_base_frequency(base_frequency) {
+                return Err(format!("Line {}: invalid base frequency \"{}\".", line_number, base_frequency));
+            }
+
+            match Sample::load(Path::new(path), base_frequency) {
+                Ok(sample) => Ok(Instrument::Sample(Arc::new(sample))),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    Ok(Instrument::default())
+                }
+            }
+        }
+        _ => Err(format!("Line {}: unknown instrument \"{}\".", line_number, name)),
+    }
+}
+
+/// Periodically writes the project to a temporary file while it has unsaved changes, so it can be recovered after a crash.  
+/// Every running instance writes its own file, named after its process id.
+pub struct Autosave {
+    path: PathBuf,
+    interval: Duration,
+
+    timer: Instant,
+    saved_revision: u64,
+    worker: Option<JoinHandle<()>>,
+}
+impl Autosave {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+    const FILE_PREFIX: &str = "song_painter_autosave_";
+
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            path: std::env::temp_dir().join(format!("{}{}.{}", Self::FILE_PREFIX, std::process::id(), Project::EXTENSION)),
+            interval,
+
+            timer: Instant::now(),
+            saved_revision: 0,
+            worker: None,
+        }
+    }
+
+    pub const fn get_interval(&self) -> Duration {
+        self.interval
+    }
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Starts a background save if the timeline changed and the interval passed.
+    /// `source` is the project file the timeline was opened from or last saved to.
+    pub fn update(&mut self, timeline: &Timeline, source: Option<&Path>) {
+        if !timeline.has_unsaved_changes() || timeline.get_revision() == self.saved_revision || self.timer.elapsed() < self.interval {
+            return;
+        }
+        if self.worker.as_ref().is_some_and(|worker| !worker.is_finished()) {
+            return;
+        }
+        self.join();
+
+        let mut project = Project::from_timeline(timeline);
+        project.source = source.map(Path::to_path_buf);
+        self.saved_revision = timeline.get_revision();
+        self.timer = Instant::now();
+
+        let path = self.path.clone();
+        self.worker = Some(std::thread::spawn(move || Self::write(&project, &path)));
+    }
+    /// Saves right away on the calling thread if there is anything unsaved, e.g. when closing the window.
+    pub fn flush(&mut self, timeline: &Timeline, source: Option<&Path>) {
+        self.join();
+        if !timeline.has_unsaved_changes() {
+            self.discard();
+            return;
+        }
+
+        let mut project = Project::from_timeline(timeline);
+        project.source = source.map(Path::to_path_buf);
+        Self::write(&project, &self.path);
+    }
+    /// Removes the autosave, call after the project was saved manually.
+    pub fn discard(&mut self) {
+        self.join();
+        self.saved_revision = 0;
+        if self.path.exists() {
+            if let Err(error) = fs::remove_file(&self.path) {
+                eprintln!("Failed to remove autosave at: {}. Error: {}", self.path.display(), error);
+            }
+        }
+    }
+
+    /// The newest autosave another session left behind that is newer than the project it was made from.  
+    /// It's moved to this session's autosave, so [Self::discard] removes it and the next autosave replaces it.
+    pub fn find_recovery(&self) -> Option<Project> {
+        let directory = self.path.parent()?;
+        let (path, project) = fs::read_dir(directory)
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| *path != self.path && Self::is_autosave(path))
+            .filter_map(|path| Some((fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?, path)))
+            .filter_map(|(modified, path)| {
+                let project = match Project::load(&path) {
+                    Ok(project) => project,
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        return None;
+                    }
+                };
+                let source_modified = project.source
+                    .as_ref()
+                    .and_then(|source| fs::metadata(source).and_then(|metadata| metadata.modified()).ok());
+
+                source_modified.is_none_or(|source_modified| source_modified < modified).then_some((modified, path, project))
+            })
+            .max_by_key(|(modified, _, _)| *modified)
+            .map(|(_, path, project)| (path, project))?;
+
+        if let Err(error) = fs::rename(&path, &self.path) {
+            eprintln!("Failed to take over autosave at: {}. Error: {}", path.display(), error);
+        }
+        Some(project)
+    }
+    fn is_autosave(path: &Path) -> bool {
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        name.starts_with(Self::FILE_PREFIX) && path.extension().is_some_and(|extension| extension == Project::EXTENSION)
+    }
+
+    fn join(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                eprintln!("Autosave thread panicked.");
+            }
+        }
+    }
+    /// Writes next to the autosave and renames, so a crash mid-write never leaves a truncated autosave behind.
+    fn write(project: &Project, path: &Path) {
+        let temporary_path = path.with_extension("tmp");
+        if let Err(error) = project.save(&temporary_path) {
+            eprintln!("{}", error);
+            return;
+        }
+        if let Err(error) = fs::rename(&temporary_path, path) {
+            eprintln!("Failed to write autosave at: {}. Error: {}", path.display(), error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(x: f32) -> Line {
+        Line { start: Point2::new(x, 0.0), end: Point2::new(x + 1.0, 0.0), velocity: 0.0 }
+    }
+
+    #[test]
+    fn opened_records_cant_be_undone() {
+        let mut composition = Composition::default();
+        for x in [0.0, 2.0] {
+            composition.get_track_mut(0).push_record(vec![line(x)], StrokeParams::default());
+        }
+        let text = Project::from_composition(&composition).to_text();
+
+        let mut opened = Project::parse(&text).unwrap().into_composition(TimelineConfig::default());
+        let record_system = opened.get_track_mut(0).get_record_system_mut();
+        record_system.undo();
+
+        assert_eq!(record_system.get_record_count(), 2);
+    }
+
+    #[test]
+    fn autosaves_are_per_process() {
+        let autosave = Autosave::new(Autosave::DEFAULT_INTERVAL);
+        let name = autosave.path.file_name().unwrap().to_str().unwrap();
+
+        assert!(name.contains(&std::process::id().to_string()));
+        assert!(Autosave::is_autosave(&autosave.path));
+    }
+
+    #[test]
+    fn saved_automation_renders_the_same() {
+        let mut composition = Composition::default();
+        let track = composition.get_track_mut(0);
+        track.push_record(vec![line(0.0), line(1.0)], StrokeParams::default());
+        track.push_automation(vec![Line { start: Point2::new(0.5, 0.2), end: Point2::new(1.5, 0.8), velocity: 0.0 }]);
+        let rendered = composition.render_samples_f32();
+
+        let text = Project::from_composition(&composition).to_text();
+        let opened = Project::parse(&text).unwrap().into_composition(TimelineConfig::default());
+        assert_eq!(opened.render_samples_f32(), rendered);
+    }
+
+    #[test]
+    fn saved_stroke_params_render_the_same() {
+        let mut composition = Composition::default();
+        let track = composition.get_track_mut(0);
+        track.push_record(vec![line(0.0)], StrokeParams { instrument: Some(Instrument::Square), amplitude: Some(0.5) });
+        track.push_record(vec![line(1.0)], StrokeParams::default());
+        let rendered = composition.render_samples_f32();
+
+        let text = Project::from_composition(&composition).to_text();
+        let opened = Project::parse(&text).unwrap().into_composition(TimelineConfig::default());
+        assert_eq!(opened.get_tracks()[0].get_record_system().get_record_params(), composition.get_tracks()[0].get_record_system().get_record_params());
+        assert_eq!(opened.render_samples_f32(), rendered);
+    }
+
+    #[test]
+    fn sample_base_frequency_must_be_positive() {
+        let text = Project::from_composition(&Composition::default()).to_text();
+        assert!(text.contains("instrument sine\n"));
+
+        for base_frequency in ["0", "-440", "NaN", "inf"] {
+            let broken = text.replace("instrument sine\n", &format!("instrument sample {} kick.wav\n", base_frequency));
+            let error = Project::parse(&broken).err().unwrap();
+            assert!(error.contains("invalid base frequency"), "{}", error);
+        }
+    }
+}
@@ -1,34 +1,104 @@
-use crate::engine::{mesh::{Attribute, Layout, Mesh}, shader::Shader, texture::Texture};
+use crate::{engine::{mesh::{Attribute, Layout, Mesh}, shader::Shader, text::BitmapFont, texture::Texture}, tuning::Scale};
+
+/// Which rows of the timeline background are lit, one per pitch class.
+pub struct ScaleTemplate {
+    pub name: &'static str,
+    /// `None` lights every pitch class.
+    pub scale: Option<Scale>,
+    /// Pitch class (0 is C) of the scale's first degree.
+    pub tonic: u8,
+}
+impl ScaleTemplate {
+    /// The first one is the default.
+    pub const BUILT_IN: [Self; 5] = [
+        Self { name: "C major", scale: Some(Scale::Major), tonic: 0 },
+        Self { name: "A minor", scale: Some(Scale::NaturalMinor), tonic: 9 },
+        Self { name: "C major pentatonic", scale: Some(Scale::MajorPentatonic), tonic: 0 },
+        Self { name: "A minor pentatonic", scale: Some(Scale::MinorPentatonic), tonic: 9 },
+        Self { name: "Chromatic", scale: None, tonic: 0 },
+    ];
+
+    pub fn contains(&self, pitch_class: u8) -> bool {
+        match self.scale {
+            Some(scale) => scale.get_degrees().contains(&((pitch_class + 12 - self.tonic % 12) % 12)),
+            None => true,
+        }
+    }
+
+    /// Bit `n` is set when pitch class `n` is in the template.
+    pub fn get_mask(&self) -> u16 {
+        (0..12u8).filter(|pitch_class| self.contains(*pitch_class)).fold(0, |mask, pitch_class| mask | 1 << pitch_class)
+    }
+
+    /// 1x12 texture, texel row `i` covers world heights `i..i + 1`, which is where pitch `i + 1` is drawn.
+    fn create_texture(&self) -> Texture {
+        let data: Vec<u8> = (0..12u8)
+            .flat_map(|row| if self.contains((row + 1) % 12) { [255, 255, 255, 255] } else { [0, 0, 0, 255] })
+            .collect();
+
+        Texture::from_rgba(1, 12, &data, gl::NEAREST, gl::REPEAT).expect("Scale template matches its texture size.")
+    }
+}
 
 pub struct Resources {
     pub square_mesh: Mesh,
     pub line_mesh: Mesh,
 
     pub timeline_shader: Shader,
+    pub beat_grid_shader: Shader,
+    pub semitone_grid_shader: Shader,
     pub timeline_tone_shader: Shader,
+    pub automation_shader: Shader,
     pub playline_shader: Shader,
+    pub loop_region_shader: Shader,
+    pub waveform_shader: Shader,
+    pub ruler_shader: Shader,
+    pub cursor_shader: Shader,
+    pub text_shader: Shader,
 
-    pub cmajor_template_texture: Texture,
+    pub font: BitmapFont,
+    /// One per [ScaleTemplate::BUILT_IN], in the same order.
+    pub scale_template_textures: Vec<Texture>,
 }
 
 impl Default for Resources {
     fn default() -> Self {
         let timeline_shader = Shader::new("./assets/shaders/timeline.vert", "./assets/shaders/timeline.frag");
         timeline_shader.bind();
-        timeline_shader.set_int("u_CMajorTemplateSampler", 0);
+        timeline_shader.set_int("u_ScaleTemplateSampler", 0);
+
+        let text_shader = Shader::new("./assets/shaders/text.vert", "./assets/shaders/text.frag");
+        text_shader.bind();
+        text_shader.set_int("u_FontSampler", 0);
+        text_shader.set_float("u_GlyphCount", BitmapFont::glyph_count() as f32);
 
         Self {
             square_mesh: Mesh::basic_square(),
             line_mesh: Mesh::new(&[1.0, -1.0], &Layout::default().next_attribute(Attribute::Float), gl::LINES),
 
             timeline_shader,
-            timeline_tone_shader: Shader::new(
+            beat_grid_shader: Shader::new("./assets/shaders/timeline.vert", "./assets/shaders/beat_grid.frag"),
+            semitone_grid_shader: Shader::new("./assets/shaders/timeline.vert", "./assets/shaders/semitone_grid.frag"),
+            timeline_tone_shader: Shader::new_with_geometry(
                 "./assets/shaders/timeline_tone.vert",
+                "./assets/shaders/timeline_tone.geom",
                 "./assets/shaders/timeline_tone.frag",
             ),
+            automation_shader: Shader::new_with_geometry(
+                "./assets/shaders/timeline_tone.vert",
+                "./assets/shaders/timeline_tone.geom",
+                "./assets/shaders/automation.frag",
+            ),
             playline_shader: Shader::new("./assets/shaders/playline.vert", "./assets/shaders/playline.frag"),
+            loop_region_shader: Shader::new("./assets/shaders/timeline.vert", "./assets/shaders/loop_region.frag"),
+            waveform_shader: Shader::new("./assets/shaders/waveform.vert", "./assets/shaders/waveform.frag"),
+            ruler_shader: Shader::new("./assets/shaders/timeline.vert", "./assets/shaders/ruler.frag"),
+            cursor_shader: Shader::new("./assets/shaders/cursor.vert", "./assets/shaders/cursor.frag"),
+            text_shader,
+
+            font: BitmapFont::new(),
 
-            cmajor_template_texture: Texture::load_from_file("./assets/textures/cmajortemplate.png", gl::NEAREST, gl::REPEAT),
+            scale_template_textures: ScaleTemplate::BUILT_IN.iter().map(ScaleTemplate::create_texture).collect(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,185 @@
+use std::{f32::consts::PI, path::Path};
+
+use image::{Rgba, RgbaImage};
+use nalgebra::{Complex, Vector3};
+use rayon::prelude::*;
+
+/// Short-time FFT of rendered audio, saved as an image with time going right and frequency going up.
+#[derive(Clone, Copy, Debug)]
+pub struct SpectrogramSettings {
+    /// Samples per frame, a power of two. The image is half as many pixels tall.
+    pub fft_size: usize,
+    /// Samples between the starts of neighbouring frames, one pixel column each.
+    pub hop: usize,
+    /// Level in dBFS drawn as black, anything quieter is clipped.
+    pub floor_db: f32,
+}
+impl Default for SpectrogramSettings {
+    fn default() -> Self {
+        Self {
+            fft_size: 2048,
+            hop: 512,
+            floor_db: -90.0,
+        }
+    }
+}
+impl SpectrogramSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.fft_size.is_power_of_two() || self.fft_size < 2 {
+            return Err(format!("FFT size must be a power of two of at least 2, got: {}.", self.fft_size));
+        }
+        if self.hop == 0 {
+            return Err(String::from("Hop must be at least 1 sample."));
+        }
+        if !(self.floor_db.is_finite() && self.floor_db < 0.0) {
+            return Err(format!("Floor must be below 0 dB, got: {}.", self.floor_db));
+        }
+
+        Ok(())
+    }
+
+    /// Songs shorter than one frame are padded with silence to a single column.
+    pub fn render(&self, samples: &[f32]) -> RgbaImage {
+        let bins = self.fft_size / 2;
+        let frames = samples.len().saturating_sub(self.fft_size).div_ceil(self.hop) + 1;
+
+        let window: Vec<f32> = (0..self.fft_size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / self.fft_size as f32).cos())
+            .collect();
+        // A full scale sine peaks at 0 dB.
+        let scale = 2.0 / window.iter().sum::<f32>();
+
+        let columns: Vec<Vec<f32>> = (0..frames).into_par_iter().map(|frame| {
+            let start = frame * self.hop;
+            let mut buffer: Vec<Complex<f32>> = window
+                .iter()
+                .enumerate()
+                .map(|(i, weight)| Complex::new(samples.get(start + i).copied().unwrap_or(0.0) * weight, 0.0))
+                .collect();
+            fft(&mut buffer);
+
+            buffer[..bins]
+                .iter()
+                .map(|bin| {
+                    let db = 20.0 * (bin.norm() * scale).max(f32::MIN_POSITIVE).log10();
+                    (1.0 - db / self.floor_db).clamp(0.0, 1.0)
+                })
+                .collect()
+        }).collect();
+
+        let mut image = RgbaImage::new(frames as u32, bins as u32);
+        for (x, column) in columns.iter().enumerate() {
+            for (bin, &level) in column.iter().enumerate() {
+                image.put_pixel(x as u32, (bins - 1 - bin) as u32, ramp_color(level));
+            }
+        }
+
+        image
+    }
+
+    pub fn save(&self, samples: &[f32], path: &Path) -> Result<(), String> {
+        self.validate().map_err(|error| format!("Failed to save spectrogram at: {}. Error: {}", path.display(), error))?;
+
+        self.render(samples)
+            .save(path)
+            .map_err(|error| format!("Failed to save spectrogram at: {}. Error: {}", path.display(), error))
+    }
+}
+
+/// In-place iterative radix-2 FFT, `buffer` must be a power of two long.
+pub(crate) fn fft(buffer: &mut [Complex<f32>]) {
+    let length = buffer.len();
+    let bits = length.trailing_zeros();
+    if bits == 0 {
+        return;
+    }
+
+    for i in 0..length {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= length {
+        let step = Complex::from_polar(1.0, -2.0 * PI / size as f32);
+        for chunk in buffer.chunks_exact_mut(size) {
+            let (low, high) = chunk.split_at_mut(size / 2);
+            let mut twiddle = Complex::new(1.0, 0.0);
+            for (a, b) in low.iter_mut().zip(high.iter_mut()) {
+                let product = *b * twiddle;
+                *b = *a - product;
+                *a += product;
+                twiddle *= step;
+            }
+        }
+        size *= 2;
+    }
+}
+
+/// Black through purple and orange to pale yellow as `level` goes from 0 to 1.
+fn ramp_color(level: f32) -> Rgba<u8> {
+    const STOPS: [Vector3<f32>; 5] = [
+        Vector3::new(0.0, 0.0, 0.02),
+        Vector3::new(0.3, 0.05, 0.45),
+        Vector3::new(0.75, 0.2, 0.35),
+        Vector3::new(0.98, 0.55, 0.1),
+        Vector3::new(1.0, 0.98, 0.7),
+    ];
+
+    let position = level * (STOPS.len() - 1) as f32;
+    let i = (position as usize).min(STOPS.len() - 2);
+    let color = STOPS[i].lerp(&STOPS[i + 1], position - i as f32);
+
+    Rgba([(color.x * 255.0) as u8, (color.y * 255.0) as u8, (color.z * 255.0) as u8, 255])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fft_matches_a_direct_dft() {
+        let input: Vec<Complex<f32>> = (0..64).map(|i| Complex::new((i as f32 * 0.7).sin() + (i % 5) as f32 * 0.1, (i as f32 * 0.3).cos())).collect();
+        let mut output = input.clone();
+        fft(&mut output);
+
+        for (k, bin) in output.iter().enumerate() {
+            let dft: Complex<f32> = input
+                .iter()
+                .enumerate()
+                .map(|(n, sample)| sample * Complex::from_polar(1.0, -2.0 * PI * (k * n) as f32 / input.len() as f32))
+                .sum();
+            assert!((bin - dft).norm() < 1e-3, "bin {k}: {bin}, expected {dft}");
+        }
+    }
+
+    #[test]
+    fn sine_lights_up_its_bin() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        const BIN: usize = 46;
+
+        let settings = SpectrogramSettings::default();
+        let frequency = BIN as f32 * SAMPLE_RATE / settings.fft_size as f32;
+        let samples: Vec<f32> = (0..settings.fft_size).map(|i| (2.0 * PI * frequency * i as f32 / SAMPLE_RATE).sin()).collect();
+
+        let image = settings.render(&samples);
+        let bins = settings.fft_size / 2;
+        let brightest = (0..bins).max_by_key(|&bin| image.get_pixel(0, (bins - 1 - bin) as u32).0.iter().map(|&channel| channel as u32).sum::<u32>());
+        assert_eq!(brightest, Some(BIN));
+    }
+
+    #[test]
+    fn short_audio_is_zero_padded_to_one_column() {
+        let settings = SpectrogramSettings { fft_size: 256, ..Default::default() };
+        let samples: Vec<f32> = (0..100).map(|i| (i as f32 * 0.2).sin()).collect();
+        let mut padded = samples.clone();
+        padded.resize(settings.fft_size, 0.0);
+
+        let image = settings.render(&samples);
+        assert_eq!(image.dimensions(), (1, settings.fft_size as u32 / 2));
+        assert_eq!(image, settings.render(&padded));
+        assert_eq!(settings.render(&[]).dimensions(), (1, settings.fft_size as u32 / 2));
+    }
+}
@@ -1,107 +1,48 @@
 use core::f32;
-use std::{cmp::Ordering, time::{Duration, Instant}};
+use std::{collections::HashMap, path::Path, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread::{self, JoinHandle}, time::{Duration, Instant}};
 
-use gl::types::{GLint, GLsizeiptr, GLuint};
-use glfw::{Key, MouseButton};
-use nalgebra::{Point2, Vector2};
-use rodio::{Sink, Source};
+use glfw::{GamepadAxis, Key, MouseButton};
+use nalgebra::{Point2, Vector2, Vector4};
+use rodio::{buffer::SamplesBuffer, Sink, Source};
 
-use crate::{engine::window::Window, resources::Resources};
-
-#[derive(Default, PartialEq)]
-enum RecordDirection {
-    #[default] Undefined,
-    Right,
-    Left,
-}
-
-#[derive(Default)]
-struct Record {
-    direction: RecordDirection,
-    clamp_x: f32,
-}
+use crate::{composition::{Composition, Envelope, Instrument, Line, PitchInterpolation, PlayerSource, RecordSystem, StrokeParams, Track}, effects::{Bitcrusher, Delay, Reverb, Tremolo}, midi::MidiMessage, musicxml, note_list::{self, Note}, osc::{OscArgument, OscSender}, engine::{mesh::{Attribute, InstancedMesh, Layout, Mesh}, text::{BitmapFont, TextMesh}, window::Window}, resources::{Resources, ScaleTemplate}, tuning::{self, Scale}};
 
+/// Keeps the lines on the GPU in fixed-size chunks, so an edit only re-uploads the chunks after it.
 #[derive(Default)]
-pub struct RecordSystem {
-    history: Vec<Vec<Line>>,
-    undo_history: Vec<Vec<Line>>,
-
-    current: Record,
+pub struct ToneSystem {
+    chunks: Vec<LinesMesh>,
+    /// Index of the first line that changed since the last update.
+    dirty_from: Option<usize>,
 }
-impl RecordSystem {
-    pub fn new_record(&mut self) {
-        self.current = Record::default();
-        self.undo_history.clear();
+impl ToneSystem {
+    const CHUNK_SIZE: usize = 4096;
 
-        if let Some(lines) = self.history.last() {
-            if lines.is_empty() {
-                self.history.pop();
-            }
-        }
-        self.history.push(Vec::new());
+    pub fn mark_dirty(&mut self, from: usize) {
+        self.dirty_from = Some(self.dirty_from.map_or(from, |dirty_from| dirty_from.min(from)));
     }
-    pub fn add_line(&mut self, start: Point2<f32>, end: Point2<f32>, tone_system: &mut ToneSystem) -> Option<&Line> {
-        let line = Line { start, end };
-        if self.current.direction == RecordDirection::Undefined {
-            if start.x < end.x {
-                self.current.direction = RecordDirection::Right;
-                self.current.clamp_x = 0.0;
-            } else {
-                self.current.direction = RecordDirection::Left;
-                self.current.clamp_x = f32::INFINITY;
-            }
-        }
 
-        if end.x - start.x > 0.005 {
-            if self.current.direction == RecordDirection::Left {
-                self.new_record();
-                self.current.direction = RecordDirection::Right;
-                self.current.clamp_x = f32::INFINITY;
-            }
-        } else if end.x - start.x < -0.005 && self.current.direction == RecordDirection::Right {
-            self.new_record();
-            self.current.direction = RecordDirection::Left;
-            self.current.clamp_x = 0.0;
-        }
-        
-        if let Some(last) = self.history.last_mut() {
-            last.push(line.clone());
-            tone_system.mark_dirty();
-
-            return last.last();
-        }
+    pub fn update(&mut self, record_system: &RecordSystem) {
+        let Some(dirty_from) = self.dirty_from.take() else { return; };
+        let lines = record_system.get_lines();
 
-        None
-    }
+        self.chunks.truncate(lines.len().div_ceil(Self::CHUNK_SIZE));
+        for (i, chunk_lines) in lines.chunks(Self::CHUNK_SIZE).enumerate().skip(dirty_from / Self::CHUNK_SIZE) {
+            if i == self.chunks.len() {
+                self.chunks.push(LinesMesh::with_capacity(Self::CHUNK_SIZE));
+            }
 
-    pub fn undo(&mut self, tone_system: &mut ToneSystem) {
-        if let Some(lines) = self.history.pop() {
-            self.undo_history.push(lines);
-            tone_system.mark_dirty();
+            let first = dirty_from.saturating_sub(i * Self::CHUNK_SIZE);
+            self.chunks[i].write(first, &chunk_lines[first..]);
         }
     }
-    pub fn redo(&mut self, tone_system: &mut ToneSystem) {
-        if let Some(lines) = self.undo_history.pop() {
-            self.history.push(lines);
-            tone_system.mark_dirty();
-        }
-    }
-}
 
-#[derive(Default)]
-pub struct ToneSystem {
-    tones_lines_mesh: LinesMesh,
-    dirty: bool,
-}
-impl ToneSystem {
-    pub fn mark_dirty(&mut self) {
-        self.dirty = true;
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty_from.is_some()
     }
 
-    pub fn update(&mut self, record_system: &RecordSystem) {
-        if self.dirty {
-            self.tones_lines_mesh.update(&record_system.history.iter().flatten().cloned().collect::<Vec<Line>>());
-            self.dirty = false;
+    pub fn draw(&self) {
+        for chunk in &self.chunks {
+            chunk.draw();
         }
     }
 }
@@ -113,26 +54,49 @@ pub struct DrawingSystem {
 
     last_cursor_x: f32,
     last_cursor_y: f32,
+
+    /// Synth settings new records are drawn with.
+    stroke_params: StrokeParams,
+    /// Record picked with Shift+right-click, which the arrow keys move.
+    selected_record: Option<usize>,
 }
 impl DrawingSystem {
-    pub fn update(&mut self, window: &Window, view: &View, tone_system: &mut ToneSystem, record_system: &mut RecordSystem) {
-        let cursor_x = window.get_mouse_x() / window.get_width() as f32 * view.scale.x + view.offset.x;
-        let cursor_y = (1.0 - window.get_mouse_y() / window.get_height() as f32) * view.scale.y + view.offset.y;
+    pub const fn get_stroke_params(&self) -> &StrokeParams {
+        &self.stroke_params
+    }
+    pub fn set_stroke_params(&mut self, stroke_params: StrokeParams) {
+        self.stroke_params = stroke_params;
+    }
+    pub const fn get_selected_record(&self) -> Option<usize> {
+        self.selected_record
+    }
+    pub fn set_selected_record(&mut self, selected_record: Option<usize>) {
+        self.selected_record = selected_record;
+    }
+
+    pub fn update(&mut self, window: &Window, view: &View, record_system: &mut RecordSystem) {
+        let cursor = view.get_cursor_position(window);
+        if window.is_minimized() || !cursor.x.is_finite() || !cursor.y.is_finite() {
+            return;
+        }
+        let (cursor_x, cursor_y) = (cursor.x, cursor.y);
 
         if window.is_mouse_button_just_pressed(MouseButton::Left) {
-            record_system.new_record();
+            record_system.new_record(self.stroke_params.clone());
 
             self.cursor_click_x = cursor_x;
             self.cursor_click_y = cursor_y;
         }
         
-        let safe_radius: f32 = 0.025 * Vector2::new(
-            window.get_mouse_dx(),
-            window.get_mouse_dy(),
-        ).magnitude();
+        // Dead-zone around the click in pixels, converted to world units so it stays the same size at any zoom.
+        const SAFE_RADIUS_PIXELS: f32 = 3.0;
+        let safe_radius = Vector2::new(
+            SAFE_RADIUS_PIXELS / window.get_width() as f32 * view.scale.x,
+            SAFE_RADIUS_PIXELS / window.get_height() as f32 * view.scale.y,
+        );
         let is_out_of_safe_radius =
-            (cursor_x - self.cursor_click_x).abs() > safe_radius ||
-            (cursor_y - self.cursor_click_y).abs() > safe_radius;
+            (cursor_x - self.cursor_click_x).abs() > safe_radius.x ||
+            (cursor_y - self.cursor_click_y).abs() > safe_radius.y;
         
         if window.is_mouse_button_pressed(MouseButton::Left) && is_out_of_safe_radius {
             let is_just_out_from_safe_radius = self.cursor_click_x != f32::INFINITY && self.cursor_click_y != f32::INFINITY;
@@ -140,7 +104,7 @@ impl DrawingSystem {
                 record_system.add_line(
                     Point2::new(self.cursor_click_x, self.cursor_click_y),
                     Point2::new(cursor_x, cursor_y),
-                    tone_system,
+                    window.get_delta_secs(),
                 );
             } else {
                 let is_mouse_moved = cursor_x != self.last_cursor_x || cursor_y != self.last_cursor_y;
@@ -148,7 +112,7 @@ impl DrawingSystem {
                     record_system.add_line(
                         Point2::new(self.last_cursor_x, self.last_cursor_y),
                         Point2::new(cursor_x, cursor_y),
-                        tone_system,
+                        window.get_delta_secs(),
                     );
                 }
             }
@@ -159,41 +123,215 @@ impl DrawingSystem {
 
         self.last_cursor_x = cursor_x;
         self.last_cursor_y = cursor_y;
+
+        // Right-clicking a record deletes it, as an edit that can be undone. With Shift it's selected instead.
+        const DELETE_RADIUS_PIXELS: f32 = 6.0;
+        if window.is_mouse_button_just_pressed(MouseButton::Right) && !window.is_mouse_button_pressed(MouseButton::Left) {
+            let radius = Vector2::new(
+                DELETE_RADIUS_PIXELS / window.get_width() as f32 * view.scale.x,
+                DELETE_RADIUS_PIXELS / window.get_height() as f32 * view.scale.y,
+            );
+            if let Some(index) = record_system.find_record_near(cursor, radius) {
+                if window.is_key_pressed(Key::LeftShift) {
+                    self.selected_record = Some(index);
+                    println!("Selected record {}, arrow keys move it and Escape deselects.", index + 1);
+                } else {
+                    record_system.remove_record(index);
+                    self.selected_record = None;
+                    println!("Deleted record {}.", index + 1);
+                }
+            }
+        }
     }
 }
 
 pub struct RenderSystem;
 impl RenderSystem {
-    pub fn draw_timeline(&self, resources: &Resources, view: &View, bpm: f32) {
+    /// `scale_template` indexes [ScaleTemplate::BUILT_IN], `None` leaves the rows plain.
+    /// `bar_beats` is the length of a bar in quarter note beats.
+    pub fn draw_timeline(&self, resources: &Resources, view: &View, bpm: f32, bar_beats: f32, scale_template: Option<usize>) {
         resources.timeline_shader.bind();
         resources.timeline_shader.set_vec2("u_ViewOffset", &view.offset);
         resources.timeline_shader.set_vec2("u_ViewScale", &view.scale);
         resources.timeline_shader.set_float("u_BPM", bpm);
+        resources.timeline_shader.set_float("u_BarBeats", bar_beats);
+        resources.timeline_shader.set_int("u_ShowTemplate", scale_template.is_some() as i32);
+
+        if let Some(scale_template) = scale_template {
+            resources.scale_template_textures[scale_template].bind(0);
+        }
+        resources.square_mesh.draw();
+    }
+    pub fn draw_semitone_grid(&self, resources: &Resources, grid_config: &GridConfig, view: &View) {
+        resources.semitone_grid_shader.bind();
+        resources.semitone_grid_shader.set_vec2("u_ViewOffset", &view.offset);
+        resources.semitone_grid_shader.set_vec2("u_ViewScale", &view.scale);
+        resources.semitone_grid_shader.set_vec4("u_SemitoneColor", &grid_config.semitone_color);
+        resources.semitone_grid_shader.set_vec4("u_OctaveColor", &grid_config.octave_color);
+        resources.semitone_grid_shader.set_float("u_SemitoneWidth", grid_config.semitone_width);
+        resources.semitone_grid_shader.set_float("u_OctaveWidth", grid_config.octave_width);
+
+        resources.square_mesh.draw();
+    }
+    /// `bar_beats` is the length of a bar in quarter note beats.
+    pub fn draw_beat_grid(&self, resources: &Resources, grid_config: &GridConfig, bar_beats: f32, view: &View) {
+        resources.beat_grid_shader.bind();
+        resources.beat_grid_shader.set_vec2("u_ViewOffset", &view.offset);
+        resources.beat_grid_shader.set_vec2("u_ViewScale", &view.scale);
+        resources.beat_grid_shader.set_float("u_BarBeats", bar_beats);
+        resources.beat_grid_shader.set_float("u_Subdivisions", grid_config.beat_subdivisions.max(1) as f32);
+        resources.beat_grid_shader.set_vec4("u_BarColor", &grid_config.bar_color);
+        resources.beat_grid_shader.set_vec4("u_BeatColor", &grid_config.beat_color);
+        resources.beat_grid_shader.set_vec4("u_SubdivisionColor", &grid_config.subdivision_color);
+        resources.beat_grid_shader.set_float("u_BarWidth", grid_config.bar_width);
+        resources.beat_grid_shader.set_float("u_BeatWidth", grid_config.beat_width);
+        resources.beat_grid_shader.set_float("u_SubdivisionWidth", grid_config.subdivision_width);
 
-        resources.cmajor_template_texture.bind(0);
         resources.square_mesh.draw();
     }
-    pub fn draw_timeline_tones(&self, resources: &Resources, tone_system: &ToneSystem, view: &View) {
+    /// Parts of lines on pitches outside of `highlight_outside`, if any, are drawn in a warning color.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_timeline_tones(
+        &self,
+        resources: &Resources,
+        tone_system: &ToneSystem,
+        color_ramp: ToneColorRamp,
+        highlight_outside: Option<&ScaleTemplate>,
+        line_style: &ToneLineStyle,
+        screen_size: Vector2<f32>,
+        view: &View,
+    ) {
         resources.timeline_tone_shader.bind();
         resources.timeline_tone_shader.set_vec2("u_ViewOffset", &view.offset);
         resources.timeline_tone_shader.set_vec2("u_ViewScale", &view.scale);
-
-        tone_system.tones_lines_mesh.draw();
+        resources.timeline_tone_shader.set_vec2("u_ScreenSize", &screen_size);
+        resources.timeline_tone_shader.set_float("u_LineWidth", line_style.width);
+        resources.timeline_tone_shader.set_float("u_Feather", line_style.feather);
+        resources.timeline_tone_shader.set_int("u_ColorRamp", color_ramp as i32);
+        resources.timeline_tone_shader.set_int("u_HighlightOutOfScale", highlight_outside.is_some() as i32);
+        resources.timeline_tone_shader.set_int("u_ScaleMask", highlight_outside.map_or(0, ScaleTemplate::get_mask) as i32);
+
+        tone_system.draw();
+    }
+    /// Volume curves of a track, `view` being a [View::automation].
+    pub fn draw_automation(&self, resources: &Resources, automation_system: &ToneSystem, color: Vector4<f32>, line_style: &ToneLineStyle, screen_size: Vector2<f32>, view: &View) {
+        resources.automation_shader.bind();
+        resources.automation_shader.set_vec2("u_ViewOffset", &view.offset);
+        resources.automation_shader.set_vec2("u_ViewScale", &view.scale);
+        resources.automation_shader.set_vec2("u_ScreenSize", &screen_size);
+        resources.automation_shader.set_float("u_LineWidth", line_style.width);
+        resources.automation_shader.set_float("u_Feather", line_style.feather);
+        resources.automation_shader.set_vec4("u_Color", &color);
+
+        automation_system.draw();
     }
-    pub fn draw_playline(&self, resources: &Resources, play_time: f32, view: &View) {
+    pub fn draw_playline(&self, resources: &Resources, play_time: f32, style: &PlaylineStyle, screen_width: f32, view: &View) {
         resources.playline_shader.bind();
         resources.playline_shader.set_float("u_Time", play_time);
         resources.playline_shader.set_float("u_ViewOffset", view.offset.x);
         resources.playline_shader.set_float("u_ViewScale", view.scale.x);
+        resources.playline_shader.set_float("u_HalfWidth", style.width / screen_width);
+        resources.playline_shader.set_vec4("u_BaseColor", &style.base_color);
+        resources.playline_shader.set_vec4("u_Color", &style.color);
+
+        resources.square_mesh.draw();
+    }
+    /// Shades the beats from `start` to `end`.
+    pub fn draw_loop_region(&self, resources: &Resources, (start, end): (f32, f32), color: Vector4<f32>, view: &View) {
+        resources.loop_region_shader.bind();
+        resources.loop_region_shader.set_vec2("u_ViewOffset", &view.offset);
+        resources.loop_region_shader.set_vec2("u_ViewScale", &view.scale);
+        resources.loop_region_shader.set_vec2("u_Region", &Vector2::new(start, end));
+        resources.loop_region_shader.set_vec4("u_Color", &color);
+
+        resources.square_mesh.draw();
+    }
+    pub fn draw_waveform_preview(&self, resources: &Resources, waveform_preview: &Mesh, view: &View) {
+        const STRIP_HEIGHT: f32 = 0.1;
+
+        resources.waveform_shader.bind();
+        resources.waveform_shader.set_float("u_ViewOffset", view.offset.x);
+        resources.waveform_shader.set_float("u_ViewScale", view.scale.x);
+        resources.waveform_shader.set_float("u_StripHeight", STRIP_HEIGHT);
+
+        waveform_preview.draw();
+    }
+    /// Tick strip along the top edge, `strip_height` pixels tall. Long ticks every `label_beats`, short ones every `tick_beats`.
+    pub fn draw_ruler(&self, resources: &Resources, label_beats: f32, tick_beats: f32, strip_height: f32, screen_height: f32, view: &View) {
+        resources.ruler_shader.bind();
+        resources.ruler_shader.set_vec2("u_ViewOffset", &view.offset);
+        resources.ruler_shader.set_vec2("u_ViewScale", &view.scale);
+        resources.ruler_shader.set_float("u_StripHeight", strip_height / screen_height);
+        resources.ruler_shader.set_float("u_LabelBeats", label_beats);
+        resources.ruler_shader.set_float("u_TickBeats", tick_beats);
+        resources.ruler_shader.set_vec4("u_BackgroundColor", &Vector4::new(0.05, 0.05, 0.05, 0.85));
+        resources.ruler_shader.set_vec4("u_TickColor", &Vector4::new(1.0, 1.0, 1.0, 0.5));
 
-        resources.line_mesh.draw();
+        resources.square_mesh.draw();
+    }
+    /// Crosshair centered on `position` in world space, `half_size` is the length of its arms in clip space.
+    pub fn draw_cursor(&self, resources: &Resources, position: Point2<f32>, half_size: Vector2<f32>, color: Vector4<f32>, view: &View) {
+        resources.cursor_shader.bind();
+        resources.cursor_shader.set_vec2("u_ViewOffset", &view.offset);
+        resources.cursor_shader.set_vec2("u_ViewScale", &view.scale);
+        resources.cursor_shader.set_vec2("u_Position", &position.coords);
+        resources.cursor_shader.set_vec2("u_HalfSize", &half_size);
+        resources.cursor_shader.set_vec4("u_Color", &color);
+
+        for axis in [Vector2::x(), Vector2::y()] {
+            resources.cursor_shader.set_vec2("u_Axis", &axis);
+            resources.line_mesh.draw();
+        }
+    }
+    /// [BitmapFont] text placed in pixels, `glyph_size` is how big each character is drawn in pixels.
+    pub fn draw_text(&self, resources: &Resources, text_mesh: &TextMesh, screen_size: Vector2<f32>, glyph_size: Vector2<f32>, color: Vector4<f32>) {
+        resources.text_shader.bind();
+        resources.text_shader.set_vec2("u_ScreenSize", &screen_size);
+        resources.text_shader.set_vec2("u_GlyphSize", &glyph_size);
+        resources.text_shader.set_vec4("u_Color", &color);
+
+        resources.font.bind(0);
+        text_mesh.draw();
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct View {
     offset: Vector2<f32>,
     scale: Vector2<f32>,
 }
+impl View {
+    /// World position of the bottom left corner, x in beats and y in semitones.
+    pub const fn get_offset(&self) -> Vector2<f32> {
+        self.offset
+    }
+    /// Time can't be scrolled before the first beat.
+    pub fn set_offset(&mut self, offset: Vector2<f32>) {
+        self.offset = Vector2::new(offset.x.max(0.0), offset.y);
+    }
+
+    /// Visible beats and semitones.
+    pub const fn get_scale(&self) -> Vector2<f32> {
+        self.scale
+    }
+    /// Only kept positive, the zoom limits are part of [ViewConfig].
+    pub fn set_scale(&mut self, scale: Vector2<f32>) {
+        self.scale = Vector2::new(scale.x.max(f32::EPSILON), scale.y.max(f32::EPSILON));
+    }
+
+    /// The same beats with y going from 0 at the bottom of the screen to 1 at the top, where automation gain is drawn.
+    pub fn automation(&self) -> Self {
+        Self { offset: Vector2::new(self.offset.x, 0.0), scale: Vector2::new(self.scale.x, 1.0) }
+    }
+
+    /// World position (x in beats, y in semitones) under the mouse cursor.
+    pub fn get_cursor_position(&self, window: &Window) -> Point2<f32> {
+        Point2::new(
+            window.get_mouse_x() / window.get_width() as f32 * self.scale.x + self.offset.x,
+            (1.0 - window.get_mouse_y() / window.get_height() as f32) * self.scale.y + self.offset.y,
+        )
+    }
+}
 impl Default for View {
     fn default() -> Self {
         Self {
@@ -203,31 +341,596 @@ impl Default for View {
     }
 }
 
+/// How scrolling and zooming the [View] feels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewConfig {
+    /// How fast the drawn view catches up with the scrolled one, higher is snappier.
+    pub sharpness: f32,
+    /// Fraction of the visible range zoomed per scroll step.
+    pub scale_speed: f32,
+    /// Fractions of the visible range scrolled per scroll step.
+    pub scroll_speed_x: f32,
+    pub scroll_speed_y: f32,
+    /// Visible ranges panned per second with the left stick fully tilted.
+    pub gamepad_pan_speed: f32,
+
+    /// Fewest and most beats visible at once.
+    pub scale_x_min: f32,
+    pub scale_x_max: f32,
+    /// Fewest and most semitones visible at once.
+    pub scale_y_min: f32,
+    pub scale_y_max: f32,
+}
+impl ViewConfig {
+    pub fn clamp_scale(&self, scale: Vector2<f32>) -> Vector2<f32> {
+        Vector2::new(
+            scale.x.clamp(self.scale_x_min, self.scale_x_max),
+            scale.y.clamp(self.scale_y_min, self.scale_y_max),
+        )
+    }
+}
+impl Default for ViewConfig {
+    fn default() -> Self {
+        Self {
+            sharpness: 36.0,
+            scale_speed: 0.1,
+            scroll_speed_x: 0.025,
+            scroll_speed_y: 0.015,
+            gamepad_pan_speed: 1.0,
+
+            scale_x_min: 0.25,
+            scale_x_max: 400.0,
+            scale_y_min: 6.0,
+            scale_y_max: 48.0,
+        }
+    }
+}
+
+/// Look of the horizontal line drawn at every semitone, C lines are emphasized,
+/// and of the vertical lines at every bar, beat and beat subdivision.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridConfig {
+    pub semitone_color: Vector4<f32>,
+    pub octave_color: Vector4<f32>,
+    /// Line widths in pixels.
+    pub semitone_width: f32,
+    pub octave_width: f32,
+
+    pub bar_color: Vector4<f32>,
+    pub beat_color: Vector4<f32>,
+    pub subdivision_color: Vector4<f32>,
+    /// Line widths in pixels.
+    pub bar_width: f32,
+    pub beat_width: f32,
+    pub subdivision_width: f32,
+    /// Lines per beat, 1 draws no subdivisions.
+    pub beat_subdivisions: u32,
+}
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            semitone_color: Vector4::new(1.0, 1.0, 1.0, 0.06),
+            octave_color: Vector4::new(1.0, 1.0, 1.0, 0.2),
+            semitone_width: 1.0,
+            octave_width: 2.0,
+
+            bar_color: Vector4::new(0.0, 0.0, 0.0, 0.35),
+            beat_color: Vector4::new(0.0, 0.0, 0.0, 0.2),
+            subdivision_color: Vector4::new(0.0, 0.0, 0.0, 0.08),
+            bar_width: 2.0,
+            beat_width: 1.0,
+            subdivision_width: 1.0,
+            beat_subdivisions: 4,
+        }
+    }
+}
+
+/// Look of the line at the play position, it fades from `base_color` at the bottom to `color` at the top.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlaylineStyle {
+    pub color: Vector4<f32>,
+    pub base_color: Vector4<f32>,
+    /// In pixels.
+    pub width: f32,
+    /// Shading over the loop region, if there is one.
+    pub loop_region_color: Vector4<f32>,
+}
+impl Default for PlaylineStyle {
+    fn default() -> Self {
+        Self {
+            color: Vector4::new(0.4, 1.0, 0.16, 1.0),
+            base_color: Vector4::new(0.16, 1.0, 0.1, 0.2),
+            width: 1.0,
+            loop_region_color: Vector4::new(0.16, 1.0, 0.1, 0.05),
+        }
+    }
+}
+
+/// Width of the drawn tone lines, the same at every zoom.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneLineStyle {
+    /// In pixels.
+    pub width: f32,
+    /// Pixels over which the edges fade out, 0 gives hard edges.
+    pub feather: f32,
+}
+impl Default for ToneLineStyle {
+    fn default() -> Self {
+        Self { width: 2.5, feather: 1.0 }
+    }
+}
+
+/// How lines are colored by their pitch, the index is the shader's `u_ColorRamp`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneColorRamp {
+    Plain,
+    /// Blue for low notes through green to yellow for high ones.
+    #[default] Spectrum,
+    /// A hue per pitch class, repeating every octave.
+    Chroma,
+    /// Dark blue for low notes to white for high ones.
+    Ice,
+}
+impl ToneColorRamp {
+    pub const fn next(&self) -> Self {
+        match self {
+            Self::Plain => Self::Spectrum,
+            Self::Spectrum => Self::Chroma,
+            Self::Chroma => Self::Ice,
+            Self::Ice => Self::Plain,
+        }
+    }
+}
+
+/// A MIDI key being held while recording.
+struct MidiNote {
+    start_beat: f32,
+    started: Instant,
+    /// Key velocity from 0 to 1.
+    loudness: f32,
+}
+
+/// `/note <voice> <frequency> <amplitude>` sent once playback reaches `time`, amplitude 0 releases the voice.
+struct OscNoteEvent {
+    time: Duration,
+    voice: i32,
+    frequency: f32,
+    amplitude: f32,
+}
+
+/// Plays `source` until `fading` is raised, then fades it out over [Timeline::PANIC_FADE_SECS] and ends it.
+/// A source that hasn't started playing by then ends right away.
+struct PanicFade<S> {
+    source: S,
+    fading: Arc<AtomicBool>,
+    started: bool,
+    /// Samples left of the fade, counting down once `fading` is raised.
+    remaining: Option<usize>,
+}
+impl<S: Source<Item = f32>> PanicFade<S> {
+    fn new(source: S, fading: Arc<AtomicBool>) -> Self {
+        Self { source, fading, started: false, remaining: None }
+    }
+}
+impl<S: Source<Item = f32>> Iterator for PanicFade<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if !self.fading.load(Ordering::Relaxed) {
+            self.started = true;
+            return self.source.next();
+        }
+        if !self.started {
+            return None;
+        }
+
+        let fade_samples = ((Timeline::PANIC_FADE_SECS * self.source.sample_rate() as f32) as usize * self.source.channels() as usize).max(1);
+        let remaining = self.remaining.get_or_insert(fade_samples);
+        *remaining = remaining.checked_sub(1)?;
+        let gain = *remaining as f32 / fade_samples as f32;
+        self.source.next().map(|sample| sample * gain)
+    }
+}
+impl<S: Source<Item = f32>> Source for PanicFade<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+/// Settings a [Timeline] starts with, the default reproduces [Timeline::default].
+#[derive(Clone, Debug)]
+pub struct TimelineConfig {
+    pub bpm: f32,
+    pub sample_rate: u32,
+    /// Linear gain applied to the final mix.
+    pub master_gain: f32,
+    /// Instrument of every new track.
+    pub default_instrument: Instrument,
+    pub envelope: Envelope,
+    /// Seconds each pitch jump within a record slides over, 0 disables portamento.
+    pub glide_secs: f32,
+    pub bitcrusher: Bitcrusher,
+    pub delay: Delay,
+    pub reverb: Reverb,
+    pub tremolo: Tremolo,
+    /// Edits each track keeps for undoing, 0 keeps them all.
+    pub undo_limit: usize,
+}
+impl Default for TimelineConfig {
+    fn default() -> Self {
+        Self {
+            bpm: Composition::DEFAULT_BPM,
+            sample_rate: Composition::DEFAULT_SAMPLE_RATE,
+            master_gain: 1.0,
+            default_instrument: Instrument::default(),
+            envelope: Envelope::default(),
+            glide_secs: 0.0,
+            bitcrusher: Bitcrusher::default(),
+            delay: Delay::default(),
+            reverb: Reverb::default(),
+            tremolo: Tremolo::default(),
+            undo_limit: 1000,
+        }
+    }
+}
+
+/// A track taken out by [Timeline::delete_track], with where it was.
+struct DeletedTrack {
+    index: usize,
+    track: Track,
+    /// It was the only track, so an empty one took its place.
+    was_only: bool,
+}
+
 pub struct Timeline {
-    record_system: RecordSystem,
+    composition: Composition,
+    /// GPU copies of the lines of each track of the composition.
+    tone_systems: Vec<ToneSystem>,
+    /// GPU copies of the automation lines of each track.
+    automation_systems: Vec<ToneSystem>,
+    active_track: usize,
+    /// Newest last, see [Self::restore_deleted_track].
+    deleted_tracks: Vec<DeletedTrack>,
+    /// Drawing, undo and deleting edit the active track's automation instead of its notes.
+    editing_automation: bool,
+
     drawing_system: DrawingSystem,
     render_system: RenderSystem,
-    tone_system: ToneSystem,
 
     raw_view: View,
     view: View,
+    view_config: ViewConfig,
+    grid_config: GridConfig,
+    playline_style: PlaylineStyle,
+    tone_line_style: ToneLineStyle,
+    /// Index into [ScaleTemplate::BUILT_IN] of the background.
+    scale_template: usize,
+    /// Lights the rows of [Self::scale_template] in the background.
+    show_template: bool,
+    /// Draws the parts of lines on pitches outside of the scale template in a warning color.
+    highlight_out_of_scale: bool,
+    tone_color_ramp: ToneColorRamp,
 
     playing: bool,
     player_timer: Instant,
     player_duration: Duration,
-    player_bpm: f32,
+    /// Raised by [Self::panic] to fade out everything the last [Self::play] queued.
+    panic_fade: Arc<AtomicBool>,
+
+    /// Beats per bar and the note value of a beat, like `(6, 8)`.
+    time_signature: (u32, u32),
+    count_in_bars: u32,
+
+    playhead_beat: f32,
+    dragging_playhead: bool,
+
+    /// World position under the cursor and the crosshair size in clip space, `None` while drawing or off the timeline.
+    hover: Option<(Point2<f32>, Vector2<f32>)>,
+    /// Note names along the left edge, created on the first update since it needs a GL context.
+    note_labels: Option<TextMesh>,
+    /// Note, frequency and beat under the cursor in the bottom right corner, empty while not hovering.
+    readout: Option<TextMesh>,
+    /// Bar:beat and seconds along the top edge, one label every `ruler_label_beats`.
+    ruler_labels: Option<TextMesh>,
+    ruler_label_beats: f32,
+    screen_size: Vector2<f32>,
+
+    waveform_preview: Option<Mesh>,
+    /// [Composition::get_render_key] the waveform preview was built from.
+    waveform_key: Option<u64>,
+    waveform_dirty: bool,
+    /// Peaks being computed off the UI thread and the beats per peak.
+    waveform_worker: Option<(f32, JoinHandle<Vec<f32>>)>,
+
+    midi_recording: bool,
+    midi_notes: HashMap<u8, MidiNote>,
+
+    osc_sender: Option<Arc<OscSender>>,
+    osc_replaces_audio: bool,
+    osc_enabled: bool,
+    /// Events of the current playback in time order, and how many of them were sent.
+    osc_events: Vec<OscNoteEvent>,
+    osc_sent: usize,
+    osc_voices: usize,
+
+    /// Incremented on every change that would be saved in a project.
+    revision: u64,
+    saved_revision: u64,
 }
 impl Timeline {
+    /// Text draws the 5x7 font at twice its size.
+    const TEXT_GLYPH_SIZE: Vector2<f32> = Vector2::new(BitmapFont::GLYPH_WIDTH as f32 * 2.0, BitmapFont::GLYPH_HEIGHT as f32 * 2.0);
+    const TEXT_ADVANCE: f32 = BitmapFont::GLYPH_WIDTH as f32 * 2.0 + 2.0;
+    const RULER_HEIGHT: f32 = BitmapFont::GLYPH_HEIGHT as f32 * 2.0 + 8.0;
+    /// Short enough to feel instant, long enough not to click.
+    pub const PANIC_FADE_SECS: f32 = 0.02;
+
     fn update_record_system(&mut self, window: &Window) {
+        let track = self.composition.get_track_mut(self.active_track);
+        let record_system = if self.editing_automation { track.get_automation_mut() } else { track.get_record_system_mut() };
         if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::Z) {
             if window.is_key_pressed(Key::LeftShift) {
-                self.record_system.redo(&mut self.tone_system);
+                record_system.redo();
             } else {
-                self.record_system.undo(&mut self.tone_system);
+                record_system.undo();
             }
         }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::N) {
+            let count = record_system.get_record_count();
+            record_system.clear();
+            println!("Track {}: cleared {} records, Ctrl+Z brings them back.", self.active_track + 1, count);
+        }
+    }
+    /// Arrow keys move the selected record by a grid step in time and a semitone in pitch, or to the next pitch of the
+    /// snapping scale. With Shift they move it by a bar or an octave. Automation moves by a twentieth, or a quarter.
+    fn update_selection(&mut self, window: &Window) {
+        let Some(index) = self.drawing_system.get_selected_record() else {
+            return;
+        };
+        let track = self.get_active_track();
+        let record_system = if self.editing_automation { track.get_automation() } else { track.get_record_system() };
+        let Some(lines) = record_system.records().nth(index).filter(|lines| !lines.is_empty()) else {
+            self.drawing_system.set_selected_record(None);
+            return;
+        };
+        if window.is_key_just_pressed(Key::Escape) {
+            self.drawing_system.set_selected_record(None);
+            println!("Selection cleared.");
+            return;
+        }
+        if window.is_key_pressed(Key::LeftControl) {
+            return;
+        }
+
+        let direction = Vector2::new(
+            window.is_key_just_pressed(Key::Right) as i32 - window.is_key_just_pressed(Key::Left) as i32,
+            window.is_key_just_pressed(Key::Up) as i32 - window.is_key_just_pressed(Key::Down) as i32,
+        ).cast::<f32>();
+        if direction == Vector2::zeros() {
+            return;
+        }
+        let larger = window.is_key_pressed(Key::LeftShift);
+
+        let step_x = if larger {
+            self.get_bar_beats()
+        } else {
+            self.composition.get_time_quantize().unwrap_or(1.0 / self.grid_config.beat_subdivisions.max(1) as f32)
+        };
+        let step_y = match (self.editing_automation, larger, self.composition.get_scale()) {
+            (true, false, _) => 0.05 * direction.y,
+            (true, true, _) => 0.25 * direction.y,
+            (false, true, _) => 12.0 * direction.y,
+            (false, false, None) => direction.y,
+            (false, false, Some(scale)) => {
+                let tonic = self.composition.get_tonic();
+                let pitch = tuning::pitch_from_y(lines[0].start.y);
+                let snapped = scale.snap(pitch, tonic);
+                let target = (1..=12)
+                    .map(|semitones| snapped + semitones as f32 * direction.y)
+                    .find(|candidate| scale.snap(*candidate, tonic) == *candidate)
+                    .unwrap_or(snapped + 12.0 * direction.y);
+                target - pitch
+            }
+        };
+
+        // Records can't be moved before the start of the timeline.
+        let min_x = lines.iter().fold(f32::INFINITY, |min, line| min.min(line.start.x).min(line.end.x));
+        let offset = Vector2::new((step_x * direction.x).max(-min_x), step_y);
+
+        let track = self.composition.get_track_mut(self.active_track);
+        let record_system = if self.editing_automation { track.get_automation_mut() } else { track.get_record_system_mut() };
+        record_system.move_record(index, offset);
+        println!("Moved record {} by {} beats and {} {}.", index + 1, offset.x, offset.y, if self.editing_automation { "volume" } else { "semitones" });
+    }
+    fn update_tracks(&mut self, window: &Window) {
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::T) {
+            self.restore_deleted_track();
+        } else if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::T) {
+            self.add_track();
+        } else if window.is_key_just_pressed(Key::Tab) {
+            self.set_active_track((self.active_track + 1) % self.composition.get_tracks().len());
+        }
+
+        if window.is_key_pressed(Key::LeftControl) && (window.is_key_just_pressed(Key::Up) || window.is_key_just_pressed(Key::Down)) {
+            let step = if window.is_key_pressed(Key::Up) { 1.0 } else { -1.0 };
+            let tuning_a4 = self.composition.get_tuning_a4() + step;
+            self.get_composition_mut().set_tuning_a4(tuning_a4);
+            println!("A4 tuning: {} Hz.", self.composition.get_tuning_a4());
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::G) {
+            // Moves the lines themselves, to the subdivisions of the beat grid.
+            let grid = 1.0 / self.grid_config.beat_subdivisions.max(1) as f32;
+            self.get_active_track_mut().get_record_system_mut().quantize(grid);
+            println!("Track {} quantized to {} beats.", self.active_track + 1, grid);
+        } else if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::G) {
+            let time_quantize = match self.composition.get_time_quantize() {
+                None => Some(1.0),
+                Some(grid) if grid > 0.125 => Some(grid * 0.5),
+                Some(_) => None,
+            };
+            self.get_composition_mut().set_time_quantize(time_quantize);
+            println!("Time quantize: {:?} beats.", self.composition.get_time_quantize());
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::D) {
+            let pitch_interpolation = match self.composition.get_pitch_interpolation() {
+                PitchInterpolation::Linear => PitchInterpolation::Spline,
+                PitchInterpolation::Spline => PitchInterpolation::Linear,
+            };
+            self.get_composition_mut().set_pitch_interpolation(pitch_interpolation);
+            println!("Pitch interpolation: {:?}.", pitch_interpolation);
+        }
+        if window.is_key_pressed(Key::LeftControl) && (window.is_key_just_pressed(Key::Comma) || window.is_key_just_pressed(Key::Period)) {
+            // Comma moves the start of the loop region to the playhead and period its end, a missing end is a bar later.
+            let bar_beats = self.get_bar_beats();
+            let (start, end) = self.composition.get_loop_region().unwrap_or((self.playhead_beat, self.playhead_beat + bar_beats));
+            let loop_region = if window.is_key_pressed(Key::Comma) {
+                (self.playhead_beat, if end > self.playhead_beat { end } else { self.playhead_beat + bar_beats })
+            } else {
+                (if start < self.playhead_beat { start } else { (self.playhead_beat - bar_beats).max(0.0) }, self.playhead_beat)
+            };
+
+            self.get_composition_mut().set_loop_region(Some(loop_region));
+            println!("Loop region: {:?} beats.", self.composition.get_loop_region());
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::Slash) {
+            self.get_composition_mut().set_loop_region(None);
+            println!("Loop region cleared.");
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::A) {
+            self.editing_automation = !self.editing_automation;
+            self.drawing_system.set_selected_record(None);
+            println!("Editing: {}.", if self.editing_automation { "volume automation" } else { "notes" });
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::R) {
+            self.set_midi_recording(!self.midi_recording);
+            println!("MIDI recording: {}.", self.midi_recording);
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::Q) && self.osc_sender.is_some() {
+            self.set_osc_enabled(!self.osc_enabled);
+            println!("OSC output: {}.", self.osc_enabled);
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::C) {
+            // Strokes more than a bar apart stay separate, so undo still works phrase by phrase.
+            // Closer than the release they'd overlap as voices, merging them would change the sound.
+            const MARGIN_SECS: f32 = 0.001;
+            let release_beats = (self.composition.get_envelope().release + MARGIN_SECS) * self.composition.get_bpm() / 60.0;
+            let bar_beats = self.get_bar_beats();
+            let merged = self.get_active_track_mut().get_record_system_mut().consolidate(release_beats, Some(bar_beats));
+            println!("Track {}: merged {} records.", self.active_track + 1, merged);
+        } else if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::C) {
+            let bypass = !self.composition.is_compressor_bypassed();
+            self.get_composition_mut().set_compressor_bypass(bypass);
+            println!("Compressor: {}.", if bypass { "bypassed" } else { "on" });
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::K) {
+            self.set_count_in_bars((self.count_in_bars + 1) % 3);
+            println!("Count-in: {} bars.", self.count_in_bars);
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::J) {
+            const TIME_SIGNATURES: [(u32, u32); 5] = [(4, 4), (3, 4), (6, 8), (5, 4), (7, 8)];
+
+            let next = TIME_SIGNATURES.iter().position(|signature| *signature == self.time_signature).map_or(0, |i| i + 1);
+            self.set_time_signature(TIME_SIGNATURES[next % TIME_SIGNATURES.len()]);
+            println!("Time signature: {}/{}.", self.time_signature.0, self.time_signature.1);
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::W) {
+            const SWINGS: [f32; 4] = [0.0, 0.2, 1.0 / 3.0, 0.5];
+
+            let next = SWINGS.iter().position(|swing| *swing == self.get_swing()).map_or(0, |i| i + 1);
+            self.set_swing(SWINGS[next % SWINGS.len()]);
+            println!("Swing: {}.", self.get_swing());
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::M) {
+            let mix_mode = self.composition.get_mix_mode().next();
+            self.get_composition_mut().set_mix_mode(mix_mode);
+            println!("Mix mode: {:?}.", mix_mode);
+        } else if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::M) {
+            let temperament = self.composition.get_temperament().next();
+            self.get_composition_mut().set_temperament(temperament);
+            println!("Temperament: {:?}.", self.composition.get_temperament());
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::P) {
+            let scale = match self.composition.get_scale() {
+                None => Some(Scale::Major),
+                Some(scale) => scale.next(),
+            };
+            self.get_composition_mut().set_scale(scale);
+            println!("Scale snapping: {:?}.", self.composition.get_scale());
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::H) {
+            self.set_show_template(!self.show_template);
+            println!("Scale template background: {}.", if self.show_template { "shown" } else { "hidden" });
+        } else if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::H) {
+            self.set_scale_template(self.scale_template + 1);
+            println!("Scale template: {}.", self.get_scale_template().name);
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::U) {
+            self.set_tone_color_ramp(self.tone_color_ramp.next());
+            println!("Line colors: {:?}.", self.tone_color_ramp);
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::E) {
+            self.set_highlight_out_of_scale(!self.highlight_out_of_scale);
+            println!("Out of scale highlight: {}.", self.highlight_out_of_scale);
+        }
+        if window.is_key_pressed(Key::LeftControl) && (window.is_key_just_pressed(Key::Left) || window.is_key_just_pressed(Key::Right)) {
+            let step = if window.is_key_pressed(Key::Right) { 1 } else { 11 };
+            let tonic = (self.composition.get_tonic() + step) % 12;
+            self.get_composition_mut().set_tonic(tonic);
+            println!("Tonic: {}.", tuning::pitch_class_name(tonic));
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::B) {
+            let band_limited = !self.composition.is_band_limited();
+            self.get_composition_mut().set_band_limited(band_limited);
+            println!("Band-limited oscillators: {}.", band_limited);
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_pressed(Key::LeftShift) && window.is_key_just_pressed(Key::I) {
+            // Instrument of the strokes drawn from now on, starting from the track's own.
+            let mut params = self.drawing_system.get_stroke_params().clone();
+            params.instrument = match params.instrument {
+                None => Some(self.get_active_track().get_instrument().next()),
+                Some(Instrument::Noise | Instrument::Sample(_)) => None,
+                Some(instrument) => Some(instrument.next()),
+            };
+            println!("Stroke instrument: {}.", params.instrument.as_ref().map_or(String::from("track"), |instrument| format!("{:?}", instrument)));
+            self.drawing_system.set_stroke_params(params);
+        } else if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::I) {
+            let track = self.get_active_track_mut();
+            track.set_instrument(track.get_instrument().next());
+            println!("Track {} instrument: {:?}.", self.active_track + 1, self.get_active_track().get_instrument());
+        }
+        if window.is_key_pressed(Key::LeftControl) && window.is_key_just_pressed(Key::V) {
+            const AMPLITUDES: [Option<f32>; 6] = [None, Some(0.1), Some(0.2), Some(0.5), Some(0.75), Some(1.0)];
+
+            let mut params = self.drawing_system.get_stroke_params().clone();
+            let i = AMPLITUDES.iter().position(|amplitude| *amplitude == params.amplitude).map_or(0, |i| i + 1);
+            params.amplitude = AMPLITUDES[i % AMPLITUDES.len()];
+            println!("Stroke amplitude: {}.", params.amplitude.map_or(String::from("default"), |amplitude| amplitude.to_string()));
+            self.drawing_system.set_stroke_params(params);
+        }
+        if window.is_key_pressed(Key::LeftControl) && (window.is_key_just_pressed(Key::LeftBracket) || window.is_key_just_pressed(Key::RightBracket)) {
+            // Shift steps by a semitone instead of a few cents.
+            let step = if window.is_key_pressed(Key::LeftShift) { 100.0 } else { 5.0 };
+            let step = if window.is_key_pressed(Key::RightBracket) { step } else { -step };
+
+            let track = self.get_active_track_mut();
+            track.set_detune_cents(track.get_detune_cents() + step);
+            println!("Track {} detune: {} cents.", self.active_track + 1, self.get_active_track().get_detune_cents());
+        }
     }
     fn update_player(&mut self) {
+        self.update_osc();
         if self.playing {
             if self.player_timer.elapsed() >= self.player_duration {
                 self.playing = false;
@@ -237,21 +940,127 @@ impl Timeline {
             self.player_timer = Instant::now();
         }
     }
+    /// Returns whether the playhead is being dragged, in which case nothing should be drawn.
+    fn update_playhead(&mut self, window: &Window) -> bool {
+        const HIT_RADIUS_PIXELS: f32 = 6.0;
+
+        if window.is_mouse_button_just_pressed(MouseButton::Left) {
+            let playhead_x = (self.get_play_beat() - self.view.offset.x) / self.view.scale.x * window.get_width() as f32;
+            self.dragging_playhead = (window.get_mouse_x() - playhead_x).abs() <= HIT_RADIUS_PIXELS;
+        }
+        if !window.is_mouse_button_pressed(MouseButton::Left) {
+            self.dragging_playhead = false;
+        }
+
+        if self.dragging_playhead {
+            self.playhead_beat = self.view.get_cursor_position(window).x.max(0.0);
+        }
+        self.dragging_playhead
+    }
     fn update_drawing_system(&mut self, window: &Window) {
-        self.drawing_system.update(window, &self.view, &mut self.tone_system, &mut self.record_system);
+        let view = if self.editing_automation { self.view.automation() } else { self.view };
+        let track = self.composition.get_track_mut(self.active_track);
+        let record_system = if self.editing_automation { track.get_automation_mut() } else { track.get_record_system_mut() };
+        self.drawing_system.update(window, &view, record_system);
     }
-    fn update_view(&mut self, window: &Window) {
-        const VIEW_SHARPNESS: f32 = 36.0;
+    fn update_hover(&mut self, window: &Window, dragging_playhead: bool) {
+        const CROSSHAIR_PIXELS: f32 = 8.0;
+
+        let cursor = self.view.get_cursor_position(window);
+        let is_drawing = window.is_mouse_button_pressed(MouseButton::Left) || dragging_playhead;
+        let is_on_timeline = !window.is_minimized() && cursor.x.is_finite() && cursor.y.is_finite() && cursor.x >= 0.0;
+
+        self.hover = (!is_drawing && is_on_timeline).then(|| {
+            (cursor, Vector2::new(CROSSHAIR_PIXELS / window.get_width() as f32 * 2.0, CROSSHAIR_PIXELS / window.get_height() as f32 * 2.0))
+        });
+    }
+    /// Shows what would be drawn under the cursor, with the scale snapping and tuning [Composition::render_audio] uses.
+    fn update_readout(&mut self, window: &Window) {
+        let texts = match self.hover {
+            Some((position, _)) => {
+                let pitch = self.composition.get_pitch_at(position.y);
+                let note = (pitch.round() + tuning::MIDI_NOTE_PITCH_ZERO as f32).clamp(0.0, 127.0) as u8;
+                let text = format!(
+                    "{}  {:.1} HZ  BEAT {:.2}",
+                    tuning::midi_note_name(note),
+                    self.composition.get_frequency(pitch),
+                    position.x,
+                );
+
+                let x = window.get_width() as f32 - text.len() as f32 * Self::TEXT_ADVANCE - 8.0;
+                vec![(Vector2::new(x, 8.0), text)]
+            }
+            None => Vec::new(),
+        };
+
+        self.readout.get_or_insert_with(TextMesh::new).set_texts(&texts, Self::TEXT_ADVANCE);
+    }
+    /// Labels every visible semitone, or only the Cs once the rows get thinner than the text.
+    fn update_note_labels(&mut self, window: &Window) {
+        self.screen_size = Vector2::new(window.get_width() as f32, window.get_height() as f32);
 
-        const SCALE_SPEED: f32 = 0.1;
-        const SCROLL_SPEED_X: f32 = 0.025;
-        const SCROLL_SPEED_Y: f32 = 0.015;
+        let semitone_pixels = self.screen_size.y / self.view.scale.y;
+        let only_octaves = semitone_pixels < Self::TEXT_GLYPH_SIZE.y + 2.0;
 
-        const SCALE_X_MIN: f32 = 0.25;
-        const SCALE_X_MAX: f32 = 400.0;
+        let first_note = (self.view.offset.y + tuning::MIDI_NOTE_PITCH_ZERO as f32 - 0.5).ceil().max(0.0);
+        let last_note = (self.view.offset.y + self.view.scale.y + tuning::MIDI_NOTE_PITCH_ZERO as f32 + 0.5).floor().min(127.0);
 
-        const SCALE_Y_MIN: f32 = 6.0;
-        const SCALE_Y_MAX: f32 = 48.0;
+        let mut labels = Vec::new();
+        if first_note <= last_note {
+            for note in first_note as u8..=last_note as u8 {
+                if only_octaves && note % 12 != 0 {
+                    continue;
+                }
+
+                let y = (tuning::y_from_midi_note(note) - self.view.offset.y) / self.view.scale.y * self.screen_size.y;
+                labels.push((Vector2::new(4.0, y - Self::TEXT_GLYPH_SIZE.y * 0.5), tuning::midi_note_name(note)));
+            }
+        }
+
+        self.note_labels
+            .get_or_insert_with(TextMesh::new)
+            .set_texts(&labels, Self::TEXT_ADVANCE);
+    }
+    /// Picks the label spacing from a beat, a bar or a power of two bars, the closest that keeps labels from overlapping.
+    /// Seconds are the played time, with swing, at the current bpm.
+    fn update_ruler(&mut self) {
+        const LABEL_GAP_PIXELS: f32 = 16.0;
+
+        let (_, beat_value) = self.time_signature;
+        let beat_beats = 4.0 / beat_value as f32;
+        let bar_beats = self.get_bar_beats();
+        let beat_pixels = self.screen_size.x / self.view.scale.x;
+
+        let label = |beat: f32| {
+            let bar = (beat / bar_beats + 1e-4).floor();
+            let beat_in_bar = ((beat - bar * bar_beats) / beat_beats + 1e-4).floor();
+            let secs = self.composition.get_played_beat(beat) / self.composition.get_bpm() * 60.0;
+            format!("{}:{} {:.2}S", bar as u32 + 1, beat_in_bar as u32 + 1, secs)
+        };
+        // The label at the right edge is the widest on screen.
+        let label_pixels = label(self.view.offset.x + self.view.scale.x).len() as f32 * Self::TEXT_ADVANCE + LABEL_GAP_PIXELS;
+
+        let mut label_beats = if bar_beats > beat_beats { beat_beats } else { bar_beats };
+        while label_beats * beat_pixels < label_pixels && label_beats < 1e6 {
+            label_beats = if label_beats < bar_beats { bar_beats } else { label_beats * 2.0 };
+        }
+        self.ruler_label_beats = label_beats;
+
+        let y = self.screen_size.y - (Self::RULER_HEIGHT + Self::TEXT_GLYPH_SIZE.y) * 0.5;
+        let mut labels = Vec::new();
+        let mut beat = (self.view.offset.x / label_beats).ceil() * label_beats;
+        while beat <= self.view.offset.x + self.view.scale.x {
+            let x = (beat - self.view.offset.x) / self.view.scale.x * self.screen_size.x;
+            labels.push((Vector2::new(x + 4.0, y), label(beat)));
+            beat += label_beats;
+        }
+
+        self.ruler_labels
+            .get_or_insert_with(TextMesh::new)
+            .set_texts(&labels, Self::TEXT_ADVANCE);
+    }
+    fn update_view(&mut self, window: &Window) {
+        let config = self.view_config;
 
         let is_ctrl_pressed = window.is_key_pressed(Key::LeftControl) || window.is_key_pressed(Key::RightControl);
         let is_alt_pressed = window.is_key_pressed(Key::LeftAlt) || window.is_key_pressed(Key::RightAlt);
@@ -259,25 +1068,25 @@ impl Timeline {
         if is_alt_pressed && !is_ctrl_pressed {
             let last_scale = self.raw_view.scale.y;
             
-            self.raw_view.scale.y -= window.get_scroll_dy() * SCALE_SPEED * self.raw_view.scale.y;
-            self.raw_view.scale.y = self.raw_view.scale.y.clamp(SCALE_Y_MIN, SCALE_Y_MAX);
+            self.raw_view.scale.y -= window.get_scroll_dy() * config.scale_speed * self.raw_view.scale.y;
+            self.raw_view.scale.y = self.raw_view.scale.y.clamp(config.scale_y_min, config.scale_y_max);
             self.raw_view.offset.y += (last_scale - self.raw_view.scale.y) * (1.0 - window.get_mouse_y() / window.get_height() as f32);
         }
         if is_ctrl_pressed && !is_alt_pressed {
             let last_scale = self.raw_view.scale.x;
 
-            self.raw_view.scale.x -= window.get_scroll_dy() * SCALE_SPEED * self.raw_view.scale.x;
-            self.raw_view.scale.x = self.raw_view.scale.x.clamp(SCALE_X_MIN, SCALE_X_MAX);
+            self.raw_view.scale.x -= window.get_scroll_dy() * config.scale_speed * self.raw_view.scale.x;
+            self.raw_view.scale.x = self.raw_view.scale.x.clamp(config.scale_x_min, config.scale_x_max);
             self.raw_view.offset.x += (last_scale - self.raw_view.scale.x) * window.get_mouse_x() / window.get_width() as f32;
         }
         
         if !is_ctrl_pressed && !is_alt_pressed {
             if window.is_key_pressed(Key::LeftShift) || window.is_key_pressed(Key::RightShift) {
-                self.raw_view.offset.x -= window.get_scroll_dy() * SCROLL_SPEED_X * self.raw_view.scale.x;
-                self.raw_view.offset.y -= window.get_scroll_dx() * SCROLL_SPEED_Y * self.raw_view.scale.y;
+                self.raw_view.offset.x -= window.get_scroll_dy() * config.scroll_speed_x * self.raw_view.scale.x;
+                self.raw_view.offset.y -= window.get_scroll_dx() * config.scroll_speed_y * self.raw_view.scale.y;
             } else {
-                self.raw_view.offset.y += window.get_scroll_dy() * SCROLL_SPEED_Y * self.raw_view.scale.y;
-                self.raw_view.offset.x -= window.get_scroll_dx() * SCROLL_SPEED_X * self.raw_view.scale.x;
+                self.raw_view.offset.y += window.get_scroll_dy() * config.scroll_speed_y * self.raw_view.scale.y;
+                self.raw_view.offset.x -= window.get_scroll_dx() * config.scroll_speed_x * self.raw_view.scale.x;
             }
         }
         if window.is_mouse_button_pressed(MouseButton::Middle) {
@@ -285,295 +1094,921 @@ impl Timeline {
             self.raw_view.offset.y += window.get_mouse_dy() / window.get_height() as f32 * self.raw_view.scale.y;
         }
 
+        // Stick drift below the dead zone shouldn't move the view.
+        const GAMEPAD_DEAD_ZONE: f32 = 0.2;
+        let stick = Vector2::new(window.get_gamepad_axis(GamepadAxis::AxisLeftX), window.get_gamepad_axis(GamepadAxis::AxisLeftY));
+        if stick.magnitude() > GAMEPAD_DEAD_ZONE {
+            let pan = config.gamepad_pan_speed * window.get_delta_secs();
+            self.raw_view.offset.x += stick.x * pan * self.raw_view.scale.x;
+            self.raw_view.offset.y -= stick.y * pan * self.raw_view.scale.y;
+        }
+
         self.raw_view.offset.x = f32::max(self.raw_view.offset.x, 0.0);
 
-        self.view.offset = self.view.offset.lerp(&self.raw_view.offset, (VIEW_SHARPNESS * window.get_delta_secs()).min(1.0));
-        self.view.scale = self.view.scale.lerp(&self.raw_view.scale, (VIEW_SHARPNESS * window.get_delta_secs()).min(1.0));
+        self.view.offset = self.view.offset.lerp(&self.raw_view.offset, (config.sharpness * window.get_delta_secs()).min(1.0));
+        self.view.scale = self.view.scale.lerp(&self.raw_view.scale, (config.sharpness * window.get_delta_secs()).min(1.0));
     }
 
     pub fn update(&mut self, window: &Window) {
+        self.update_tracks(window);
         self.update_record_system(window);
+        self.update_selection(window);
         self.update_player();
-        self.update_drawing_system(window);
+        let dragging_playhead = self.update_playhead(window);
+        if !dragging_playhead {
+            self.update_drawing_system(window);
+        }
         self.update_view(window);
+        self.update_hover(window, dragging_playhead);
+        self.update_readout(window);
+        self.update_note_labels(window);
+        self.update_ruler();
+
+        let mut is_edited = false;
+        for (i, (tone_system, automation_system)) in self.tone_systems.iter_mut().zip(&mut self.automation_systems).enumerate() {
+            let track = self.composition.get_track_mut(i);
+            if let Some(dirty_from) = track.get_record_system_mut().take_dirty_from() {
+                tone_system.mark_dirty(dirty_from);
+                is_edited = true;
+            }
+            if let Some(dirty_from) = track.get_automation_mut().take_dirty_from() {
+                automation_system.mark_dirty(dirty_from);
+                is_edited = true;
+            }
+        }
+        if is_edited {
+            self.mark_changed();
+        }
+        for ((tone_system, automation_system), track) in self.tone_systems.iter_mut().zip(&mut self.automation_systems).zip(self.composition.get_tracks()) {
+            tone_system.update(track.get_record_system());
+            automation_system.update(track.get_automation());
+        }
+        self.poll_waveform_preview();
+        if self.waveform_dirty && self.waveform_worker.is_none() && !window.is_mouse_button_pressed(MouseButton::Left) {
+            self.update_waveform_preview();
+        }
+    }
 
-        self.tone_system.update(&self.record_system);
+    fn mark_changed(&mut self) {
+        self.waveform_dirty = true;
+        self.revision += 1;
+    }
+    /// Counts up with every change, see [Self::has_unsaved_changes].
+    pub const fn get_revision(&self) -> u64 {
+        self.revision
+    }
+    pub const fn has_unsaved_changes(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+    /// Call after the project was saved.
+    pub fn mark_saved(&mut self) {
+        self.saved_revision = self.revision;
     }
+
+    pub const fn get_composition(&self) -> &Composition {
+        &self.composition
+    }
+    /// Counts as a change, like [Self::get_active_track_mut].
+    pub fn get_composition_mut(&mut self) -> &mut Composition {
+        self.mark_changed();
+        &mut self.composition
+    }
+
+    pub fn get_active_track(&self) -> &Track {
+        &self.composition.get_tracks()[self.active_track]
+    }
+    pub fn get_active_track_mut(&mut self) -> &mut Track {
+        self.mark_changed();
+        self.composition.get_track_mut(self.active_track)
+    }
+    /// Adds an empty track and makes it the active one.
+    pub fn add_track(&mut self) -> &mut Track {
+        self.composition.add_track();
+        self.tone_systems.push(ToneSystem::default());
+        self.automation_systems.push(ToneSystem::default());
+        self.set_active_track(self.composition.get_tracks().len() - 1);
+        self.get_active_track_mut()
+    }
+    /// Removes a track and keeps it for [Self::restore_deleted_track]. The only track is cleared instead.
+    pub fn delete_track(&mut self, index: usize) {
+        let track_count = self.composition.get_tracks().len();
+        if index >= track_count {
+            return;
+        }
+
+        let track = self.composition.remove_track(index);
+        if track_count == 1 {
+            self.tone_systems[0] = ToneSystem::default();
+            self.automation_systems[0] = ToneSystem::default();
+        } else {
+            self.tone_systems.remove(index);
+            self.automation_systems.remove(index);
+        }
+        self.deleted_tracks.push(DeletedTrack { index, track, was_only: track_count == 1 });
+        self.mark_changed();
+
+        println!("Deleted track {}, Ctrl+Shift+T brings it back.", index + 1);
+        let active_track = if self.active_track > index { self.active_track - 1 } else { self.active_track };
+        self.set_active_track(active_track);
+    }
+    /// Puts the last deleted track back where it was and makes it the active one.
+    /// If it was cleared as the only track, it replaces the empty track left in its place as long as nothing was drawn there.
+    pub fn restore_deleted_track(&mut self) {
+        let Some(DeletedTrack { index, track, was_only }) = self.deleted_tracks.pop() else {
+            println!("No deleted tracks to restore.");
+            return;
+        };
+
+        let tracks = self.composition.get_tracks();
+        let is_placeholder = |track: &Track| track.get_record_system().get_line_count() == 0 && track.get_automation().get_line_count() == 0;
+        if was_only && tracks.len() == 1 && is_placeholder(&tracks[0]) {
+            self.composition.insert_track(0, track);
+            self.composition.remove_track(1);
+        } else {
+            self.composition.insert_track(index, track);
+        }
+
+        let index = index.min(self.composition.get_tracks().len() - 1);
+        let mut tone_system = ToneSystem::default();
+        let mut automation_system = ToneSystem::default();
+        tone_system.mark_dirty(0);
+        automation_system.mark_dirty(0);
+        if self.tone_systems.len() < self.composition.get_tracks().len() {
+            self.tone_systems.insert(index, tone_system);
+            self.automation_systems.insert(index, automation_system);
+        } else {
+            self.tone_systems[index] = tone_system;
+            self.automation_systems[index] = automation_system;
+        }
+        self.mark_changed();
+
+        self.set_active_track(index);
+    }
+    pub const fn get_bitcrusher(&self) -> &Bitcrusher {
+        self.composition.get_bitcrusher()
+    }
+    /// Full bits without any hold plays and exports the mix unchanged.
+    pub fn set_bitcrusher(&mut self, bitcrusher: Bitcrusher) {
+        self.composition.set_bitcrusher(bitcrusher);
+        self.mark_changed();
+    }
+    pub const fn get_delay(&self) -> &Delay {
+        self.composition.get_delay()
+    }
+    /// Wet 0 plays and exports the dry mix unchanged.
+    pub fn set_delay(&mut self, delay: Delay) {
+        self.composition.set_delay(delay);
+        self.mark_changed();
+    }
+    pub const fn get_reverb(&self) -> &Reverb {
+        self.composition.get_reverb()
+    }
+    /// Wet 0 plays and exports the dry mix unchanged.
+    pub fn set_reverb(&mut self, reverb: Reverb) {
+        self.composition.set_reverb(reverb);
+        self.mark_changed();
+    }
+    pub const fn get_tremolo(&self) -> &Tremolo {
+        self.composition.get_tremolo()
+    }
+    /// Depth 0 plays and exports the mix unchanged.
+    pub fn set_tremolo(&mut self, tremolo: Tremolo) {
+        self.composition.set_tremolo(tremolo);
+        self.mark_changed();
+    }
+    pub const fn get_active_track_index(&self) -> usize {
+        self.active_track
+    }
+    /// Record of the active track the arrow keys move, Escape clears it before it stops playback.  
+    /// `None` once the selected record is gone, even before [Self::update] notices.
+    pub fn get_selected_record(&self) -> Option<usize> {
+        let track = self.get_active_track();
+        let record_system = if self.editing_automation { track.get_automation() } else { track.get_record_system() };
+        self.drawing_system
+            .get_selected_record()
+            .filter(|index| record_system.records().nth(*index).is_some_and(|lines| !lines.is_empty()))
+    }
+    pub fn set_active_track(&mut self, index: usize) {
+        let tracks = self.composition.get_tracks();
+        self.active_track = index.min(tracks.len() - 1);
+        self.drawing_system.set_selected_record(None);
+        println!("Active track: {}/{} ({:?}).", self.active_track + 1, tracks.len(), tracks[self.active_track].get_instrument());
+    }
+
+    pub const fn get_count_in_bars(&self) -> u32 {
+        self.count_in_bars
+    }
+    /// Bars of metronome clicks played before the song, never part of the rendered audio.
+    pub fn set_count_in_bars(&mut self, count_in_bars: u32) {
+        self.count_in_bars = count_in_bars;
+        self.mark_changed();
+    }
+
+    pub const fn get_time_signature(&self) -> (u32, u32) {
+        self.time_signature
+    }
+    /// At least one beat per bar, the note value is rounded up to a power of two between whole and 32nd notes.
+    pub fn set_time_signature(&mut self, (beats_per_bar, beat_value): (u32, u32)) {
+        self.time_signature = (beats_per_bar.max(1), beat_value.clamp(1, 32).next_power_of_two());
+        self.set_swing(self.composition.get_swing());
+    }
+    pub fn get_swing(&self) -> f32 {
+        self.composition.get_swing()
+    }
+    /// Swings pairs of the time signature's half beats, eighth notes in 4/4 and sixteenths in 6/8.
+    /// See [Composition::set_swing], the playline follows the swung timing.
+    pub fn set_swing(&mut self, swing: f32) {
+        let pair_beats = 4.0 / self.time_signature.1 as f32;
+        self.composition.set_swing(swing, pair_beats);
+        self.mark_changed();
+    }
+    /// Length of a bar in quarter note beats, the unit of the timeline's x axis.
+    pub fn get_bar_beats(&self) -> f32 {
+        let (beats_per_bar, beat_value) = self.time_signature;
+        beats_per_bar as f32 * 4.0 / beat_value as f32
+    }
+
+    /// Metronome clicks for [Self::set_count_in_bars], the first beat of each bar is accented.
+    fn render_count_in(&self) -> Option<SamplesBuffer<f32>> {
+        const CLICK_SECS: f32 = 0.03;
+
+        if self.count_in_bars == 0 {
+            return None;
+        }
+
+        let (beats_per_bar, beat_value) = self.time_signature;
+        let beats = self.count_in_bars * beats_per_bar;
+        // Clicks follow the signature's beat, the bpm counts quarter notes.
+        let beat_samples = (60.0 / self.composition.get_bpm() * 4.0 / beat_value as f32 * self.composition.get_sample_rate() as f32) as usize;
+        let click_samples = ((CLICK_SECS * self.composition.get_sample_rate() as f32) as usize).min(beat_samples);
+
+        let mut samples = vec![0.0; beats as usize * beat_samples];
+        for beat in 0..beats {
+            let frequency = if beat % beats_per_bar == 0 { 1760.0 } else { 1320.0 };
+            let start = beat as usize * beat_samples;
+
+            for (i, sample) in samples[start..start + click_samples].iter_mut().enumerate() {
+                let time = i as f32 / self.composition.get_sample_rate() as f32;
+                let envelope = 1.0 - i as f32 / click_samples as f32;
+                *sample = f32::sin(f32::consts::TAU * frequency * time) * envelope * envelope * 0.5;
+            }
+        }
+
+        Some(SamplesBuffer::new(1, self.composition.get_sample_rate(), samples))
+    }
+
+    /// View currently drawn, it eases towards the one set by scrolling and zooming.
+    pub const fn get_view(&self) -> &View {
+        &self.view
+    }
+    /// Jumps straight to `view` without easing.
+    pub fn set_view(&mut self, view: View) {
+        let mut clamped = View::default();
+        clamped.set_offset(view.get_offset());
+        clamped.set_scale(self.view_config.clamp_scale(view.get_scale()));
+
+        self.raw_view = clamped;
+        self.view = clamped;
+    }
+
+    pub const fn get_view_config(&self) -> &ViewConfig {
+        &self.view_config
+    }
+    /// The view eases into the new zoom limits, a maximum below its minimum is raised to it.
+    pub fn set_view_config(&mut self, mut view_config: ViewConfig) {
+        view_config.scale_x_max = view_config.scale_x_max.max(view_config.scale_x_min);
+        view_config.scale_y_max = view_config.scale_y_max.max(view_config.scale_y_min);
+
+        self.view_config = view_config;
+        self.raw_view.set_scale(view_config.clamp_scale(self.raw_view.get_scale()));
+    }
+
+    pub const fn get_grid_config(&self) -> &GridConfig {
+        &self.grid_config
+    }
+    pub fn set_grid_config(&mut self, grid_config: GridConfig) {
+        self.grid_config = grid_config;
+    }
+
+    pub const fn get_tone_line_style(&self) -> &ToneLineStyle {
+        &self.tone_line_style
+    }
+    /// Keeps the width at least a pixel and the feather from going negative.
+    pub fn set_tone_line_style(&mut self, tone_line_style: ToneLineStyle) {
+        self.tone_line_style = ToneLineStyle {
+            width: tone_line_style.width.max(1.0),
+            feather: tone_line_style.feather.max(0.0),
+        };
+    }
+    pub const fn get_playline_style(&self) -> &PlaylineStyle {
+        &self.playline_style
+    }
+    /// The width is kept at least a pixel.
+    pub fn set_playline_style(&mut self, mut playline_style: PlaylineStyle) {
+        playline_style.width = playline_style.width.max(1.0);
+        self.playline_style = playline_style;
+    }
+
+    pub const fn get_scale_template(&self) -> &ScaleTemplate {
+        &ScaleTemplate::BUILT_IN[self.scale_template]
+    }
+    pub const fn is_template_shown(&self) -> bool {
+        self.show_template
+    }
+    /// Hidden, the background rows are all the same and only the bars stand out.
+    pub fn set_show_template(&mut self, show_template: bool) {
+        self.show_template = show_template;
+    }
+    /// Wraps around past the last [ScaleTemplate::BUILT_IN].
+    pub fn set_scale_template(&mut self, index: usize) {
+        self.scale_template = index % ScaleTemplate::BUILT_IN.len();
+    }
+
+    pub const fn is_highlighting_out_of_scale(&self) -> bool {
+        self.highlight_out_of_scale
+    }
+    /// Turn off for free microtonal drawing.
+    pub fn set_highlight_out_of_scale(&mut self, highlight_out_of_scale: bool) {
+        self.highlight_out_of_scale = highlight_out_of_scale;
+    }
+
+    pub const fn get_tone_color_ramp(&self) -> ToneColorRamp {
+        self.tone_color_ramp
+    }
+    pub fn set_tone_color_ramp(&mut self, tone_color_ramp: ToneColorRamp) {
+        self.tone_color_ramp = tone_color_ramp;
+    }
+
+    pub const fn get_playhead_beat(&self) -> f32 {
+        self.playhead_beat
+    }
+    /// Beat playback starts from.
+    pub fn set_playhead_beat(&mut self, beat: f32) {
+        self.playhead_beat = beat.max(0.0);
+    }
+    /// Beat under the playline, moving along while playing.
+    pub fn get_play_beat(&self) -> f32 {
+        if self.playing {
+            self.beat_after(self.playhead_beat, self.player_timer.elapsed())
+        } else {
+            self.playhead_beat
+        }
+    }
+
+    /// Drawn beat playing `elapsed` after the one drawn at `beat`, swing included.
+    fn beat_after(&self, beat: f32, elapsed: Duration) -> f32 {
+        let played_beat = self.composition.get_played_beat(beat) + elapsed.as_secs_f32() / 60.0 * self.composition.get_bpm();
+        self.composition.get_drawn_beat(played_beat)
+    }
+
+    pub const fn is_midi_recording(&self) -> bool {
+        self.midi_recording
+    }
+    /// While recording, [Self::handle_midi] turns every held note into a flat record on the active track.
+    pub fn set_midi_recording(&mut self, midi_recording: bool) {
+        if !midi_recording {
+            let notes: Vec<u8> = self.midi_notes.keys().copied().collect();
+            for note in notes {
+                self.handle_midi(MidiMessage::NoteOff { note });
+            }
+        }
+        self.midi_recording = midi_recording;
+    }
+    /// A note starts at the playline and advances with the bpm while held, whether playing or not.
+    pub fn handle_midi(&mut self, message: MidiMessage) {
+        if !self.midi_recording {
+            return;
+        }
+
+        match message {
+            MidiMessage::NoteOn { note, velocity } => {
+                let loudness = velocity as f32 / 127.0;
+                self.midi_notes.insert(note, MidiNote { start_beat: self.get_play_beat(), started: Instant::now(), loudness });
+            }
+            MidiMessage::NoteOff { note } => {
+                let Some(midi_note) = self.midi_notes.remove(&note) else { return; };
+                let y = tuning::y_from_midi_note(note);
+                let end_beat = self.beat_after(midi_note.start_beat, midi_note.started.elapsed());
+
+                // The key velocity scales the stroke loudness, the line has no drawing speed for the falloff to use.
+                let mut params = self.drawing_system.get_stroke_params().clone();
+                params.amplitude = Some(params.amplitude.unwrap_or(Composition::BASE_AMPLITUDE) * midi_note.loudness);
+                self.get_active_track_mut().push_record(vec![Line {
+                    start: Point2::new(midi_note.start_beat, y),
+                    end: Point2::new(end_beat, y),
+                    velocity: 0.0,
+                }], params);
+            }
+        }
+    }
+
+    /// Sends OSC `/note` messages during playback instead of, or on top of, the built-in synth.  
+    /// Every record is a voice, its tone is sent whenever it changes, timed like the playline.
+    pub fn set_osc_output(&mut self, sender: Option<Arc<OscSender>>, replaces_audio: bool) {
+        self.release_osc_voices();
+        self.osc_sender = sender;
+        self.osc_replaces_audio = replaces_audio;
+    }
+    pub const fn is_osc_enabled(&self) -> bool {
+        self.osc_enabled
+    }
+    /// Mutes the OSC output without dropping it, the built-in synth plays while it's off.  
+    /// Takes effect on the next [Self::play] for the audio, right away for the messages.
+    pub fn set_osc_enabled(&mut self, osc_enabled: bool) {
+        if !osc_enabled {
+            self.silence_osc_voices();
+        }
+        self.osc_enabled = osc_enabled;
+    }
+    fn update_osc(&mut self) {
+        let Some(sender) = &self.osc_sender else { return; };
+        if !self.playing {
+            return;
+        }
+
+        // Events keep being consumed while the output is off, so turning it back on picks up where playback is.
+        let elapsed = self.player_timer.elapsed();
+        while let Some(event) = self.osc_events.get(self.osc_sent).filter(|event| event.time <= elapsed) {
+            if self.osc_enabled {
+                sender.send("/note", &[OscArgument::Int(event.voice), OscArgument::Float(event.frequency), OscArgument::Float(event.amplitude)]);
+            }
+            self.osc_sent += 1;
+        }
+    }
+    fn silence_osc_voices(&self) {
+        let Some(sender) = &self.osc_sender else { return; };
+        if self.osc_enabled && self.osc_sent < self.osc_events.len() {
+            for voice in 0..self.osc_voices {
+                sender.send("/note", &[OscArgument::Int(voice as i32), OscArgument::Float(0.0), OscArgument::Float(0.0)]);
+            }
+        }
+    }
+    fn release_osc_voices(&mut self) {
+        self.silence_osc_voices();
+
+        self.osc_events.clear();
+        self.osc_sent = 0;
+    }
+    /// Samples every voice of `audio` at a fixed step, keeping only the changes after `offset`.
+    fn schedule_osc(&mut self, audio: &PlayerSource, offset: Duration) {
+        const STEP_SECS: f32 = 0.005;
+
+        self.release_osc_voices();
+        if self.osc_sender.is_none() {
+            return;
+        }
+
+        let step = (STEP_SECS * self.composition.get_sample_rate() as f32) as usize;
+        let first = (offset.as_secs_f32() * self.composition.get_sample_rate() as f32) as usize;
+        let time = |i: usize| Duration::from_secs_f32((i - first) as f32 / self.composition.get_sample_rate() as f32);
+
+        for (voice, tones) in audio.voices().enumerate() {
+            let mut last = (0.0, 0.0);
+            for i in (first..tones.len()).step_by(step) {
+                let tone = &tones[i];
+                let current = if tone.amplitude > 0.0 { (tone.frequency, tone.amplitude) } else { (0.0, 0.0) };
+
+                if current != last {
+                    self.osc_events.push(OscNoteEvent { time: time(i), voice: voice as i32, frequency: current.0, amplitude: current.1 });
+                    last = current;
+                }
+            }
+            if last.1 > 0.0 {
+                let end = tones.len().max(first);
+                self.osc_events.push(OscNoteEvent { time: time(end), voice: voice as i32, frequency: 0.0, amplitude: 0.0 });
+            }
+        }
+
+        self.osc_events.sort_by_key(|event| event.time);
+        self.osc_voices = audio.voices().count();
+    }
+
+    /// Writes the song as a notation score, see [musicxml::export] for what gets lost on the way.
+    pub fn export_musicxml(&self, path: &Path) -> Result<(), String> {
+        musicxml::export(&self.composition, self.time_signature, path)
+    }
+    /// The notes the song plays, with the current quantize, scale and tuning settings. See [note_list::notes].
+    pub fn notes(&self) -> Vec<Note> {
+        note_list::notes(&self.composition)
+    }
+    /// The whole song as the player hears it, without clamping or conversion, see [Composition::render_samples_f32].
+    pub fn render_samples_f32(&self) -> Vec<f32> {
+        self.composition.render_samples_f32()
+    }
+
     pub fn play(&mut self, sink: &Sink) {
-        let audio = self.render_audio();
+        let mut audio = self.composition.render_audio().with_fade_in(true);
+        let offset = Duration::from_secs_f32(self.composition.get_played_beat(self.playhead_beat) / self.composition.get_bpm() * 60.0);
+        let duration = audio.total_duration();
+        self.schedule_osc(&audio, offset);
 
         sink.stop();
-        sink.append(self.render_audio());
+        self.panic_fade = Arc::default();
+
+        let mut count_in_duration = Duration::ZERO;
+        if let Some(count_in) = self.render_count_in() {
+            count_in_duration = count_in.total_duration().unwrap_or_default();
+            sink.append(PanicFade::new(count_in, self.panic_fade.clone()));
+        }
+        if !(self.osc_sender.is_some() && self.osc_enabled && self.osc_replaces_audio) {
+            audio.skip_samples((offset.as_secs_f32() * self.composition.get_sample_rate() as f32).round() as usize);
+            sink.append(PanicFade::new(audio, self.panic_fade.clone()));
+        }
 
         self.playing = true;
-        self.player_timer = Instant::now();
+        // The playline waits at the start until the count-in is over.
+        self.player_timer = Instant::now() + count_in_duration;
 
-        if let Some(duration) = audio.total_duration() {
-            self.player_duration = duration;
+        if let Some(duration) = duration {
+            self.player_duration = duration.saturating_sub(offset);
         }
     }
 
+    pub const fn is_playing(&self) -> bool {
+        self.playing
+    }
+    /// Stops the audio in `sink` and any OSC voices, the playline goes back to the playhead.
+    pub fn stop(&mut self, sink: &Sink) {
+        sink.stop();
+        self.end_playback();
+    }
+    /// All notes off, whether or not anything is playing. Unlike [Self::stop] the audio fades out over
+    /// [Self::PANIC_FADE_SECS] instead of being cut mid-wave, on the audio thread so the frame isn't held up.
+    pub fn panic(&mut self) {
+        self.panic_fade.store(true, Ordering::Relaxed);
+        self.end_playback();
+    }
+    fn end_playback(&mut self) {
+        self.release_osc_voices();
+
+        self.playing = false;
+        self.player_duration = Duration::ZERO;
+        self.player_timer = Instant::now();
+    }
+
     pub fn draw(&self, resources: &Resources) {
-        self.render_system.draw_timeline(resources, &self.view, self.player_bpm);
-        self.render_system.draw_timeline_tones(resources, &self.tone_system, &self.view);
+        self.render_system.draw_timeline(resources, &self.view, self.composition.get_bpm(), self.get_bar_beats(), self.show_template.then_some(self.scale_template));
+        if let Some(loop_region) = self.composition.get_loop_region() {
+            self.render_system.draw_loop_region(resources, loop_region, self.playline_style.loop_region_color, &self.view);
+        }
+        self.render_system.draw_beat_grid(resources, &self.grid_config, self.get_bar_beats(), &self.view);
+        self.render_system.draw_semitone_grid(resources, &self.grid_config, &self.view);
+        for tone_system in &self.tone_systems {
+            let highlight_outside = self.highlight_out_of_scale.then(|| self.get_scale_template());
+            self.render_system.draw_timeline_tones(resources, tone_system, self.tone_color_ramp, highlight_outside, &self.tone_line_style, self.screen_size, &self.view);
+        }
+        // Only the active track's curve, faint unless it's the one being drawn.
+        let automation_color = if self.editing_automation { Vector4::new(1.0, 0.85, 0.3, 0.9) } else { Vector4::new(1.0, 0.85, 0.3, 0.25) };
+        self.render_system.draw_automation(
+            resources,
+            &self.automation_systems[self.active_track],
+            automation_color,
+            &self.tone_line_style,
+            self.screen_size,
+            &self.view.automation(),
+        );
+
+        if let Some(waveform_preview) = &self.waveform_preview {
+            self.render_system.draw_waveform_preview(resources, waveform_preview, &self.view);
+        }
+        let tick_beats = (4.0 / self.time_signature.1 as f32).min(self.ruler_label_beats);
+        self.render_system.draw_ruler(resources, self.ruler_label_beats, tick_beats, Self::RULER_HEIGHT, self.screen_size.y, &self.view);
+        self.render_system.draw_playline(resources, self.get_play_beat(), &self.playline_style, self.screen_size.x, &self.view);
 
-        if self.playing {
-            self.render_system.draw_playline(resources, self.player_timer.elapsed().as_secs_f32() / 60.0 * self.player_bpm, &self.view);
+        if let Some(ruler_labels) = &self.ruler_labels {
+            self.render_system.draw_text(resources, ruler_labels, self.screen_size, Self::TEXT_GLYPH_SIZE, Vector4::new(1.0, 1.0, 1.0, 0.8));
+        }
+        if let Some(note_labels) = &self.note_labels {
+            self.render_system.draw_text(resources, note_labels, self.screen_size, Self::TEXT_GLYPH_SIZE, Vector4::new(1.0, 1.0, 1.0, 0.6));
+        }
+        if let Some(readout) = &self.readout {
+            self.render_system.draw_text(resources, readout, self.screen_size, Self::TEXT_GLYPH_SIZE, Vector4::new(1.0, 1.0, 1.0, 0.9));
         }
-    }
 
-    pub fn render_audio(&self) -> PlayerSource {
-        const SAMPLE_RATE: usize = 44100;
+        if let Some((position, half_size)) = self.hover {
+            self.render_system.draw_cursor(resources, position, half_size, Vector4::new(1.0, 1.0, 1.0, 0.5), &self.view);
 
-        let mut tones_samples = Vec::new();
-        let length = self.record_system.history
-            .iter()
-            .flatten()
-            .max_by(|a, b| {
-                if f32::max(a.start.x, a.end.x) > f32::max(b.start.x, b.end.x) {
-                    Ordering::Greater
-                } else {
-                    Ordering::Less
-                }
-            });
-        
-        if let Some(length) = length {
-            let length = (f32::max(length.start.x, length.end.x) / (self.player_bpm / 60.0) * SAMPLE_RATE as f32) as usize + 1;
-
-            for lines in &self.record_system.history {
-                let mut samples = vec![Tone { frequency: 0.0, amplitude: 0.0 }; length];
-                for line in lines {
-                    let start = Point2::new(line.start.x / (self.player_bpm / 60.0), line.start.y);
-                    let end = Point2::new(line.end.x / (self.player_bpm / 60.0), line.end.y);
-
-                    let min = if start.x < end.x { start } else { end };
-                    let max = if start.x > end.x { start } else { end };
-                    
-                    for (i, sample) in samples
-                            .iter_mut()
-                            .enumerate()
-                            .skip((min.x * SAMPLE_RATE as f32) as usize)
-                            .take(((max.x - min.x) * SAMPLE_RATE as f32) as usize + 1) {
-                        let value = (min.y + (max.y - min.y) * (i as f32 / SAMPLE_RATE as f32 - min.x)) + 0.5;
-                        let frequency = 440.0 * f32::powf(2.0, (value + 3.0) / 12.0);
-                        let amplitude = 0.33;
-    
-                        *sample = Tone { frequency, amplitude };
-                    }
-                }
-    
-                tones_samples.push(ToneSamples::new(samples.into_boxed_slice()));
+            // Where the point would end up after quantizing and scale snapping, only when that's somewhere else.
+            let snapped = self.composition.get_snapped_point(position);
+            if snapped != position {
+                self.render_system.draw_cursor(resources, snapped, half_size * 0.5, Vector4::new(1.0, 0.8, 0.2, 0.35), &self.view);
             }
         }
+    }
+
+    /// Starts building the peak envelope drawn under the timeline if the rendered audio changed since the last build.  
+    /// Only edited records are synthesized here, the mixing and the peaks run on a worker, see [Self::poll_waveform_preview].
+    fn update_waveform_preview(&mut self) {
+        const BUCKET_SECS: f32 = 0.01;
 
-        PlayerSource::new(tones_samples.into_boxed_slice(), SAMPLE_RATE as u32)
+        self.waveform_dirty = false;
+
+        let song_key = self.composition.song_key();
+        if self.waveform_key == Some(song_key) {
+            return;
+        }
+        self.waveform_key = Some(song_key);
+
+        let audio = self.composition.render_audio();
+        let sample_rate = self.composition.get_sample_rate() as f32;
+        let bucket_samples = ((BUCKET_SECS * sample_rate) as usize).max(1);
+        let bucket_beats = bucket_samples as f32 / sample_rate / 60.0 * self.composition.get_bpm();
+
+        let worker = thread::spawn(move || waveform_peaks(audio, bucket_samples));
+        self.waveform_worker = Some((bucket_beats, worker));
+    }
+    /// Turns the peaks of a finished [Self::update_waveform_preview] worker into the mesh, GL only works on this thread.
+    fn poll_waveform_preview(&mut self) {
+        if !self.waveform_worker.as_ref().is_some_and(|(_, worker)| worker.is_finished()) {
+            return;
+        }
+        let Some((bucket_beats, worker)) = self.waveform_worker.take() else {
+            return;
+        };
+        let Ok(peaks) = worker.join() else {
+            eprintln!("Failed to build the waveform preview.");
+            return;
+        };
+
+        let mut vertices = Vec::with_capacity(peaks.len() * 4);
+        for (i, peak) in peaks.into_iter().enumerate() {
+            let x = self.composition.get_drawn_beat(i as f32 * bucket_beats);
+            vertices.extend_from_slice(&[x, -peak, x, peak]);
+        }
+
+        self.waveform_preview = if vertices.len() >= 8 {
+            Some(Mesh::new(&vertices, &Layout::default().next_attribute(Attribute::Vec2), gl::TRIANGLE_STRIP))
+        } else {
+            None
+        };
     }
+
+}
+/// The loudest sample of every `bucket_samples` long bucket, the last bucket may be shorter.
+fn waveform_peaks(audio: impl Iterator<Item = f32>, bucket_samples: usize) -> Vec<f32> {
+    let mut peaks = Vec::new();
+    let mut peak = 0.0f32;
+    let mut filled = 0;
+    for sample in audio {
+        peak = peak.max(sample.abs().min(1.0));
+        filled += 1;
+
+        if filled == bucket_samples {
+            peaks.push(peak);
+            peak = 0.0;
+            filled = 0;
+        }
+    }
+    if filled > 0 {
+        peaks.push(peak);
+    }
+    peaks
 }
 impl Default for Timeline {
     fn default() -> Self {
+        Self::new(TimelineConfig::default())
+    }
+}
+impl Timeline {
+    pub fn new(config: TimelineConfig) -> Self {
+        Self::with_composition(Composition::new(config))
+    }
+    /// Edits and plays `composition`, starting on its first track.
+    /// The lines already in `composition` are uploaded on the first update without counting as a change.
+    pub fn with_composition(mut composition: Composition) -> Self {
+        let mut tone_systems = Vec::new();
+        let mut automation_systems = Vec::new();
+        for i in 0..composition.get_tracks().len() {
+            let track = composition.get_track_mut(i);
+            track.get_record_system_mut().take_dirty_from();
+            track.get_automation_mut().take_dirty_from();
+
+            let mut tone_system = ToneSystem::default();
+            let mut automation_system = ToneSystem::default();
+            tone_system.mark_dirty(0);
+            automation_system.mark_dirty(0);
+            tone_systems.push(tone_system);
+            automation_systems.push(automation_system);
+        }
+
         Self {
-            record_system: RecordSystem::default(),
+            tone_systems,
+            automation_systems,
+            composition,
+            active_track: 0,
+            deleted_tracks: Vec::new(),
+            editing_automation: false,
+
             drawing_system: DrawingSystem::default(),
-            tone_system: ToneSystem::default(),
             render_system: RenderSystem,
 
             raw_view: View::default(),
             view: View::default(),
+            view_config: ViewConfig::default(),
+            grid_config: GridConfig::default(),
+            playline_style: PlaylineStyle::default(),
+            tone_line_style: ToneLineStyle::default(),
+            scale_template: 0,
+            show_template: true,
+            highlight_out_of_scale: true,
+            tone_color_ramp: ToneColorRamp::default(),
 
             playing: false,
             player_timer: Instant::now(),
             player_duration: Duration::ZERO,
-            player_bpm: 168.0,
+            panic_fade: Arc::default(),
+
+            time_signature: (4, 4),
+            count_in_bars: 0,
+
+            playhead_beat: 0.0,
+            dragging_playhead: false,
+
+            hover: None,
+            note_labels: None,
+            readout: None,
+            ruler_labels: None,
+            ruler_label_beats: 4.0,
+            screen_size: Vector2::new(1.0, 1.0),
+
+            waveform_preview: None,
+            waveform_key: None,
+            waveform_dirty: true,
+            waveform_worker: None,
+
+            midi_recording: false,
+            midi_notes: HashMap::new(),
+
+            osc_sender: None,
+            osc_replaces_audio: false,
+            osc_enabled: true,
+            osc_events: Vec::new(),
+            osc_sent: 0,
+            osc_voices: 0,
+
+            revision: 0,
+            saved_revision: 0,
         }
     }
 }
 
-struct LinesMesh {
-    vao: GLuint,
-    base_vbo: GLuint,
-    instance_vbo: GLuint,
-    num_lines: usize,
+/// Per-instance data of [LinesMesh], a [Line] and how steep it is.
+#[repr(C)]
+struct LineInstance {
+    line: Line,
+    /// Semitones per beat, without a sign.
+    slope: f32,
 }
-impl LinesMesh {
-    pub fn draw(&self) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::DrawArraysInstanced(gl::LINES, 0, 2, self.num_lines as GLint);
-        }
-    }
-    pub fn update(&mut self, lines: &[Line]) {
-        unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_vbo);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                std::mem::size_of_val(lines) as GLsizeiptr,
-                lines.as_ptr() as *const std::ffi::c_void,
-                gl::STATIC_DRAW,
-            );
-        }
+impl LineInstance {
+    fn new(line: &Line) -> Self {
+        let delta = line.end - line.start;
+        let slope = if delta.x != 0.0 { (delta.y / delta.x).abs() } else if delta.y != 0.0 { f32::MAX } else { 0.0 };
 
-        self.num_lines = lines.len();
+        Self { line: line.clone(), slope }
     }
 }
-impl Default for LinesMesh {
-    fn default() -> Self {
-        let mut vao = 0;
-        let mut base_vbo = 0;
-        let mut instance_vbo = 0;
-
-        unsafe {
-            gl::GenVertexArrays(1, &mut vao);
-            gl::BindVertexArray(vao);
-
-            gl::GenBuffers(1, &mut base_vbo);
-            gl::BindBuffer(gl::ARRAY_BUFFER, base_vbo);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                std::mem::size_of::<[f32; 2]>() as isize,
-                [0.0f32, 1.0f32].as_ptr() as *const _,
-                gl::STATIC_DRAW
-            );
-            gl::VertexAttribPointer(0, 1, gl::FLOAT, gl::FALSE, std::mem::size_of::<f32>() as i32, std::ptr::null());
-            gl::EnableVertexAttribArray(0);
-
-            gl::GenBuffers(1, &mut instance_vbo);
-            gl::BindBuffer(gl::ARRAY_BUFFER, instance_vbo);
-            gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, std::mem::size_of::<[f32; 4]>() as i32, std::ptr::null());
-            gl::VertexAttribDivisor(1, 1);
-            gl::EnableVertexAttribArray(1);
-        }
 
-        Self {
-            vao,
-            base_vbo,
-            instance_vbo,
-            num_lines: 0,
-        }
-    }
-}
-impl Drop for LinesMesh {
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteBuffers(1, &self.base_vbo);
-            gl::DeleteBuffers(1, &self.instance_vbo);
-            gl::DeleteVertexArrays(1, &self.vao);
-        }
-    }
+struct LinesMesh {
+    mesh: InstancedMesh,
 }
+impl LinesMesh {
+    pub fn with_capacity(capacity: usize) -> Self {
+        let base = Mesh::new(&[0.0f32, 1.0f32], &Layout::default().next_attribute(Attribute::Float), gl::LINES);
+        let instance_layout = Layout::default()
+            .next_attribute(Attribute::Vec4)
+            .next_attribute(Attribute::Float)
+            .next_attribute(Attribute::Float);
 
-#[repr(C)]
-#[derive(Clone)]
-pub struct Line {
-    pub start: Point2<f32>,
-    pub end: Point2<f32>,
-}
+        let mut mesh = InstancedMesh::new(base, &instance_layout, 2);
+        mesh.reserve_instances(capacity);
 
-#[derive(Clone)]
-struct Tone {
-    frequency: f32,
-    amplitude: f32,
-}
-struct ToneSamples {
-    samples: Box<[Tone]>,
-    i: usize,
-    time: f32,
-}
-impl ToneSamples {
-    pub fn new(samples: Box<[Tone]>) -> Self {
-        Self {
-            samples,
-            i: 0,
-            time: 0.0,
-        }
+        Self { mesh }
     }
 
-    pub fn last_amplitude(&self) -> f32 {
-        self.samples[self.i].amplitude
+    pub fn draw(&self) {
+        self.mesh.draw();
     }
-
-    fn get_sample(&self) -> f32 {
-        f32::sin(self.time)
+    /// Overwrites lines starting at `first` and drops the ones after them.
+    pub fn write(&mut self, first: usize, lines: &[Line]) {
+        let instances: Vec<LineInstance> = lines.iter().map(LineInstance::new).collect();
+        self.mesh.write_instances(first, &instances);
     }
 }
-impl Iterator for ToneSamples {
-    type Item = f32;
 
-    fn next(&mut self) -> Option<f32> {
-        let tone = &self.samples[self.i];
-        let sample = self.get_sample() * tone.amplitude;
-        
-        self.i += 1;
-        if self.i >= self.samples.len() {
-            self.i = self.samples.len() - 1;
-            return None;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        self.time += f32::consts::PI * 2.0 * tone.frequency / 44100.0;
-        Some(sample)
+    fn line(start: f32, end: f32, y: f32) -> Line {
+        Line { start: Point2::new(start, y), end: Point2::new(end, y), velocity: 0.0 }
+    }
+    /// Record counts of every track.
+    fn record_counts(timeline: &Timeline) -> Vec<usize> {
+        timeline.get_composition().get_tracks().iter().map(|track| track.get_record_system().get_record_count()).collect()
     }
-}
 
-pub struct PlayerSource {
-    sample_rate: u32,
-    tones_samples: Box<[ToneSamples]>,
-}
-impl PlayerSource {
-    fn new(tones_samples: Box<[ToneSamples]>, sample_rate: u32) -> Self {
-        Self {
-            sample_rate,
-            tones_samples,
+    #[test]
+    fn deleted_tracks_come_back_in_place() {
+        let mut composition = Composition::default();
+        composition.get_track_mut(0).push_record(vec![line(0.0, 1.0, 0.0)], StrokeParams::default());
+        for _ in 0..2 {
+            composition.add_track().push_record(vec![line(0.0, 1.0, 5.0), line(1.0, 2.0, 7.0)], StrokeParams::default());
         }
+        composition.get_track_mut(2).push_record(vec![line(3.0, 4.0, 0.0)], StrokeParams::default());
+        let mut timeline = Timeline::with_composition(composition);
+        timeline.set_active_track(2);
+        let rendered = timeline.get_composition().render_samples_f32();
+
+        timeline.delete_track(1);
+        assert_eq!(record_counts(&timeline), [1, 2]);
+        assert_eq!(timeline.get_active_track_index(), 1);
+
+        timeline.restore_deleted_track();
+        assert_eq!(record_counts(&timeline), [1, 1, 2]);
+        assert_eq!(timeline.get_active_track_index(), 1);
+        assert_eq!(timeline.get_composition().render_samples_f32(), rendered);
     }
-}
-impl Iterator for PlayerSource {
-    type Item = f32;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut sample = 0.0;
-        let mut accumulated_amplitude = 0.0;
-        
-        let mut no_more_samples = true;
-        for tone_samples in &mut self.tones_samples {
-            if let Some(next_sample) = tone_samples.next() {
-                no_more_samples = false;
 
-                sample += next_sample;
-                accumulated_amplitude += tone_samples.last_amplitude();
-            }
-        }
-        if no_more_samples {
-            return None;
-        }
-        if accumulated_amplitude > 0.0 {
-            sample /= f32::sqrt(accumulated_amplitude);
-        }
+    #[test]
+    fn deleting_the_only_track_clears_it() {
+        let mut composition = Composition::default();
+        composition.get_track_mut(0).push_record(vec![line(0.0, 1.0, 0.0)], StrokeParams::default());
+        let mut timeline = Timeline::with_composition(composition);
 
-        Some(sample)
-    }
-}
-impl Source for PlayerSource {
-    fn current_frame_len(&self) -> Option<usize> {
-        None
+        timeline.delete_track(0);
+        assert_eq!(record_counts(&timeline), [0]);
+        timeline.restore_deleted_track();
+        assert_eq!(record_counts(&timeline), [1]);
     }
 
-    fn channels(&self) -> u16 {
-        1
+    #[test]
+    fn panic_fades_out_what_plays() {
+        const SAMPLE_RATE: u32 = 1000;
+        let fade_samples = (Timeline::PANIC_FADE_SECS * SAMPLE_RATE as f32) as usize;
+        let fading = Arc::new(AtomicBool::new(false));
+        let mut playing = PanicFade::new(SamplesBuffer::new(1, SAMPLE_RATE, vec![1.0; 100]), fading.clone());
+        let mut queued = PanicFade::new(SamplesBuffer::new(1, SAMPLE_RATE, vec![1.0; 100]), fading.clone());
+
+        assert!((0..10).all(|_| playing.next() == Some(1.0)));
+        fading.store(true, Ordering::Relaxed);
+        let faded: Vec<f32> = playing.collect();
+        assert_eq!(faded.len(), fade_samples);
+        assert!(faded.windows(2).all(|pair| pair[1] < pair[0]));
+        assert_eq!(faded[fade_samples - 1], 0.0);
+
+        assert_eq!(queued.next(), None);
     }
 
-    fn sample_rate(&self) -> u32 {
-        self.sample_rate
+    #[test]
+    fn waveform_peaks_keep_the_last_partial_bucket() {
+        let audio = [0.1, -0.5, 0.2, 0.3, -2.0, 0.0, 0.4];
+        assert_eq!(waveform_peaks(audio.into_iter(), 3), [0.5, 1.0, 0.4]);
+        assert_eq!(waveform_peaks(audio[..6].iter().copied(), 3), [0.5, 1.0]);
+        assert_eq!(waveform_peaks(audio.into_iter(), 1).len(), audio.len());
+        assert!(waveform_peaks(std::iter::empty(), 3).is_empty());
     }
 
-    fn total_duration(&self) -> Option<Duration> {
-        let max_samples = self.tones_samples.iter()
-            .map(|tone_samples| tone_samples.samples.len())
-            .max()
-            .unwrap_or(0);
+    #[test]
+    fn midi_velocity_sets_the_stroke_amplitude() {
+        let mut timeline = Timeline::default();
+        timeline.set_midi_recording(true);
+        for (note, velocity) in [(60, 127), (64, 32)] {
+            timeline.handle_midi(MidiMessage::NoteOn { note, velocity });
+            timeline.handle_midi(MidiMessage::NoteOff { note });
+        }
 
-        if max_samples == 0 {
-            return None;
+        let record_system = timeline.get_composition().get_tracks()[0].get_record_system();
+        let amplitudes: Vec<Option<f32>> = record_system.get_record_params().iter().map(|params| params.amplitude).collect();
+        assert_eq!(amplitudes, [Some(Composition::BASE_AMPLITUDE), Some(Composition::BASE_AMPLITUDE * 32.0 / 127.0)]);
+        assert!(record_system.records().flatten().all(|line| line.velocity == 0.0));
+
+        // Notes held for a beat, the recorded ones are only as long as the test took.
+        let record_system = timeline.get_composition_mut().get_track_mut(0).get_record_system_mut();
+        let held = record_system.records()
+            .zip(record_system.get_record_params())
+            .map(|(lines, params)| (vec![line(0.0, 1.0, lines[0].start.y)], params.clone()))
+            .collect();
+        record_system.replace_records(held);
+
+        let rendered = timeline.get_composition().render_samples_f32();
+        assert!(rendered.iter().any(|sample| *sample != 0.0));
+        for sensitivity in [0.0, 4.0] {
+            timeline.get_composition_mut().set_velocity_sensitivity(sensitivity);
+            assert_eq!(timeline.get_composition().render_samples_f32(), rendered);
         }
+    }
 
-        Some(Duration::from_secs_f32(max_samples as f32 / self.sample_rate as f32))
+    #[test]
+    fn removed_records_arent_selected() {
+        let mut composition = Composition::default();
+        composition.get_track_mut(0).push_record(vec![line(0.0, 1.0, 0.0)], StrokeParams::default());
+        let mut timeline = Timeline::with_composition(composition);
+        assert_eq!(timeline.get_selected_record(), None);
+
+        timeline.drawing_system.set_selected_record(Some(0));
+        assert_eq!(timeline.get_selected_record(), Some(0));
+
+        timeline.get_active_track_mut().get_record_system_mut().remove_record(0);
+        assert_eq!(timeline.get_selected_record(), None);
     }
-}
\ No newline at end of file
+}
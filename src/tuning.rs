@@ -0,0 +1,138 @@
+/// Concert pitch used when nothing else is configured.
+pub const DEFAULT_TUNING_A4: f32 = 440.0;
+
+/// Pitch in semitones drawn at world height `y`, 0 is the C above A4.
+pub fn pitch_from_y(y: f32) -> f32 {
+    y + 0.5
+}
+
+/// MIDI note number of pitch 0.
+pub const MIDI_NOTE_PITCH_ZERO: u8 = 72;
+
+/// World height `pitch` is drawn at, the inverse of [pitch_from_y].
+pub fn y_from_pitch(pitch: f32) -> f32 {
+    pitch - 0.5
+}
+/// World height a MIDI note is drawn at, see [y_from_pitch].
+pub fn y_from_midi_note(note: u8) -> f32 {
+    y_from_pitch(note as f32 - MIDI_NOTE_PITCH_ZERO as f32)
+}
+
+/// Name of a pitch class, 0 is C.
+pub const fn pitch_class_name(pitch_class: u8) -> &'static str {
+    const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    NAMES[pitch_class as usize % 12]
+}
+/// Scientific pitch name of a MIDI note, like `C4` for note 60.
+pub fn midi_note_name(note: u8) -> String {
+    format!("{}{}", pitch_class_name(note), note as i32 / 12 - 1)
+}
+
+/// Equal tempered frequency of `pitch` (see [pitch_from_y]) relative to `tuning_a4`.
+pub fn frequency_from_pitch(pitch: f32, tuning_a4: f32) -> f32 {
+    tuning_a4 * f32::powf(2.0, (pitch + 3.0) / 12.0)
+}
+
+/// How the 12 degrees of an octave are tuned relative to the tonic.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub enum Temperament {
+    #[default] EqualTemperament,
+    /// 5-limit just intonation.
+    JustIntonation,
+    Pythagorean,
+    /// Cents of each degree above the tonic, like a 12 note scala file.
+    Custom([f32; 12]),
+}
+impl Temperament {
+    const JUST_INTONATION_CENTS: [f32; 12] = [
+        0.0, 111.73, 203.91, 315.64, 386.31, 498.04, 590.22, 701.96, 813.69, 884.36, 1017.60, 1088.27,
+    ];
+    const PYTHAGOREAN_CENTS: [f32; 12] = [
+        0.0, 90.22, 203.91, 294.13, 407.82, 498.04, 611.73, 701.96, 792.18, 905.87, 996.09, 1109.78,
+    ];
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::EqualTemperament => Self::JustIntonation,
+            Self::JustIntonation => Self::Pythagorean,
+            Self::Pythagorean | Self::Custom(_) => Self::EqualTemperament,
+        }
+    }
+
+    /// Frequency of `pitch` (see [pitch_from_y]) with `tonic` as the pitch class (0 is C) the degrees are counted from.  
+    /// Fractional pitches are interpolated between the neighbouring degrees in cents.
+    pub fn frequency(&self, pitch: f32, tuning_a4: f32, tonic: u8) -> f32 {
+        let cents = match self {
+            Self::EqualTemperament => return frequency_from_pitch(pitch, tuning_a4),
+            Self::JustIntonation => &Self::JUST_INTONATION_CENTS,
+            Self::Pythagorean => &Self::PYTHAGOREAN_CENTS,
+            Self::Custom(cents) => cents,
+        };
+
+        let relative = pitch - tonic as f32;
+        let octave = (relative / 12.0).floor();
+        let degree = relative - octave * 12.0;
+
+        let index = (degree as usize).min(11);
+        let next_cents = if index == 11 { 1200.0 } else { cents[index + 1] };
+        let degree_cents = cents[index] + (next_cents - cents[index]) * (degree - index as f32);
+
+        frequency_from_pitch(tonic as f32 + octave * 12.0, tuning_a4) * f32::powf(2.0, degree_cents / 1200.0)
+    }
+}
+
+/// Pitch classes drawn pitches snap to when rendering, counted in semitones above the tonic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scale {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    Dorian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+}
+impl Scale {
+    /// The scale after this one, `None` after the last so cycling can turn snapping off.
+    pub const fn next(&self) -> Option<Self> {
+        match self {
+            Self::Major => Some(Self::NaturalMinor),
+            Self::NaturalMinor => Some(Self::HarmonicMinor),
+            Self::HarmonicMinor => Some(Self::Dorian),
+            Self::Dorian => Some(Self::MajorPentatonic),
+            Self::MajorPentatonic => Some(Self::MinorPentatonic),
+            Self::MinorPentatonic => Some(Self::Blues),
+            Self::Blues => None,
+        }
+    }
+
+    /// Semitones above the tonic, ascending and starting at 0.
+    pub const fn get_degrees(&self) -> &'static [u8] {
+        match self {
+            Self::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Self::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            Self::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Self::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Self::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Self::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Self::Blues => &[0, 3, 5, 6, 7, 10],
+        }
+    }
+
+    /// Nearest in-scale pitch to `pitch` (see [pitch_from_y]) with `tonic` as the pitch class (0 is C) of degree 0.
+    pub fn snap(&self, pitch: f32, tonic: u8) -> f32 {
+        let relative = pitch - tonic as f32;
+        let octave = (relative / 12.0).floor();
+        let degree = relative - octave * 12.0;
+
+        // The tonic of the next octave is a candidate too, so pitches just below it don't fall back down.
+        let nearest = self.get_degrees()
+            .iter()
+            .map(|degree| *degree as f32)
+            .chain([12.0])
+            .min_by(|a, b| (a - degree).abs().total_cmp(&(b - degree).abs()))
+            .unwrap_or(0.0);
+
+        tonic as f32 + octave * 12.0 + nearest
+    }
+}